@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// With the embed-assets feature, every file in public/ is compiled into the binary.
+// Generates a table of (path, contents) that src/static_files.rs includes, which is empty without the feature
+fn main() {
+    println!("cargo:rerun-if-changed=public");
+
+    let mut files = Vec::new();
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_some() {
+        let public = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("public");
+        collect_files(&public, "", &mut files);
+    }
+    files.sort();
+
+    let mut code = String::from("pub static EMBEDDED_FILES: &[EmbeddedFile] = &[\n");
+    for (name, path) in files {
+        code.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", name, path));
+    }
+    code.push_str("];\n");
+
+    let output = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded_files.rs");
+    fs::write(output, code).expect("Cannot write the embedded file table!");
+}
+
+// Find every file below a directory, named by its path from public/ with / between directories
+fn collect_files(directory: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) {
+    for entry in fs::read_dir(directory).expect("Cannot read the public directory!") {
+        let entry = entry.unwrap();
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_files(&path, &format!("{}/", name), files);
+        } else {
+            files.push((name, path));
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Shows the errors returned when a form is rejected.
+// The API sends one "field: message" line per problem; each is shown under its field,
+// and anything that is not about a visible field is shown in an alert instead
+function showFormErrors(form, responseText) {
+    form.querySelectorAll('.field-error').forEach(function(element) {
+        element.remove();
+    });
+
+    const otherErrors = [];
+    responseText.split('\n').forEach(function(line) {
+        const separator = line.indexOf(': ');
+        const field = separator > 0 ? form.elements[line.substring(0, separator)] : null;
+
+        if (!field || field.type === 'hidden') {
+            if (line.trim() !== '') {
+                otherErrors.push(line);
+            }
+            return;
+        }
+
+        const error = document.createElement('div');
+        error.className = 'field-error';
+        error.innerText = line.substring(separator + 2);
+        field.insertAdjacentElement('afterend', error);
+    });
+
+    if (otherErrors.length > 0) {
+        alert(otherErrors.join('\n'));
+    }
+}
@@ -0,0 +1,36 @@
+// Date and time inputs show the browser's local time, but the API stores timestamps with a timezone offset.
+// Every datetime-local input in a form is sent as RFC 3339 in the browser's offset,
+// and toLocalInput turns a timestamp from the API back into a value for one of those inputs
+function pad(number) {
+    return String(Math.floor(Math.abs(number))).padStart(2, '0');
+}
+
+function toRfc3339(localValue) {
+    const date = new Date(localValue); // Read as local time
+    if (!localValue || isNaN(date)) {
+        return localValue; // Let the API say what is wrong with it
+    }
+    const offset = -date.getTimezoneOffset(); // Minutes ahead of UTC
+    const seconds = localValue.length === 16 ? ':00' : ''; // Seconds are left out when they are zero
+    return `${localValue}${seconds}${offset >= 0 ? '+' : '-'}${pad(offset / 60)}:${pad(offset % 60)}`;
+}
+
+function toLocalInput(timestamp) {
+    const date = new Date(timestamp);
+    if (isNaN(date)) {
+        return '';
+    }
+    return `${date.getFullYear()}-${pad(date.getMonth() + 1)}-${pad(date.getDate())}` +
+        `T${pad(date.getHours())}:${pad(date.getMinutes())}:${pad(date.getSeconds())}`;
+}
+
+document.addEventListener('htmx:configRequest', function(event) {
+    if (!event.detail.elt.querySelectorAll) {
+        return;
+    }
+    event.detail.elt.querySelectorAll('input[type="datetime-local"]').forEach(function(input) {
+        if (input.name) {
+            event.detail.parameters[input.name] = toRfc3339(input.value);
+        }
+    });
+});
@@ -0,0 +1,277 @@
+// Threshold alerts on incoming data points.
+// Each rule is checked against the data points of every test it covers as they arrive. An alert fires once
+// the threshold has been crossed for the rule's duration, and resolves at the first data point that no longer crosses it
+use std::collections::HashMap;
+use crate::keyed_vector::KeyedVector;
+use crate::models::{AlertChange, AlertEvent, AlertRule, DataPoint};
+use crate::timestamp::Timestamp;
+
+pub const ALERT_RULES_PATH: &str = "./data/alerts/rules";
+pub const ALERT_EVENTS_PATH: &str = "./data/alerts/events"; // Every alert that fired or resolved, oldest first
+
+// Where one rule stands for one test
+#[derive(Clone, Debug, Default, PartialEq)]
+struct AlertState {
+    crossed_since: Option<Timestamp>, // The first of the data points in a row that crossed the threshold
+    firing: bool,
+    checked_up_to: Option<Timestamp>, // Data points at or before this have been checked, e.g. ones imported again
+    last: Option<(f64, f64)>, // The latest value and threshold, for resolving an alert whose test is deleted
+}
+
+// The state of every rule for every test it covers, keyed by (rule ID, server ID, test ID).
+// Which alerts are firing is rebuilt from the event log on start, how long a threshold has been crossed is not
+#[derive(Default)]
+pub struct AlertStates {
+    states: HashMap<(String, String, String), AlertState>,
+}
+
+impl AlertStates {
+    // The states left by the event log, oldest event first
+    pub fn from_events(events: &[AlertEvent]) -> Self {
+        let mut states = Self::default();
+        for event in events {
+            let state = states.states.entry((event.get_rule_id(), event.get_server_id(), event.get_test_id())).or_default();
+            state.firing = event.get_change() == AlertChange::Fired;
+            state.crossed_since = if state.firing { Some(event.get_time()) } else { None };
+            state.checked_up_to = Some(event.get_time());
+            state.last = Some((event.get_value(), event.get_limit()));
+        }
+        states
+    }
+
+    // Check data points just added to a test, oldest first, returning the alerts that fired or resolved.
+    // server_ram is in MB, for rules with a threshold relative to it
+    pub fn check(&mut self, rules: &[AlertRule], server_id: &str, server_ram: u32, test_id: &str, data_points: &[DataPoint]) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for rule in rules.iter().filter(|rule| rule.covers(server_id, test_id)) {
+            let state = self.states.entry((rule.get_id(), server_id.to_string(), test_id.to_string())).or_default();
+            let limit = rule.limit(server_ram);
+
+            for data_point in data_points {
+                let time = data_point.get_time();
+                if state.checked_up_to.is_some_and(|checked| time <= checked) {
+                    continue;
+                }
+                state.checked_up_to = Some(time);
+
+                let Some(value) = rule.value_of(data_point) else {
+                    continue; // Not recorded, so neither crossed nor back to normal
+                };
+                state.last = Some((value, limit));
+                let change = if rule.is_crossed(value, server_ram) {
+                    let since = *state.crossed_since.get_or_insert(time);
+                    if state.firing || time.unix_seconds() - since.unix_seconds() < rule.get_duration() as i64 {
+                        continue;
+                    }
+                    state.firing = true;
+                    AlertChange::Fired
+                } else {
+                    state.crossed_since = None;
+                    if !state.firing {
+                        continue;
+                    }
+                    state.firing = false;
+                    AlertChange::Resolved
+                };
+                events.push(AlertEvent::new(time, rule.get_id(), server_id.to_string(), test_id.to_string(), change, value, limit));
+            }
+        }
+        events.sort_by_key(|event| event.get_time()); // Kept in order of the rules otherwise
+        events
+    }
+
+    // Forget a rule, after it is deleted
+    pub fn forget_rule(&mut self, rule_id: &str) {
+        self.states.retain(|(rule, _, _), _| rule != rule_id);
+    }
+
+    // Move every rule's state for a test, or for each test of a server if test_id is None, to its new ID after a rename.
+    // Alerts that are firing are resolved under the old ID and fire again under the new one at time,
+    // so the event log, which the states are rebuilt from, agrees
+    pub fn rename(&mut self, server_id: &str, test_id: Option<&str>, new_id: &str, time: Timestamp) -> Vec<AlertEvent> {
+        let mut keys: Vec<_> = self.states.keys().filter(|key| covers(key, server_id, test_id)).cloned().collect();
+        keys.sort(); // The map has no order
+        let mut events = Vec::new();
+        for key in keys {
+            let state = self.states.remove(&key).unwrap();
+            let (rule, server, test) = key;
+            let new_key = match test_id {
+                Some(_) => (rule.clone(), server.clone(), new_id.to_string()),
+                None => (rule.clone(), new_id.to_string(), test.clone()),
+            };
+            if let (true, Some((value, limit))) = (state.firing, state.last) {
+                events.push(AlertEvent::new(time, rule, server, test, AlertChange::Resolved, value, limit));
+                let (rule, server, test) = new_key.clone();
+                events.push(AlertEvent::new(time, rule, server, test, AlertChange::Fired, value, limit));
+            }
+            self.states.insert(new_key, state);
+        }
+        events
+    }
+
+    // Forget every rule's state for a test, or for each test of a server if test_id is None, after it is deleted.
+    // Alerts that were firing are resolved at time, so they do not stay open in the event log
+    pub fn forget(&mut self, server_id: &str, test_id: Option<&str>, time: Timestamp) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        self.states.retain(|key, state| {
+            if !covers(key, server_id, test_id) {
+                return true;
+            }
+            if let (true, Some((value, limit))) = (state.firing, state.last) {
+                let (rule, server, test) = key.clone();
+                events.push(AlertEvent::new(time, rule, server, test, AlertChange::Resolved, value, limit));
+            }
+            false
+        });
+        events.sort_by_key(|event| (event.get_server_id(), event.get_test_id(), event.get_rule_id())); // The map has no order
+        events
+    }
+}
+
+// Whether a state is for a test, or for one of a server's tests if test_id is None
+fn covers((_, server, test): &(String, String, String), server_id: &str, test_id: Option<&str>) -> bool {
+    server == server_id && test_id.is_none_or(|test_id| test == test_id)
+}
+
+// Point the rules for a test, or for a server if test_id is None, at its new ID after a rename,
+// or remove them if new_id is None after it is deleted. Returns whether any rule changed
+pub async fn update_rules(rules: &mut KeyedVector<AlertRule>, server_id: &str, test_id: Option<&str>, new_id: Option<&str>) -> bool {
+    let affected: Vec<AlertRule> = rules
+        .as_slice()
+        .await
+        .iter()
+        .filter(|rule| rule.get_server_id() == server_id && test_id.is_none_or(|test_id| rule.get_test_id().as_deref() == Some(test_id)))
+        .cloned()
+        .collect();
+
+    for mut rule in affected.iter().cloned() {
+        rules.remove_by_key(&rule.get_id()).await;
+        if let Some(new_id) = new_id {
+            match test_id {
+                Some(_) => rule.set_test_id(Some(new_id.to_string())),
+                None => rule.set_server_id(new_id.to_string()),
+            }
+            let _ = rules.insert(rule).await; // Same key as before, so it cannot conflict
+        }
+    }
+    !affected.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_rules, AlertStates};
+    use std::collections::BTreeMap;
+    use crate::keyed_vector::KeyedVector;
+    use crate::models::{AlertChange, AlertRule, Comparator, DataPoint};
+    use crate::timestamp::Timestamp;
+
+    fn data_point(seconds: i64, ram: u32, cpu: u32) -> DataPoint {
+        DataPoint::new(Timestamp::from_unix_seconds(seconds).unwrap(), ram, cpu)
+    }
+
+    fn rule(id: &str, test_id: Option<&str>, metric: &str, comparator: Comparator, threshold: f64, of_server_ram: bool, duration: u32) -> AlertRule {
+        AlertRule::new(id.to_string(), "web".to_string(), test_id.map(String::from), metric.to_string(), comparator, threshold, of_server_ram, duration)
+    }
+
+    fn is_firing(states: &AlertStates, rule_id: &str) -> bool {
+        states.states[&(rule_id.to_string(), "web".to_string(), "load".to_string())].firing
+    }
+
+    fn changes(events: &[crate::models::AlertEvent]) -> Vec<(i64, AlertChange)> {
+        events.iter().map(|event| (event.get_time().unix_seconds(), event.get_change())).collect()
+    }
+
+    #[test]
+    fn test_fires_after_duration() {
+        let rules = [rule("busy", None, "cpu", Comparator::AtLeast, 90.0, false, 60)];
+        let mut states = AlertStates::default();
+
+        // Crossed for 50 s, dips, then crossed for 60 s
+        let points = [data_point(0, 0, 95), data_point(50, 0, 99), data_point(60, 0, 10), data_point(70, 0, 90), data_point(130, 0, 91)];
+        let events = states.check(&rules, "web", 1024, "load", &points);
+        assert_eq!(changes(&events), vec![(130, AlertChange::Fired)]);
+        assert_eq!((events[0].get_value(), events[0].get_limit()), (91.0, 90.0));
+        assert!(is_firing(&states, "busy"));
+
+        // Still firing, so nothing new until it resolves
+        let events = states.check(&rules, "web", 1024, "load", &[data_point(140, 0, 100), data_point(150, 0, 50)]);
+        assert_eq!(changes(&events), vec![(150, AlertChange::Resolved)]);
+        assert!(!is_firing(&states, "busy"));
+    }
+
+    #[test]
+    fn test_relative_to_server_ram() {
+        let rules = [rule("full", Some("load"), "ram", Comparator::Above, 90.0, true, 0)];
+        let mut states = AlertStates::default();
+        assert!(states.check(&rules, "web", 2000, "other", &[data_point(0, 1900, 0)]).is_empty()); // Another test
+        let events = states.check(&rules, "web", 2000, "load", &[data_point(0, 1800, 0), data_point(1, 1801, 0)]);
+        assert_eq!(changes(&events), vec![(1, AlertChange::Fired)]);
+        assert_eq!(events[0].get_limit(), 1800.0);
+    }
+
+    #[test]
+    fn test_custom_metric_and_old_points() {
+        let rules = [rule("slow", None, "latency", Comparator::Above, 100.0, false, 0)];
+        let mut states = AlertStates::default();
+        let mut slow = data_point(10, 0, 0);
+        slow.set_metrics(BTreeMap::from([("latency".to_string(), 250.0)]));
+
+        assert_eq!(changes(&states.check(&rules, "web", 0, "load", &[slow.clone()])), vec![(10, AlertChange::Fired)]);
+        // Points without the metric change nothing, and points already checked are not checked again
+        assert!(states.check(&rules, "web", 0, "load", &[data_point(5, 0, 0), data_point(20, 0, 0)]).is_empty());
+        assert!(is_firing(&states, "slow"));
+    }
+
+    #[test]
+    fn test_from_events() {
+        let rules = [rule("busy", None, "cpu", Comparator::Above, 50.0, false, 0)];
+        let mut states = AlertStates::default();
+        let events = states.check(&rules, "web", 0, "load", &[data_point(0, 0, 60)]);
+
+        // After a restart the alert is still firing, so it only resolves
+        let mut states = AlertStates::from_events(&events);
+        assert!(states.check(&rules, "web", 0, "load", &[data_point(0, 0, 70), data_point(10, 0, 80)]).is_empty());
+        assert_eq!(changes(&states.check(&rules, "web", 0, "load", &[data_point(20, 0, 10)])), vec![(20, AlertChange::Resolved)]);
+
+        assert!(states.forget("web", Some("load"), Timestamp::from_unix_seconds(30).unwrap()).is_empty()); // Already resolved
+        assert!(states.states.is_empty());
+    }
+
+    #[test]
+    fn test_rename_and_forget() {
+        let rules = [rule("busy", None, "cpu", Comparator::Above, 50.0, false, 0)];
+        let mut states = AlertStates::default();
+        states.check(&rules, "web", 0, "load", &[data_point(0, 0, 60)]);
+        states.check(&rules, "web", 0, "soak", &[data_point(0, 0, 10)]);
+
+        // Still firing under the new ID, so it resolves there
+        let moved = states.rename("web", Some("load"), "stress", Timestamp::from_unix_seconds(5).unwrap());
+        assert_eq!(changes(&moved), vec![(5, AlertChange::Resolved), (5, AlertChange::Fired)]);
+        assert_eq!((moved[0].get_test_id(), moved[1].get_test_id()), ("load".to_string(), "stress".to_string()));
+        assert_eq!(changes(&states.check(&rules, "web", 0, "stress", &[data_point(10, 0, 10)])), vec![(10, AlertChange::Resolved)]);
+
+        // Deleting a test with an alert firing resolves it, including after its server is renamed
+        states.check(&rules, "web", 0, "stress", &[data_point(20, 0, 90)]);
+        assert_eq!(states.rename("web", None, "api", Timestamp::from_unix_seconds(25).unwrap()).len(), 2);
+        let resolved = states.forget("api", None, Timestamp::from_unix_seconds(30).unwrap());
+        assert_eq!(changes(&resolved), vec![(30, AlertChange::Resolved)]);
+        assert_eq!((resolved[0].get_test_id(), resolved[0].get_value(), resolved[0].get_limit()), ("stress".to_string(), 90.0, 50.0));
+        assert!(states.states.is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_update_rules() {
+        let mut rules = KeyedVector::new();
+        let _ = rules.insert(rule("a", Some("load"), "cpu", Comparator::Above, 50.0, false, 0)).await;
+        let _ = rules.insert(rule("b", None, "cpu", Comparator::Above, 50.0, false, 0)).await;
+
+        assert!(update_rules(&mut rules, "web", Some("load"), Some("soak")).await);
+        assert_eq!(rules.get_by_key("a").await.unwrap().get_test_id(), Some("soak".to_string()));
+        assert!(!update_rules(&mut rules, "web", Some("load"), None).await);
+
+        assert!(update_rules(&mut rules, "web", None, Some("web_2")).await);
+        assert!(rules.as_slice().await.iter().all(|rule| rule.get_server_id() == "web_2"));
+        assert!(update_rules(&mut rules, "web_2", None, None).await);
+        assert_eq!(rules.length, 0);
+    }
+}
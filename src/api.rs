@@ -1,146 +1,211 @@
-use std::char::from_digit;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
-use rocket::{delete, get, post, FromForm, State};
-use rocket::form::Form;
-use rocket::http::Status;
+use rocket::{delete, get, post, FromForm, Shutdown, State};
+use rocket::data::{Data, ToByteUnit};
+use rocket::form::{Contextual, Form};
+use rocket::http::{ContentType, Status};
 use rocket::response::content::RawHtml;
-use rocket::tokio::fs::{create_dir_all, rename};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::fs::{remove_file, rename};
 use rocket::tokio::sync::Mutex;
-use crate::models::{DataPoint, Position, ScheduleEntry, Server, SiteData, Test, User};
+use crate::models::{schema_path, AlertChange, AlertEvent, AlertRule, Comparator, DataPoint, Metric, MetricKind, Position, ScheduleEntry, Server, SiteData, Test, User, Webhook, WebhookEvent, RESERVED_METRIC_NAMES};
+use crate::pagination::{ListOptions, Pager, TablePage};
+use crate::query::{Query, Searchable};
+use crate::html::{html, url, Html};
+use crate::templates;
+use crate::chart;
+use crate::alerts::{update_rules, ALERT_EVENTS_PATH, ALERT_RULES_PATH};
+use crate::my_vector::MyVector;
+use crate::json::Json;
+use crate::notifications::Notifier;
+use crate::webhooks::{alert_data, check_url, ingested_data, schedule_data, test_data, Webhooks, WEBHOOKS_PATH};
+use crate::events::{Change, DataPointEvent, EventBus};
+use crate::export::{Download, Export, ExportFormat};
+use crate::import::{import_results, read_report, ReportFormat};
+use crate::ingest::{read_data_points, IngestFormat};
+use crate::timestamp::Timestamp;
+use crate::validation::{email_address, identifier, text, validated, MAX_ALERT_DURATION, MAX_COMMENT_LENGTH, MAX_CPU_CORES, MAX_CPU_PERCENT, MAX_NAME_LENGTH, MAX_RAM, MAX_UNIT_LENGTH, MAX_URL_LENGTH};
+
+// An error status with a message explaining it, which the htmx forms show to the user
+type ApiError = (Status, String);
+
+// Parse the search box of a list endpoint, e.g. name:web ram>=4096 comment:"cpu spike"
+fn parse_search<T: Searchable>(search: &Option<String>) -> Result<Query, RawHtml<String>> {
+    parse_query::<T>(search).map_err(|error| message(&error))
+}
+
+fn parse_query<T: Searchable>(search: &Option<String>) -> Result<Query, String> {
+    parse_query_with::<T>(search, &[])
+}
+
+// Also allowing fields only some items have, e.g. a test's custom metrics for its data points
+fn parse_query_with<T: Searchable>(search: &Option<String>, extra_fields: &[String]) -> Result<Query, String> {
+    let query = match search {
+        Some(search) => Query::from_str(search)?,
+        None => Query::default(),
+    };
+    query.check_fields_with::<T>(extra_fields)?;
+    Ok(query)
+}
+
+// A message shown in place of a table, which may contain user input
+fn message(text: &str) -> RawHtml<String> {
+    RawHtml(Html::text(text).into_string())
+}
 
-#[get("/get_users?<search>")]
+const USER_SORT_FIELDS: &[&str] = &["username", "forename", "surname", "position", "email"];
+
+fn compare_users(a: &User, b: &User, field: &str) -> Ordering {
+    match field {
+        "forename" => a.get_forename().cmp(&b.get_forename()),
+        "surname" => a.get_surname().cmp(&b.get_surname()),
+        "position" => a.get_position().to_string().cmp(&b.get_position().to_string()),
+        "email" => a.get_email().cmp(&b.get_email()),
+        _ => a.get_username().cmp(&b.get_username()),
+    }
+}
+
+#[get("/get_users?<search>&<list..>")]
 pub async fn get_users(
     site_data: &State<Arc<Mutex<SiteData>>>,
     search: Option<String>,
-) -> RawHtml<String> {
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    let query = parse_search::<User>(&search)?;
     let site_data = site_data.lock().await;
-    let mut output = String::new();
-    output.push_str("<table>");
-    output.push_str("<tr><th>Username</th><th>Forename</th><th>Surname</th><th>Position</th></tr>\n");
 
-    let users = if let Some(ref search_term) = search {
+    let mut users = if !query.is_empty() {
         site_data
             .users
-            .search_all(|user| user.get_username().contains(search_term))
+            .search_all(|user| query.matches(user))
             .await
     } else {
-        site_data.users.clone()
+        (*site_data.users).clone()
     };
 
-    for i in 0..users.length {
-        let user = users.get(i).await.unwrap();
-        let username = user.get_username();
-
-        output.push_str(&format!(
-            "<tr onclick=\"window.location.href='/manage-user?username={}'\" style=\"cursor:pointer\">",
-            username
-        ));
-
-        output.push_str(&format!(
-            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
-            username,
-            user.get_forename().unwrap_or_default(),
-            user.get_surname().unwrap_or_default(),
-            user.get_position()
-        ));
+    if !list.is_natural_order() { // Users are stored in username order
+        let field = list.sort_field(USER_SORT_FIELDS);
+        users.quick_sort_by(|a, b| list.order(compare_users(a, b, field))).await;
+    }
 
-        output.push_str("</tr>\n");
+    let pager = Pager::new("/api/get_users", search.as_deref(), &list, USER_SORT_FIELDS, users.length);
+    let mut output = html!("<table>");
+    output.push(html!("<tr>"));
+    output.push(pager.header("username", "Username"));
+    output.push(pager.header("forename", "Forename"));
+    output.push(pager.header("surname", "Surname"));
+    output.push(pager.header("position", "Position"));
+    output.push(pager.header("email", "Email"));
+    output.push(html!("</tr>\n"));
+
+    for user in users.page(list.offset(), list.per_page()).await {
+        output.push(templates::user_row(user));
     }
-    output.push_str("</table>");
+    output.push(html!("</table>"));
 
-    RawHtml(output)
+    Ok(pager.finish(output))
 }
 
-#[get("/get_servers?<search>")]
+#[get("/get_servers?<search>&<list..>")]
 pub async fn get_servers(
     site_data: &State<Arc<Mutex<SiteData>>>,
     search: Option<String>,
-) -> RawHtml<String> {
-    get_server_table(site_data, "/test-list", search).await
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    get_server_table(site_data, "/api/get_servers", "/test-list", search, list).await
 }
 
-#[get("/get_servers_manager?<search>")]
+#[get("/get_servers_manager?<search>&<list..>")]
 pub async fn get_servers_manager(
     site_data: &State<Arc<Mutex<SiteData>>>,
     search: Option<String>,
-) -> RawHtml<String> {
-    get_server_table(site_data, "/manage-server", search).await
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    get_server_table(site_data, "/api/get_servers_manager", "/manage-server", search, list).await
+}
+
+const SERVER_SORT_FIELDS: &[&str] = &["id", "name", "created_by", "ram", "cpu"];
+
+fn compare_servers(a: &Server, b: &Server, field: &str) -> Ordering {
+    match field {
+        "name" => a.get_name().cmp(&b.get_name()),
+        "created_by" => a.get_created_by().cmp(&b.get_created_by()),
+        "ram" => a.get_ram().cmp(&b.get_ram()),
+        "cpu" => a.get_cpu().cmp(&b.get_cpu()),
+        _ => a.get_id().cmp(&b.get_id()),
+    }
 }
 
 async fn get_server_table(
     site_data: &State<Arc<Mutex<SiteData>>>,
+    base_url: &str,
     url_prefix: &str,
     search: Option<String>,
-) -> RawHtml<String> {
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    let query = parse_search::<Server>(&search)?;
     let site_data = site_data.lock().await;
-    let mut output = String::new();
 
-    output.push_str("<table>");
-    output.push_str("<tr>");
-    output.push_str("<th>ID</th>");
-    output.push_str("<th>Name</th>");
-    output.push_str("<th>Created By</th>");
-    output.push_str("<th>RAM (MB)</th>");
-    output.push_str("<th>CPU (Cores)</th>");
-    output.push_str("<th>Number of Tests</th>");
-    output.push_str("</tr>");
-
-    let servers = if let Some(ref search_term) = search {
+    let mut servers = if !query.is_empty() {
         site_data
             .servers
-            .search_all(|server| server.get_id().contains(search_term))
+            .search_all(|server| query.matches(server))
             .await
     } else {
-        site_data.servers.clone()
+        (*site_data.servers).clone()
     };
 
-    for i in 0..servers.length {
-        let mut server = servers.get(i).await.unwrap();
-        server.load_tests().await;
-        let length = server.tests.length;
-
-        output.push_str(&format!(
-            "<tr onclick=\"window.location.href='{}?server_id={}'\" style=\"cursor:pointer\">",
-            url_prefix,
-            server.get_id(),
-        ));
-
-        output.push_str(&format!("<td>{}</td>", server.get_id()));
-        output.push_str(&format!("<td>{}</td>", server.get_name()));
-        output.push_str(&format!("<td>{}</td>", server.get_created_by()));
-        output.push_str(&format!("<td>{}</td>", server.get_ram()));
-        output.push_str(&format!("<td>{}</td>", server.get_cpu()));
-        output.push_str(&format!("<td>{}</td>", length));
+    if !list.is_natural_order() { // Servers are stored in ID order
+        let field = list.sort_field(SERVER_SORT_FIELDS);
+        servers.quick_sort_by(|a, b| list.order(compare_servers(a, b, field))).await;
+    }
 
-        output.push_str("</tr>");
+    let pager = Pager::new(base_url, search.as_deref(), &list, SERVER_SORT_FIELDS, servers.length);
+    let mut output = html!("<table>");
+    output.push(html!("<tr>"));
+    output.push(pager.header("id", "ID"));
+    output.push(pager.header("name", "Name"));
+    output.push(pager.header("created_by", "Created By"));
+    output.push(pager.header("ram", "RAM (MB)"));
+    output.push(pager.header("cpu", "CPU (Cores)"));
+    output.push(html!("<th>Number of Tests</th>"));
+    output.push(html!("</tr>"));
+
+    for server in servers.page(list.offset(), list.per_page()).await {
+        let mut server = server.clone();
+        server.load_tests().await; // Only load the tests for servers on this page
+        output.push(templates::server_row(&server, url_prefix));
     }
 
-    output.push_str("</table>");
+    output.push(html!("</table>"));
 
-    RawHtml(output)
+    Ok(pager.finish(output))
 }
 
 #[get("/get_server_info/<server_id>")]
 pub async fn get_server_info(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String) -> String {
     let site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id() == server_id).await {
+    let server = match site_data.servers.get_by_key(&server_id).await {
         Some(server) => server,
         None => return "Server Not Found".to_string(),
     };
 
-    let server = site_data.servers.get(server_index).await.unwrap();
     let mut output = String::new();
 
     output.push_str(server.get_id().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(server.get_name().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(server.get_created_by().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(server.get_ram().to_string().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(server.get_cpu().to_string().as_str());
 
     output
@@ -150,157 +215,376 @@ pub async fn get_server_info(site_data: &State<Arc<Mutex<SiteData>>>, server_id:
 pub async fn get_user_info(site_data: &State<Arc<Mutex<SiteData>>>, username: String) -> String {
     let site_data = site_data.lock().await;
 
-    let user_index = match site_data.users.search(|a| a.get_username() == username).await {
-        Some(username_index) => username_index,
+    let user = match site_data.users.get_by_key(&username).await {
+        Some(user) => user,
         None => return "User Not Found".to_string(),
     };
 
-    let user = site_data.users.get(user_index).await.unwrap();
     let mut output = String::new();
 
     output.push_str(user.get_username().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(user.get_forename().unwrap_or_default().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(user.get_surname().unwrap_or_default().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(user.get_position().to_string().as_str());
+    output.push(',');
+    output.push_str(user.get_email().unwrap_or_default().as_str());
 
     output
 }
 
-#[get("/get_tests/<server_id>?<search>")]
+const TEST_SORT_FIELDS: &[&str] = &["id", "data_points"];
+
+fn compare_tests(a: &Test, b: &Test, field: &str) -> Ordering {
+    match field {
+        "data_points" => a.data.length.cmp(&b.data.length),
+        _ => a.get_id().cmp(&b.get_id()),
+    }
+}
+
+#[get("/get_tests/<server_id>?<search>&<list..>")]
 pub async fn get_tests(
     site_data: &State<Arc<Mutex<SiteData>>>,
     server_id: String,
     search: Option<String>,
-) -> RawHtml<String> {
-    let site_data = site_data.lock().await;
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    let query = parse_search::<Test>(&search)?;
+    let mut site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id() == server_id).await {
+    let mut server = match site_data.servers.get_by_key(&server_id).await {
         Some(server) => server,
-        None => return RawHtml("Server not found!".to_string()),
+        None => return Err(message("Server not found!")),
     };
-
-    let mut server = site_data.servers.get(server_index).await.unwrap();
     server.load_tests().await;
 
-    let mut output = String::new();
-    output.push_str("<table>");
-    output.push_str("<tr>");
-    output.push_str("<th>ID</th>");
-    output.push_str("<th>Num. of Data Points</th>");
-    output.push_str("</tr>");
-
-    let tests = if let Some(ref search_term) = search {
+    let mut tests = if !query.is_empty() {
         server
             .tests
-            .search_all(|test| test.get_id().contains(search_term))
+            .search_all(|test| query.matches(test))
             .await
     } else {
-        server.tests.clone()
+        (*server.tests).clone()
     };
 
-    for i in 0..tests.length {
-        let test = tests.get(i).await.unwrap();
-        let test_id = test.get_id();
-        let num_data_points = test.data.length;
-
-        let row_link = format!("/test-data?server_id={}&test_id={}", server_id, test_id);
-        output.push_str(&format!(
-            "<tr onclick=\"window.location.href='{}'\" style=\"cursor:pointer\">",
-            row_link
-        ));
+    if !list.is_natural_order() { // Tests are stored in ID order
+        let field = list.sort_field(TEST_SORT_FIELDS);
+        tests.quick_sort_by(|a, b| list.order(compare_tests(a, b, field))).await;
+    }
 
-        output.push_str(&format!("<td>{}</td>", test_id));
-        output.push_str(&format!("<td>{}</td>", num_data_points));
-        output.push_str("</tr>");
+    let base_url = format!("/api/get_tests/{}", server_id);
+    let pager = Pager::new(&base_url, search.as_deref(), &list, TEST_SORT_FIELDS, tests.length);
+    let mut output = html!("<table>");
+    output.push(html!("<tr>"));
+    output.push(pager.header("id", "ID"));
+    output.push(pager.header("data_points", "Num. of Data Points"));
+    output.push(html!("<th>Mean RAM (MB)</th><th>95th % RAM (MB)</th><th>Mean CPU (%)</th><th>95th % CPU (%)</th>"));
+    output.push(html!("</tr>"));
+
+    for test in tests.page(list.offset(), list.per_page()).await {
+        let stats = site_data.stats.get(&server_id, test).await;
+        output.push(templates::test_row(&server_id, test, &stats.ram.summary().await, &stats.cpu.summary().await));
     }
 
-    output.push_str("</table>");
+    output.push(html!("</table>"));
 
-    RawHtml(output)
+    Ok(pager.finish(output))
 }
 
-#[get("/get_test_data/<server_id>/<test_id>?<search>")]
+const DATA_POINT_SORT_FIELDS: &[&str] = &["time", "ram", "cpu", "comment"];
+
+// Any other field is a custom metric, with data points missing a value first
+fn compare_data_points(a: &DataPoint, b: &DataPoint, field: &str) -> Ordering {
+    match field {
+        "time" => a.get_time().cmp(&b.get_time()),
+        "ram" => a.get_ram().cmp(&b.get_ram()),
+        "cpu" => a.get_cpu().cmp(&b.get_cpu()),
+        "comment" => a.get_comment().cmp(&b.get_comment()),
+        metric => a.get_metric(metric).partial_cmp(&b.get_metric(metric)).unwrap_or(Ordering::Equal),
+    }
+}
+
+#[get("/get_test_data/<server_id>/<test_id>?<search>&<list..>")]
 pub async fn get_test_data(
     site_data: &State<Arc<Mutex<SiteData>>>,
     server_id: String,
     test_id: String,
     search: Option<String>,
-) -> RawHtml<String> {
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
     let test = match get_test(site_data.inner(), server_id.clone(), test_id.clone()).await {
         Some(test) => test,
-        None => return RawHtml("Could not find test!".to_string()),
+        None => return Err(message("Could not find test!")),
     };
 
-    let mut output = String::new();
+    let metrics = test.metrics.as_slice().await;
+    let mut sort_fields = DATA_POINT_SORT_FIELDS.to_vec(); // Custom metrics can be searched and sorted by too
+    let metric_names: Vec<String> = metrics.iter().map(Metric::get_name).collect();
+    sort_fields.extend(metric_names.iter().map(String::as_str));
+    let query = parse_query_with::<DataPoint>(&search, &metric_names).map_err(|error| message(&error))?;
+    let matches = |data_point: &DataPoint| query.matches(data_point);
+
+    // Stored in time order, and shown that way unless sorted, in which case every match has to be sorted first.
+    // Offset is where the page starts within data_points
+    let (data_points, offset, total) = if list.is_natural_order() {
+        let (page, total) = test.data.search_page(matches, list.offset(), list.per_page()).await;
+        (page, 0, total)
+    } else {
+        let mut data_points = test.data.search_all(matches).await;
+        let field = list.sort_field(&sort_fields);
+        data_points.quick_sort_by(|a, b| list.order(compare_data_points(a, b, field))).await;
+        let total = data_points.length;
+        (data_points, list.offset(), total)
+    };
 
-    output.push_str("<table>");
-    output.push_str("<tr>");
-    output.push_str("<th>Time</th>");
-    output.push_str("<th>RAM (MB)</th>");
-    output.push_str("<th>CPU (%)</th>");
-    output.push_str("<th>Comment</th>");
-    output.push_str("</tr>");
-
-    for i in 0..test.data.length {
-        if let Some(data_point) = test.data.get(i).await {
-            let time = data_point.get_time();
-
-            if let Some(ref search_term) = search {
-                if !time.contains(search_term) {
-                    continue;
-                }
-            }
+    let base_url = format!("/api/get_test_data/{}/{}", server_id, test_id);
+    let pager = Pager::new(&base_url, search.as_deref(), &list, &sort_fields, total);
+    let mut output = html!("<table>");
+    output.push(html!("<tr>"));
+    output.push(pager.header("time", "Time"));
+    output.push(pager.header("ram", "RAM (MB)"));
+    output.push(pager.header("cpu", "CPU (%)"));
+    output.push(pager.header("comment", "Comment"));
+    for metric in metrics {
+        output.push(pager.header(&metric.get_name(), &metric.label()));
+    }
+    output.push(html!("</tr>"));
 
-            let url = format!(
-                "/manage-datapoint?server_id={}&test_id={}&time={}",
-                server_id, test_id, time
-            );
+    for data_point in data_points.page(offset, list.per_page()).await {
+        output.push(templates::data_point_row(&server_id, &test_id, metrics, data_point));
+    }
+
+    output.push(html!("</table>"));
+
+    Ok(pager.finish(output))
+}
+
+#[get("/get_test_stats/<server_id>/<test_id>")]
+pub async fn get_test_stats(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String, test_id: String) -> RawHtml<String> {
+    // Held from reading the test until it is cached, so no data point can be added in between and missed
+    let mut site_data = site_data.lock().await;
+    let test = match find_test(&site_data, &server_id, &test_id).await {
+        Some(test) => test,
+        None => return message("Could not find test!"),
+    };
+
+    let stats = site_data.stats.get(&server_id, &test).await;
+    let mut columns = vec![
+        ("RAM (MB)".to_string(), stats.ram.summary().await),
+        ("CPU (%)".to_string(), stats.cpu.summary().await),
+    ];
+    for (metric, metric_stats) in &stats.metrics {
+        columns.push((metric.label(), metric_stats.summary().await));
+    }
+    RawHtml(templates::stats_table(&columns).into_string())
+}
+
+// The format of an export, CSV unless given
+fn export_format(format: &Option<String>) -> Result<ExportFormat, ApiError> {
+    ExportFormat::from_str(format.as_deref().unwrap_or("csv")).map_err(|error| (Status::BadRequest, error))
+}
+
+// Download a test's data points, or only those matching a search as used by the data point table
+#[get("/export/<server_id>/<test_id>?<format>&<search>")]
+pub async fn export_test(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    server_id: String,
+    test_id: String,
+    format: Option<String>,
+    search: Option<String>,
+) -> Result<Download, ApiError> {
+    let format = export_format(&format)?;
+    let test = match get_test(site_data.inner(), server_id.clone(), test_id.clone()).await {
+        Some(test) => test,
+        None => return Err((Status::NotFound, format!("Test '{}' does not exist on server '{}'", test_id, server_id))),
+    };
 
-            output.push_str(&format!(
-                "<tr onclick=\"window.location.href='{}'\" style=\"cursor:pointer\">",
-                url
-            ));
+    let metrics: Vec<String> = test.metrics.as_slice().await.iter().map(Metric::get_name).collect();
+    let query = parse_query_with::<DataPoint>(&search, &metrics).map_err(|error| (Status::BadRequest, error))?;
+    let mut export = Export::new(false, metrics);
+    for data_point in test.data.as_slice().await.iter().filter(|data_point| query.matches(*data_point)) {
+        export.add(&test_id, data_point);
+    }
+    Ok(export.download(format, &format!("{}_{}", server_id, test_id)))
+}
 
-            output.push_str(&format!("<td>{}</td>", time));
-            output.push_str(&format!("<td>{}</td>", data_point.get_ram()));
-            output.push_str(&format!("<td>{}</td>", data_point.get_cpu()));
-            output.push_str(&format!(
-                "<td>{}</td>",
-                data_point.get_comment().unwrap_or_default()
-            ));
+// Download the data points of every test on a server, with a column for the test.
+// Has a column for every custom metric of any of the tests
+#[get("/export/<server_id>?<format>&<search>")]
+pub async fn export_server(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    server_id: String,
+    format: Option<String>,
+    search: Option<String>,
+) -> Result<Download, ApiError> {
+    let format = export_format(&format)?;
+    let site_data = site_data.lock().await; // Held while the tests are read, so none is read part way through being saved
+    let mut server = match site_data.servers.get_by_key(&server_id).await {
+        Some(server) => server,
+        None => return Err((Status::NotFound, format!("Server '{}' does not exist", server_id))),
+    };
+    server.load_tests().await;
+    drop(site_data);
 
-            output.push_str("</tr>");
+    let tests = server.tests.as_slice().await;
+    let mut metrics = Vec::new();
+    for test in tests {
+        metrics.extend(test.metrics.as_slice().await.iter().map(Metric::get_name));
+    }
+    let query = parse_query_with::<DataPoint>(&search, &metrics).map_err(|error| (Status::BadRequest, error))?;
+    let mut export = Export::new(true, metrics);
+    for test in tests { // Tests are in ID order, and each test's data in time order
+        for data_point in test.data.as_slice().await.iter().filter(|data_point| query.matches(*data_point)) {
+            export.add(&test.get_id(), data_point);
         }
     }
+    Ok(export.download(format, &server_id))
+}
 
-    output.push_str("</table>");
+// The custom metrics of a test, for the test manager
+#[get("/get_metrics/<server_id>/<test_id>")]
+pub async fn get_metrics(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String, test_id: String) -> RawHtml<String> {
+    let test = match get_test(site_data.inner(), server_id.clone(), test_id.clone()).await {
+        Some(test) => test,
+        None => return message("Could not find test!"),
+    };
 
-    RawHtml(output)
+    RawHtml(templates::metrics_table(&server_id, &test_id, test.metrics.as_slice().await).into_string())
 }
 
-pub async fn get_test(site_data: &Arc<Mutex<SiteData>>, server_id: String, test_id: String) -> Option<Test> {
-    let site_data = site_data.lock().await;
-    let servers = &site_data.servers;
+// Inputs for the custom metrics of a test, for the data point forms. Filled in from the data point at time, if given
+#[get("/get_metric_inputs/<server_id>/<test_id>?<time>")]
+pub async fn get_metric_inputs(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String, test_id: String, time: Option<String>) -> RawHtml<String> {
+    let test = match get_test(site_data.inner(), server_id, test_id).await {
+        Some(test) => test,
+        None => return message("Could not find test!"),
+    };
 
-    let server_index = match servers.search(|a| a.get_id() == server_id).await {
-        Some(server) => server,
-        None => return None
+    let data = test.data.as_slice().await;
+    let data_point = match time.as_deref().map(Timestamp::from_str) {
+        Some(Ok(time)) => test.find_time(time).await.map(|index| &data[index as usize]),
+        _ => None,
     };
+    RawHtml(templates::metric_inputs(test.metrics.as_slice().await, data_point).into_string())
+}
 
-    let mut server = servers.get(server_index).await.expect("Server was found but was not in array!");
-    server.load_tests().await;
+#[derive(FromForm)]
+pub struct CreateMetricData {
+    #[field(validate = identifier())]
+    server_id: String,
+    #[field(validate = identifier())]
+    test_id: String,
+    #[field(validate = identifier())]
+    #[field(validate = not_reserved())]
+    name: String,
+    #[field(validate = text(false, MAX_UNIT_LENGTH))]
+    unit: String,
+    kind: MetricKind,
+}
 
-    let tests = &server.tests;
+// Metric names share the data point columns, so cannot be one of the built-in ones
+fn not_reserved<'v>(name: &str) -> rocket::form::Result<'v, ()> {
+    if RESERVED_METRIC_NAMES.contains(&name) {
+        return Err(rocket::form::Error::validation(format!("'{}' is already a column of every data point", name)).into());
+    }
+    Ok(())
+}
 
-    let test_index = match tests.search(|a| a.get_id() == test_id).await {
+#[post("/create_metric", data = "<form>")]
+pub async fn create_metric(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    form: Form<Contextual<'_, CreateMetricData>>,
+) -> Result<RawHtml<String>, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
+    let mut test = match find_test(&site_data, &form_data.server_id, &form_data.test_id).await {
         Some(test) => test,
-        None => return None
+        None => return Err((Status::NotFound, format!("Test '{}' does not exist on server '{}'", form_data.test_id, form_data.server_id))),
     };
 
-    Some(tests.get(test_index).await.expect("Test was found but was not in array!"))
+    let metric = Metric::new(form_data.name.clone(), form_data.unit.trim().to_string(), form_data.kind);
+    if test.metrics.insert(metric).await.is_err() {
+        return Err((Status::Conflict, format!("name: this test already has a metric called '{}'", form_data.name)));
+    }
+
+    test.metrics.save_to_file(&schema_path(&form_data.server_id, &form_data.test_id)).await.expect("Failed to save metrics!");
+    site_data.stats.invalidate(&form_data.server_id, &form_data.test_id);
+    Ok(RawHtml(templates::metrics_table(&form_data.server_id, &form_data.test_id, test.metrics.as_slice().await).into_string()))
+}
+
+// Stop recording a metric. Values already recorded stay in the data, and come back if the metric is added again
+#[delete("/delete_metric?<server_id>&<test_id>&<name>")]
+pub async fn delete_metric(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    server_id: String,
+    test_id: String,
+    name: String,
+) -> Result<RawHtml<String>, Status> {
+    let mut site_data = site_data.lock().await;
+    let mut test = find_test(&site_data, &server_id, &test_id).await.ok_or(Status::NotFound)?;
+    test.metrics.remove_by_key(&name).await.ok_or(Status::NotFound)?;
+
+    test.metrics.save_to_file(&schema_path(&server_id, &test_id)).await.expect("Failed to save metrics!");
+    site_data.stats.invalidate(&server_id, &test_id);
+    Ok(RawHtml(templates::metrics_table(&server_id, &test_id, test.metrics.as_slice().await).into_string()))
+}
+
+#[get("/get_test_chart/<server_id>/<test_id>")]
+pub async fn get_test_chart(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String, test_id: String) -> Result<(ContentType, String), Status> {
+    let site_data = site_data.lock().await;
+
+    let mut server = site_data.servers.get_by_key(&server_id).await.ok_or(Status::NotFound)?;
+    server.load_tests().await;
+    let test = server.tests.get_by_key(&test_id).await.ok_or(Status::NotFound)?;
+
+    let chart = chart::test_chart(test.data.as_slice().await, server.get_ram());
+    Ok((ContentType::SVG, chart.into_string()))
+}
+
+// Server-sent events for each data point of a test created, updated or deleted from now on, see events.rs.
+// A stream that falls too far behind is sent a reload event, after which the page should fetch the data again
+#[get("/events/<server_id>/<test_id>")]
+pub async fn test_events(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    events: &State<EventBus>,
+    server_id: String,
+    test_id: String,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    if get_test(site_data.inner(), server_id.clone(), test_id.clone()).await.is_none() {
+        return Err(Status::NotFound);
+    }
+
+    let mut receiver = events.subscribe();
+    Ok(EventStream! {
+        loop {
+            let event = select! {
+                event = receiver.recv() => event,
+                _ = &mut shutdown => break, // Otherwise open pages would keep the server from stopping
+            };
+            match event {
+                Ok(event) if event.is_for(&server_id, &test_id) => yield event.to_event(),
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => yield Event::data("").event("reload"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+pub async fn get_test(site_data: &Arc<Mutex<SiteData>>, server_id: String, test_id: String) -> Option<Test> {
+    find_test(&*site_data.lock().await, &server_id, &test_id).await
+}
+
+// The test as it is on disk. Handlers that change a test hold the lock from reading it until it is saved,
+// so nothing else writes to it in between
+async fn find_test(site_data: &SiteData, server_id: &str, test_id: &str) -> Option<Test> {
+    let mut server = site_data.servers.get_by_key(server_id).await?;
+    server.load_tests().await;
+
+    server.tests.get_by_key(test_id).await
 }
 
 #[get("/get_datapoint_info/<server_id>/<test_id>/<time>")]
@@ -310,7 +594,11 @@ pub async fn get_datapoint_info(site_data: &State<Arc<Mutex<SiteData>>>, server_
         None => return "Unable to find test".to_string(),
     };
 
-    let datapoint_index = match test.data.search(|a| a.get_time() == time).await {
+    let datapoint_index = match Timestamp::from_str(&time) {
+        Ok(time) => test.find_time(time).await,
+        Err(_) => None,
+    };
+    let datapoint_index = match datapoint_index {
         Some(datapoint_index) => datapoint_index,
         None => return "Unable to find datapoint".to_string(),
     };
@@ -318,189 +606,256 @@ pub async fn get_datapoint_info(site_data: &State<Arc<Mutex<SiteData>>>, server_
 
     let mut output = String::new();
 
-    output.push_str(datapoint.get_time().as_str());
-    output.push_str(",");
+    output.push_str(datapoint.get_time().to_string().as_str());
+    output.push(',');
     output.push_str(datapoint.get_ram().to_string().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(datapoint.get_cpu().to_string().as_str());
-    output.push_str(",");
+    output.push(',');
     output.push_str(datapoint.get_comment().unwrap_or_default().as_str());
 
     output
 }
 
 #[derive(FromForm)]
-struct UpdateServerData {
+pub struct UpdateServerData {
+    #[field(validate = identifier())]
     old_id: String,
+    #[field(validate = identifier())]
     id: String,
+    #[field(validate = text(true, MAX_NAME_LENGTH))]
     name: String,
+    #[field(validate = text(true, MAX_NAME_LENGTH))]
     created_by: String,
-    ram: String,
-    cpu: String,
+    #[field(validate = range(1..=MAX_RAM))]
+    ram: u32,
+    #[field(validate = range(1..=MAX_CPU_CORES))]
+    cpu: u32,
 }
 
 #[derive(FromForm)]
-struct CreateServerData {
+pub struct CreateServerData {
+    #[field(validate = identifier())]
     id: String,
+    #[field(validate = text(true, MAX_NAME_LENGTH))]
     name: String,
+    #[field(validate = text(true, MAX_NAME_LENGTH))]
     created_by: String,
-    ram: String,
-    cpu: String,
+    #[field(validate = range(1..=MAX_RAM))]
+    ram: u32,
+    #[field(validate = range(1..=MAX_CPU_CORES))]
+    cpu: u32,
 }
 
 #[derive(FromForm)]
-struct UpdateTestData {
+pub struct UpdateTestData {
+    #[field(validate = identifier())]
     server_id: String,
+    #[field(validate = identifier())]
     old_id: String,
+    #[field(validate = identifier())]
     id: String,
 }
 
 #[derive(FromForm)]
-struct CreateTestData {
+pub struct CreateTestData {
+    #[field(validate = identifier())]
     server_id: String,
+    #[field(validate = identifier())]
     id: String,
 }
 
-#[post("/update_test", data = "<form_data>")]
+#[post("/update_test", data = "<form>")]
 pub async fn update_test(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<UpdateTestData>,
-) -> Status {
-    let site_data = site_data.lock().await;
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    form: Form<Contextual<'_, UpdateTestData>>,
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id() == form_data.server_id).await {
-        Some(server_index) => server_index,
-        None => return Status::NotFound,
+    let server = match site_data.servers.get_mut_by_key(&form_data.server_id).await {
+        Some(server) => server,
+        None => return Err((Status::NotFound, format!("Server '{}' does not exist", form_data.server_id))),
     };
-    let server = site_data.servers.get_mut(server_index).await.unwrap();
     server.load_tests().await;
 
-    let test_index = match server.tests.search(|a| a.get_id() == form_data.old_id).await {
-        Some(test_index) => test_index,
-        None => return Status::NotFound,
-    };
-    let test = server.tests.get_mut(test_index).await.unwrap();
+    if !server.tests.contains_key(&form_data.old_id).await {
+        return Err((Status::NotFound, format!("Test '{}' does not exist", form_data.old_id)));
+    }
 
-    test.set_id(form_data.id.clone());
+    if form_data.old_id != form_data.id {
+        if server.tests.contains_key(&form_data.id).await {
+            return Err((Status::Conflict, format!("A test with the ID '{}' already exists on this server", form_data.id)));
+        }
 
-    Status::Ok
+        // Tests are stored as one file each, so renaming the test renames the file
+        let old_path = format!("./data/tests/{}/{}", server.get_id(), form_data.old_id);
+        let new_path = format!("./data/tests/{}/{}", server.get_id(), form_data.id);
+        if let Err(error) = rename(&old_path, &new_path).await {
+            return Err((Status::InternalServerError, format!("Cannot rename the test's data: {}", error)));
+        }
+        let old_schema = schema_path(&server.get_id(), &form_data.old_id);
+        if Path::new(&old_schema).exists() {
+            if let Err(error) = rename(&old_schema, schema_path(&server.get_id(), &form_data.id)).await {
+                let _ = rename(&new_path, &old_path).await; // Put the data back, so the test keeps its metrics
+                return Err((Status::InternalServerError, format!("Cannot rename the test's metrics: {}", error)));
+            }
+        }
+
+        let mut test = server.tests.remove_by_key(&form_data.old_id).await.unwrap();
+        test.set_id(form_data.id.clone());
+        let _ = server.tests.insert(test).await; // Cannot conflict, checked above
+        site_data.stats.invalidate(&form_data.server_id, &form_data.old_id);
+
+        // Rules for the test follow it to its new ID
+        let moved = site_data.alerts.rename(&form_data.server_id, Some(&form_data.old_id), &form_data.id, Timestamp::now());
+        record_alerts(&site_data, webhooks, &moved).await;
+        if update_rules(&mut site_data.alert_rules, &form_data.server_id, Some(&form_data.old_id), Some(&form_data.id)).await {
+            site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+        }
+        update_schedule_tests(&mut site_data, webhooks, notifier, &form_data.server_id, Some(&form_data.old_id), Some(&form_data.id)).await;
+    }
+
+    Ok(Status::Ok)
 }
 
-#[post("/create_test", data = "<form_data>")]
+#[post("/create_test", data = "<form>")]
 pub async fn create_test(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<CreateTestData>,
-) -> Status {
-    let site_data = site_data.lock().await;
+    webhooks: &State<Arc<Webhooks>>,
+    form: Form<Contextual<'_, CreateTestData>>,
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id()  == form_data.server_id).await {
-        Some(server_index) => server_index,
-        None => return Status::NotFound,
+    let server = match site_data.servers.get_mut_by_key(&form_data.server_id).await {
+        Some(server) => server,
+        None => return Err((Status::NotFound, format!("Server '{}' does not exist", form_data.server_id))),
     };
-    let server = site_data.servers.get_mut(server_index).await.unwrap();
+    server.load_tests().await;
+
+    if server.tests.contains_key(&form_data.id).await { // Saving would otherwise wipe the existing test's data
+        return Err((Status::Conflict, format!("A test with the ID '{}' already exists on this server", form_data.id)));
+    }
 
     let test = Test::new(form_data.id.clone());
-    println!("{}", format!("./tests/{}/{}", server.get_id(), test.get_id()).as_str());
     test.data.save_to_file(format!("./data/tests/{}/{}", server.get_id(), test.get_id()).as_str()).await.unwrap();
     server.load_tests().await;
-    println!("{}", test);
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::TestCreated, test_data(&form_data.server_id, &form_data.id)).await;
 
-    Status::Ok
+    Ok(Status::Ok)
 }
 
-#[post("/update_server", data = "<form_data>")]
+#[post("/update_server", data = "<form>")]
 pub async fn update_server(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<UpdateServerData>
-) -> Status {
-    let site_data = site_data.lock().await;
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    form: Form<Contextual<'_, UpdateServerData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id() == form_data.old_id).await {
-        Some(server_index) => server_index,
-        None => return Status::NotFound
-    };
-    let server = site_data.servers.get_mut(server_index).await.unwrap();
+    if !site_data.servers.contains_key(&form_data.old_id).await {
+        return Err((Status::NotFound, format!("Server '{}' does not exist", form_data.old_id)));
+    }
 
-    let ram = match u32::from_str(form_data.ram.as_str()) {
-        Ok(ram) => ram,
-        Err(_) => return Status::UnprocessableEntity,
-    };
-    let cpu = match u32::from_str(form_data.cpu.as_str()) {
-        Ok(cpu) => cpu,
-        Err(_) => return Status::UnprocessableEntity,
-    };
+    if form_data.old_id != form_data.id && site_data.servers.contains_key(&form_data.id).await { // Check before moving the test directory
+        return Err((Status::Conflict, format!("A server with the ID '{}' already exists", form_data.id)));
+    }
 
-    server.set_id(form_data.id.clone()).await;
+    // Remove and re-insert, so the server stays in order if its ID changes
+    let mut server = site_data.servers.remove_by_key(&form_data.old_id).await.unwrap();
+    if let Err(error) = server.set_id(form_data.id.clone()).await {
+        let _ = site_data.servers.insert(server).await; // Put the server back as it was
+        return Err((Status::InternalServerError, format!("Cannot move the server's data: {}", error)));
+    }
     server.set_name(form_data.name.clone());
     server.set_created_by(form_data.created_by.clone());
-    server.set_ram(ram);
-    server.set_cpu(cpu);
+    server.set_ram(form_data.ram);
+    server.set_cpu(form_data.cpu);
+    let _ = site_data.servers.insert(server).await; // Cannot conflict, checked above
+    site_data.stats.invalidate_server(&form_data.old_id);
+
+    if form_data.old_id != form_data.id {
+        let moved = site_data.alerts.rename(&form_data.old_id, None, &form_data.id, Timestamp::now());
+        record_alerts(&site_data, webhooks, &moved).await;
+        if update_rules(&mut site_data.alert_rules, &form_data.old_id, None, Some(&form_data.id)).await {
+            site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+        }
+        update_schedule_tests(&mut site_data, webhooks, notifier, &form_data.old_id, None, Some(&form_data.id)).await;
+    }
 
     site_data.servers.save_to_file("./data/servers").await.expect("Failed to save servers!");
-    Status::Ok
+    Ok(Status::Ok)
 }
 
 #[delete("/delete_server?<server_id>")]
-pub async fn delete_server(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String) -> Status {
+pub async fn delete_server(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    server_id: String,
+) -> Status {
     let mut site_data = site_data.lock().await;
 
-    let server_index = match site_data.servers.search(|a| a.get_id() == server_id).await {
-        Some(server_index) => server_index,
+    let server = match site_data.servers.remove_by_key(&server_id).await {
+        Some(server) => server,
         None => return Status::NotFound,
     };
-    
-    let server = site_data.servers.get(server_index).await.unwrap();
     server.delete_tests_directory().await;
+    site_data.stats.invalidate_server(&server_id);
+    let resolved = site_data.alerts.forget(&server_id, None, Timestamp::now());
+    record_alerts(&site_data, webhooks, &resolved).await;
+    if update_rules(&mut site_data.alert_rules, &server_id, None, None).await {
+        site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+    }
+    update_schedule_tests(&mut site_data, webhooks, notifier, &server_id, None, None).await;
 
-    site_data.servers.remove(server_index).await;
     site_data.servers.save_to_file("./data/servers").await.expect("Failed to save servers!");
     Status::Ok
 }
 
+// Also removes the user from the schedule entries they are assigned to
 #[delete("/delete_user?<username>")]
-pub async fn delete_user(site_data: &State<Arc<Mutex<SiteData>>>, username: String) -> Status {
+pub async fn delete_user(site_data: &State<Arc<Mutex<SiteData>>>, webhooks: &State<Arc<Webhooks>>, username: String) -> Status {
     let mut site_data = site_data.lock().await;
 
-    let user_index = match site_data.users.search(|a| a.get_username() == username).await {
-        Some(user_index) => user_index,
-        None => return Status::NotFound,
-    };
+    if site_data.users.remove_by_key(&username).await.is_none() {
+        return Status::NotFound;
+    }
 
-    let user = site_data.users.get(user_index).await.unwrap();
-    site_data.users.remove(user_index).await;
     site_data.users.save_to_file("./data/users").await.expect("Failed to save users!");
+    update_assignments(&mut site_data, webhooks, &username, None).await;
     Status::Ok
 }
 
-#[post("/create_server", data = "<form_data>")]
-pub async fn create_server(site_data: &State<Arc<Mutex<SiteData>>>, form_data: Form<CreateServerData>) -> Status {
+#[post("/create_server", data = "<form>")]
+pub async fn create_server(site_data: &State<Arc<Mutex<SiteData>>>, form: Form<Contextual<'_, CreateServerData>>) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
     let mut site_data = site_data.lock().await;
-    
-    let ram = match u32::from_str(form_data.ram.as_str()) {
-        Ok(ram) => ram,
-        Err(_) => return Status::UnprocessableEntity,
-    };
-    let cpu = match u32::from_str(form_data.cpu.as_str()) {
-        Ok(cpu) => cpu,
-        Err(_) => return Status::UnprocessableEntity,
-    };
-    
+
     let server = Server::new(
         form_data.id.clone(),
         form_data.name.clone(),
-        form_data.created_by.clone(), 
-        ram.clone(),
-        cpu.clone(),
+        form_data.created_by.clone(),
+        form_data.ram,
+        form_data.cpu,
     );
-    
-    site_data.servers.push(server).await;
+
+    if site_data.servers.insert(server).await.is_err() {
+        return Err((Status::Conflict, format!("A server with the ID '{}' already exists", form_data.id)));
+    }
     site_data.servers.save_to_file("./data/servers").await.expect("Failed to save servers!");
-    
-    Status::Ok
+
+    Ok(Status::Ok)
 }
 
-#[post("/create_user", data = "<form_data>")]
-pub async fn create_user(site_data: &State<Arc<Mutex<SiteData>>>, form_data: Form<CreateUserData>) -> Status {
+#[post("/create_user", data = "<form>")]
+pub async fn create_user(site_data: &State<Arc<Mutex<SiteData>>>, form: Form<Contextual<'_, CreateUserData>>) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
     let mut site_data = site_data.lock().await;
 
     let forename = match form_data.forename.is_empty() {
@@ -513,50 +868,74 @@ pub async fn create_user(site_data: &State<Arc<Mutex<SiteData>>>, form_data: For
         true => None,
     };
 
-    let position = Position::from_str(form_data.position.as_str()).unwrap();
+    let position = form_data.position.clone();
 
-    let user = User::new(
+    let mut user = User::new(
         form_data.username.clone(),
         forename,
         surname,
         position,
     );
+    user.set_email(match form_data.email.is_empty() {
+        false => Some(form_data.email.clone()),
+        true => None,
+    });
 
-    site_data.users.push(user).await;
+    if site_data.users.insert(user).await.is_err() {
+        return Err((Status::Conflict, format!("A user with the username '{}' already exists", form_data.username)));
+    }
 
-    Status::Ok
+    site_data.users.save_to_file("./data/users").await.expect("Failed to save users!");
+    Ok(Status::Ok)
 }
 
 #[derive(FromForm)]
-struct UpdateUserData {
+pub struct UpdateUserData {
+    #[field(validate = identifier())]
     old_username: String,
+    #[field(validate = identifier())]
     username: String,
+    #[field(validate = text(false, MAX_NAME_LENGTH))]
     forename: String,
+    #[field(validate = text(false, MAX_NAME_LENGTH))]
     surname: String,
-    position: String,
+    position: Position,
+    #[field(validate = email_address(), default = String::new())] // Optional, for forms from before users had one
+    email: String,
 }
 
 #[derive(FromForm)]
-struct CreateUserData {
+pub struct CreateUserData {
+    #[field(validate = identifier())]
     username: String,
+    #[field(validate = text(false, MAX_NAME_LENGTH))]
     forename: String,
+    #[field(validate = text(false, MAX_NAME_LENGTH))]
     surname: String,
-    position: String,
+    position: Position,
+    #[field(validate = email_address(), default = String::new())] // Optional, for forms from before users had one
+    email: String,
 }
 
-
-#[post("/update_user", data = "<form_data>")]
+#[post("/update_user", data = "<form>")]
 pub async fn update_user(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<UpdateUserData>
-) -> Status {
-    let site_data = site_data.lock().await;
+    webhooks: &State<Arc<Webhooks>>,
+    form: Form<Contextual<'_, UpdateUserData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
 
-    let user_index = match site_data.users.search(|user| user.get_username() == form_data.old_username).await {
-        Some(user_index) => user_index,
-        None => return Status::NotFound,
-    };
-    let user = site_data.users.get_mut(user_index).await.unwrap();
+    if !site_data.users.contains_key(&form_data.old_username).await {
+        return Err((Status::NotFound, format!("User '{}' does not exist", form_data.old_username)));
+    }
+
+    if form_data.old_username != form_data.username && site_data.users.contains_key(&form_data.username).await {
+        return Err((Status::Conflict, format!("A user with the username '{}' already exists", form_data.username)));
+    }
+
+    // Remove and re-insert, so the user stays in order if their username changes
+    let mut user = site_data.users.remove_by_key(&form_data.old_username).await.unwrap();
 
     user.set_username(form_data.username.clone());
 
@@ -570,33 +949,55 @@ pub async fn update_user(
         false => Some(form_data.surname.clone())
     });
 
-    user.set_position(Position::from_str(form_data.position.as_str()).unwrap());
+    user.set_position(form_data.position.clone());
+
+    user.set_email(match form_data.email.is_empty() {
+        true => None,
+        false => Some(form_data.email.clone())
+    });
+
+    let _ = site_data.users.insert(user).await; // Cannot conflict, checked above
 
     // Save updated user data to a file
     site_data.users.save_to_file("./data/users").await.expect("Failed to save users!");
-    Status::Ok
+    if form_data.old_username != form_data.username {
+        update_assignments(&mut site_data, webhooks, &form_data.old_username, Some(&form_data.username)).await;
+    }
+    Ok(Status::Ok)
 }
 
 // For updating and creating DataPoints
 #[derive(FromForm)]
-struct UpdateDataPointData {
+pub struct UpdateDataPointData {
+    #[field(validate = identifier())]
     server_id: String,
+    #[field(validate = identifier())]
     test_id: String,
-    old_time: String,
-    time: String,
-    cpu: String,
-    ram: String,
+    old_time: Timestamp,
+    time: Timestamp,
+    #[field(validate = range(0..=MAX_CPU_PERCENT))]
+    cpu: u32,
+    #[field(validate = range(0..=MAX_RAM))]
+    ram: u32,
+    #[field(validate = text(false, MAX_COMMENT_LENGTH))]
     comment: String,
+    metrics: HashMap<String, String>, // Custom metrics by name, checked against the test
 }
 
 #[derive(FromForm)]
-struct CreateDataPointData {
+pub struct CreateDataPointData {
+    #[field(validate = identifier())]
     server_id: String,
+    #[field(validate = identifier())]
     test_id: String,
-    time: String,
-    ram: String,
-    cpu: String,
+    time: Timestamp,
+    #[field(validate = range(0..=MAX_RAM))]
+    ram: u32,
+    #[field(validate = range(0..=MAX_CPU_PERCENT))]
+    cpu: u32,
+    #[field(validate = text(false, MAX_COMMENT_LENGTH))]
     comment: String,
+    metrics: HashMap<String, String>,
 }
 
 // Get Test Info
@@ -619,98 +1020,291 @@ pub async fn get_test_info(
 #[delete("/delete_test?<server_id>&<test_id>")]
 pub async fn delete_test(
     site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
     server_id: String,
     test_id: String
-) -> Status {
-    let site_data = site_data.lock().await;
+) -> Result<Status, ApiError> {
+    let mut site_data = site_data.lock().await;
 
     // Find the server
-    let server_index = match site_data.servers.search(|a| a.get_id() == server_id).await {
-        Some(index) => index,
-        None => return Status::NotFound,
+    let server = match site_data.servers.get_mut_by_key(&server_id).await {
+        Some(server) => server,
+        None => return Err((Status::NotFound, format!("Server '{}' does not exist", server_id))),
     };
-    let mut server = site_data.servers.get_mut(server_index).await.unwrap();
     server.load_tests().await;
 
-    // Find and remove the test
-    let test_index = match server.tests.search(|a| a.get_id() == test_id).await {
-        Some(index) => index,
-        None => return Status::NotFound,
+    if !server.tests.contains_key(&test_id).await {
+        return Err((Status::NotFound, format!("Test '{}' does not exist", test_id)));
+    }
+
+    // Tests are read from the files under ./data/tests, so the test is only gone once its data is
+    let path = format!("./data/tests/{}/{}", server_id, test_id);
+    if Path::new(&path).exists() {
+        if let Err(error) = remove_file(path).await {
+            return Err((Status::InternalServerError, format!("Cannot delete the test's data: {}", error)));
+        }
+    }
+    server.tests.remove_by_key(&test_id).await;
+    let schema = schema_path(&server_id, &test_id);
+    let schema_removed = match Path::new(&schema).exists() {
+        true => remove_file(schema).await,
+        false => Ok(()),
     };
-    server.tests.remove(test_index).await;
+    site_data.stats.invalidate(&server_id, &test_id);
+    let resolved = site_data.alerts.forget(&server_id, Some(&test_id), Timestamp::now());
+    record_alerts(&site_data, webhooks, &resolved).await;
+    if update_rules(&mut site_data.alert_rules, &server_id, Some(&test_id), None).await {
+        site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+    }
+    update_schedule_tests(&mut site_data, webhooks, notifier, &server_id, Some(&test_id), None).await;
 
-    // Save the test data to a file
-    server.tests.save_to_file(&format!("./data/servers/{}/tests", server.get_id())).await.expect("Failed to save tests!");
-    Status::Ok
+    // Left behind, the metrics would be picked up by a new test with the same ID
+    match schema_removed {
+        Ok(()) => Ok(Status::Ok),
+        Err(error) => Err((Status::InternalServerError, format!("The test was deleted, but not its metrics: {}", error))),
+    }
 }
 
 // Update DataPoint
-#[post("/update_datapoint", data = "<form_data>")]
+#[post("/update_datapoint", data = "<form>")]
 pub async fn update_datapoint(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<UpdateDataPointData>
-) -> Status {
-    let test = match get_test(site_data.inner(), form_data.server_id.clone(), form_data.test_id.clone()).await {
+    events: &State<EventBus>,
+    form: Form<Contextual<'_, UpdateDataPointData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
+    let mut test = match find_test(&site_data, &form_data.server_id, &form_data.test_id).await {
         Some(test) => test,
-        None => return Status::NotFound,
+        None => return Err((Status::NotFound, format!("Test '{}' does not exist on server '{}'", form_data.test_id, form_data.server_id))),
     };
 
-    let datapoint_index = match test.data.search(|a| a.get_time() == form_data.old_time).await {
+    let datapoint_index = match test.find_time(form_data.old_time).await {
         Some(index) => index,
-        None => return Status::NotFound,
+        None => return Err((Status::NotFound, format!("There is no data point at '{}'", form_data.old_time))),
     };
+
+    // Data points are looked up by time, so it must stay unique within the test
+    if form_data.old_time != form_data.time && test.find_time(form_data.time).await.is_some() {
+        return Err((Status::Conflict, format!("There is already a data point at '{}'", form_data.time)));
+    }
+
+    let mut metrics = test.parse_metrics(&form_data.metrics).await.map_err(|error| (Status::UnprocessableEntity, error))?;
     let datapoint = test.data.get_mut(datapoint_index).await.unwrap();
+    for (name, value) in datapoint.get_metrics() { // Values of metrics the test no longer records are kept
+        if !test.metrics.contains_key(name).await {
+            metrics.insert(name.clone(), *value);
+        }
+    }
 
     // Update the data point
-    datapoint.set_time(form_data.time.clone());
-    datapoint.set_cpu(form_data.cpu.parse::<u32>().unwrap_or(0));
-    datapoint.set_ram(form_data.ram.parse::<u32>().unwrap_or(0));
+    datapoint.set_time(form_data.time);
+    datapoint.set_cpu(form_data.cpu);
+    datapoint.set_ram(form_data.ram);
     datapoint.set_comment(if form_data.comment.is_empty() { None } else { Some(form_data.comment.clone()) });
+    datapoint.set_metrics(metrics);
+    let updated = datapoint.clone();
+    test.data.quick_sort().await; // Keep the data in time order, in case the time changed
 
     // Save the data point data to a file
     test.data.save_to_file(&format!("./data/tests/{}/{}", form_data.server_id, form_data.test_id)).await.expect("Failed to save data points!");
-    Status::Ok
+    site_data.stats.invalidate(&form_data.server_id, &form_data.test_id); // The old values cannot be taken back out
+    events.publish(DataPointEvent::new(&form_data.server_id, &form_data.test_id, Change::Updated, form_data.old_time, Some(updated)));
+    Ok(Status::Ok)
 }
 
 // Create DataPoint
-#[post("/create_datapoint", data = "<form_data>")]
+#[post("/create_datapoint", data = "<form>")]
 pub async fn create_datapoint(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<CreateDataPointData>
-) -> Status {
-    let mut test = match get_test(site_data.inner(), form_data.server_id.clone(), form_data.test_id.clone()).await {
+    events: &State<EventBus>,
+    webhooks: &State<Arc<Webhooks>>,
+    form: Form<Contextual<'_, CreateDataPointData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
+    let mut test = match find_test(&site_data, &form_data.server_id, &form_data.test_id).await {
         Some(test) => test,
-        None => return Status::NotFound,
+        None => return Err((Status::NotFound, format!("Test '{}' does not exist on server '{}'", form_data.test_id, form_data.server_id))),
     };
 
+    // Data points are looked up by time, so it must be unique within the test
+    if test.find_time(form_data.time).await.is_some() {
+        return Err((Status::Conflict, format!("There is already a data point at '{}'", form_data.time)));
+    }
+
+    let metrics = test.parse_metrics(&form_data.metrics).await.map_err(|error| (Status::UnprocessableEntity, error))?;
+
     // Create a new data point
-    let datapoint = DataPoint::new(
-        form_data.time.clone(),
-        form_data.ram.parse::<u32>().unwrap_or(0),
-        form_data.cpu.parse::<u32>().unwrap_or(0),
+    let mut datapoint = DataPoint::new(
+        form_data.time,
+        form_data.ram,
+        form_data.cpu,
     );
+    if !form_data.comment.is_empty() {
+        datapoint.add_comment(form_data.comment.clone());
+    }
+    datapoint.set_metrics(metrics);
 
-    test.data.push(datapoint).await;
+    site_data.stats.add(&form_data.server_id, &form_data.test_id, &datapoint).await;
+    test.data.insert_sorted(datapoint.clone()).await; // Data is kept in time order
 
     // Save the data point data to a file
     test.data.save_to_file(&format!("./data/tests/{}/{}", form_data.server_id, form_data.test_id)).await.expect("Failed to save data points!");
-    Status::Ok
+    let added = std::slice::from_ref(&datapoint);
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::DataPointsIngested, ingested_data(&form_data.server_id, &form_data.test_id, added)).await;
+    check_alerts(&mut site_data, webhooks, &form_data.server_id, &form_data.test_id, added).await;
+    events.publish(DataPointEvent::new(&form_data.server_id, &form_data.test_id, Change::Created, form_data.time, Some(datapoint)));
+    Ok(Status::Ok)
+}
+
+// Bodies sent to ingest_datapoints and import_report can be much larger than a form
+const MAX_UPLOAD_MEGABYTES: u32 = 64;
+
+// what is what the body holds, for the error message, e.g. "data points"
+async fn read_upload(data: Data<'_>, what: &str) -> Result<String, ApiError> {
+    match data.open(MAX_UPLOAD_MEGABYTES.mebibytes()).into_string().await {
+        Ok(body) if body.is_complete() => Ok(body.into_inner()),
+        Ok(_) => Err((Status::PayloadTooLarge, format!("Send at most {} MB of {} at once", MAX_UPLOAD_MEGABYTES, what))),
+        Err(_) => Err((Status::BadRequest, format!("Send {} as UTF-8 text", what))),
+    }
+}
+
+// Add many data points to a test at once, from CSV with a header row, a JSON array of objects, or NDJSON,
+// as given by the Content-Type. Nothing is added unless every row is valid
+#[post("/ingest/<server_id>/<test_id>", data = "<data>")]
+pub async fn ingest_datapoints(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    events: &State<EventBus>,
+    webhooks: &State<Arc<Webhooks>>,
+    server_id: String,
+    test_id: String,
+    content_type: Option<&ContentType>,
+    data: Data<'_>,
+) -> Result<String, ApiError> {
+    let format = match content_type.and_then(IngestFormat::from_content_type) {
+        Some(format) => format,
+        None => return Err((Status::UnsupportedMediaType, "Send data points as text/csv, application/json or application/x-ndjson".to_string())),
+    };
+
+    // The body is read before locking, so a slow upload does not hold up everything else
+    let body = read_upload(data, "data points").await?;
+
+    let mut site_data = site_data.lock().await;
+    let mut test = match find_test(&site_data, &server_id, &test_id).await {
+        Some(test) => test,
+        None => return Err((Status::NotFound, format!("Test '{}' does not exist on server '{}'", test_id, server_id))),
+    };
+
+    let data_points = read_data_points(&test, format, &body).await.map_err(|errors| (Status::UnprocessableEntity, errors.join("\n")))?;
+    let added = data_points.length;
+
+    let path = format!("./data/tests/{}/{}", server_id, test_id);
+    let last_time = match test.data.length {
+        0 => None,
+        length => test.data.get(length - 1).await.map(|data_point| data_point.get_time()),
+    };
+    let first_new_time = data_points.get(0).await.map(|data_point| data_point.get_time());
+
+    site_data.stats.add_all(&server_id, &test_id, data_points.as_slice().await).await;
+
+    if last_time < first_new_time { // Samples usually come after everything recorded so far, so can go on the end of the file
+        data_points.append_to_file(&path).await.expect("Failed to save data points!");
+    } else { // Otherwise the data is merged, and written back in time order
+        for data_point in data_points.as_slice().await {
+            test.data.push(data_point.clone()).await;
+        }
+        test.data.quick_sort().await;
+        test.data.save_to_file(&path).await.expect("Failed to save data points!");
+    }
+    if added > 0 {
+        let data = ingested_data(&server_id, &test_id, data_points.as_slice().await);
+        webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::DataPointsIngested, data).await;
+    }
+    check_alerts(&mut site_data, webhooks, &server_id, &test_id, data_points.as_slice().await).await;
+    for data_point in data_points.as_slice().await {
+        events.publish(DataPointEvent::new(&server_id, &test_id, Change::Created, data_point.get_time(), Some(data_point.clone())));
+    }
+
+    Ok(format!("Added {} data point{}", added, if added == 1 { "" } else { "s" }))
+}
+
+// Add the results of a test run to a server, from a JUnit XML report (application/xml or text/xml)
+// or a JSON report (application/json). Tests are created for test cases the server does not have yet,
+// and the response says what was created, added and skipped
+#[post("/import/<server_id>", data = "<data>")]
+pub async fn import_report(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    events: &State<EventBus>,
+    webhooks: &State<Arc<Webhooks>>,
+    server_id: String,
+    content_type: Option<&ContentType>,
+    data: Data<'_>,
+) -> Result<String, ApiError> {
+    let format = match content_type.and_then(ReportFormat::from_content_type) {
+        Some(format) => format,
+        None => return Err((Status::UnsupportedMediaType, "Send a JUnit report as application/xml, or a JSON report as application/json".to_string())),
+    };
+    if !site_data.lock().await.servers.contains_key(&server_id).await {
+        return Err((Status::NotFound, format!("Server '{}' does not exist", server_id)));
+    }
+    let body = read_upload(data, "a report").await?;
+    let results = read_report(format, &body).map_err(|errors| (Status::UnprocessableEntity, errors.join("\n")))?;
+
+    let mut site_data = site_data.lock().await;
+    let server = match site_data.servers.get_mut_by_key(&server_id).await {
+        Some(server) => server,
+        None => return Err((Status::NotFound, format!("Server '{}' does not exist", server_id))), // Deleted while the report was sent
+    };
+    server.load_tests().await;
+
+    // Results without a time of their own are recorded at the start of this second
+    let now = Timestamp::from_unix_seconds(Timestamp::now().unix_seconds()).unwrap();
+    let report = import_results(&mut server.tests, &results, now).await;
+    for test_id in &report.changed {
+        let test = server.tests.get_by_key(test_id).await.unwrap();
+        test.data.save_to_file(&format!("./data/tests/{}/{}", server_id, test_id)).await.expect("Failed to save data points!");
+        test.metrics.save_to_file(&schema_path(&server_id, test_id)).await.expect("Failed to save metrics!");
+    }
+    for test_id in &report.created {
+        webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::TestCreated, test_data(&server_id, test_id)).await;
+    }
+    for test_id in &report.changed {
+        site_data.stats.invalidate(&server_id, test_id);
+
+        let mut data_points: Vec<DataPoint> = report.added.iter().filter(|(id, _)| id == test_id).map(|(_, data_point)| data_point.clone()).collect();
+        data_points.sort_by_key(|data_point| data_point.get_time()); // Results are not always in time order
+        webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::DataPointsIngested, ingested_data(&server_id, test_id, &data_points)).await;
+        check_alerts(&mut site_data, webhooks, &server_id, test_id, &data_points).await;
+        for data_point in data_points {
+            events.publish(DataPointEvent::new(&server_id, test_id, Change::Created, data_point.get_time(), Some(data_point)));
+        }
+    }
+
+    Ok(report.to_string())
 }
 
 // Delete DataPoint
 #[delete("/delete_datapoint?<server_id>&<test_id>&<time>")]
 pub async fn delete_datapoint(
     site_data: &State<Arc<Mutex<SiteData>>>,
+    events: &State<EventBus>,
     server_id: String,
     test_id: String,
     time: String,
 ) -> Status {
-    let mut test = match get_test(site_data.inner(), server_id.clone(), test_id.clone()).await {
+    let mut site_data = site_data.lock().await;
+    let mut test = match find_test(&site_data, &server_id, &test_id).await {
         Some(test) => test,
         None => return Status::NotFound,
     };
 
-    let datapoint_index = match test.data.search(|a| a.get_time() == time).await {
+    let time = match Timestamp::from_str(&time) {
+        Ok(time) => time,
+        Err(_) => return Status::NotFound,
+    };
+    let datapoint_index = match test.find_time(time).await {
         Some(index) => index,
         None => return Status::NotFound,
     };
@@ -720,26 +1314,286 @@ pub async fn delete_datapoint(
 
     // Save the data point data to a file
     test.data.save_to_file(&format!("./data/tests/{}/{}", server_id, test_id)).await.expect("Failed to save data points!");
+    site_data.stats.invalidate(&server_id, &test_id);
+    events.publish(DataPointEvent::new(&server_id, &test_id, Change::Deleted, time, None));
     Status::Ok
 }
 
+// Check data points just added to a test against the alert rules covering it, oldest first,
+// recording any alerts that fire or resolve and telling webhooks about them
+async fn check_alerts(site_data: &mut SiteData, webhooks: &Webhooks, server_id: &str, test_id: &str, data_points: &[DataPoint]) -> Vec<AlertEvent> {
+    let server_ram = match site_data.servers.get_mut_by_key(server_id).await {
+        Some(server) => server.get_ram(),
+        None => return Vec::new(),
+    };
+    let rules = site_data.alert_rules.as_slice().await;
+    let alerts = site_data.alerts.check(rules, server_id, server_ram, test_id, data_points);
+    record_alerts(site_data, webhooks, &alerts).await;
+    alerts
+}
+
+// Add alerts that fired or resolved to the event log, and tell webhooks about them
+async fn record_alerts(site_data: &SiteData, webhooks: &Webhooks, alerts: &[AlertEvent]) {
+    if !alerts.is_empty() {
+        let mut log = MyVector::new();
+        for alert in alerts {
+            log.push(alert.clone()).await;
+        }
+        log.append_to_file(ALERT_EVENTS_PATH).await.expect("Failed to save alerts!");
+    }
+    for alert in alerts {
+        let event = match alert.get_change() {
+            AlertChange::Fired => WebhookEvent::AlertFired,
+            AlertChange::Resolved => WebhookEvent::AlertResolved,
+        };
+        webhooks.send(site_data.webhooks.as_slice().await, event, alert_data(alert)).await;
+    }
+}
+
+// Alert rules are listed by server, including those for one of its tests
+async fn alert_rules_table(site_data: &SiteData, server_id: &str) -> RawHtml<String> {
+    let rules = site_data.alert_rules.search_all(|rule| rule.get_server_id() == server_id).await;
+    RawHtml(templates::alert_rules_table(rules.as_slice().await).into_string())
+}
+
+#[get("/get_alert_rules/<server_id>")]
+pub async fn get_alert_rules(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String) -> RawHtml<String> {
+    let site_data = site_data.lock().await;
+    if !site_data.servers.contains_key(&server_id).await {
+        return message("Could not find server!");
+    }
+    alert_rules_table(&site_data, &server_id).await
+}
+
+#[derive(FromForm)]
+pub struct CreateAlertRuleData {
+    #[field(validate = identifier())]
+    id: String,
+    #[field(validate = identifier())]
+    server_id: String,
+    #[field(default = String::new())]
+    test_id: String, // Empty for every test of the server
+    #[field(validate = identifier())]
+    metric: String,
+    comparator: Comparator,
+    threshold: f64,
+    of_server_ram: bool, // The threshold is a percentage of the server's RAM
+    #[field(validate = range(0..=MAX_ALERT_DURATION))]
+    duration: u32,
+}
+
+#[post("/create_alert_rule", data = "<form>")]
+pub async fn create_alert_rule(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    form: Form<Contextual<'_, CreateAlertRuleData>>,
+) -> Result<RawHtml<String>, ApiError> {
+    let form_data = validated(&form)?;
+    let test_id = form_data.test_id.trim();
+    if !test_id.is_empty() {
+        identifier(test_id).map_err(|errors| {
+            (Status::UnprocessableEntity, errors.iter().map(|error| format!("test_id: {}", error.kind)).collect::<Vec<_>>().join("\n"))
+        })?;
+    }
+
+    // Custom metrics are only known for a test, a rule for every test may watch one that some tests record
+    let test = match test_id {
+        "" => None,
+        test_id => match get_test(site_data.inner(), form_data.server_id.clone(), test_id.to_string()).await {
+            Some(test) => Some(test),
+            None => return Err((Status::NotFound, format!("test_id: server '{}' has no test '{}'", form_data.server_id, test_id))),
+        },
+    };
+
+    let mut errors = Vec::new();
+    let metric = form_data.metric.as_str();
+    if metric != "ram" && metric != "cpu" {
+        if RESERVED_METRIC_NAMES.contains(&metric) {
+            errors.push(format!("metric: '{}' is not a number", metric));
+        } else if let Some(test) = &test {
+            if !test.metrics.contains_key(metric).await {
+                errors.push(format!("metric: test '{}' does not record '{}'", test.get_id(), metric));
+            }
+        }
+    }
+    if !form_data.threshold.is_finite() {
+        errors.push("threshold: must be a number".to_string());
+    }
+    if form_data.of_server_ram {
+        if metric != "ram" {
+            errors.push("of_server_ram: only RAM can be compared with the server's RAM".to_string());
+        } else if !(0.0..=100.0).contains(&form_data.threshold) {
+            errors.push("threshold: must be a percentage from 0 to 100".to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Err((Status::UnprocessableEntity, errors.join("\n")));
+    }
+
+    let mut site_data = site_data.lock().await;
+    if !site_data.servers.contains_key(&form_data.server_id).await {
+        return Err((Status::NotFound, format!("Server '{}' does not exist", form_data.server_id)));
+    }
+
+    let rule = AlertRule::new(
+        form_data.id.clone(),
+        form_data.server_id.clone(),
+        test.map(|test| test.get_id()),
+        form_data.metric.clone(),
+        form_data.comparator,
+        form_data.threshold,
+        form_data.of_server_ram,
+        form_data.duration,
+    );
+    if site_data.alert_rules.insert(rule).await.is_err() {
+        return Err((Status::Conflict, format!("id: an alert rule with the ID '{}' already exists", form_data.id)));
+    }
+
+    site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+    Ok(alert_rules_table(&site_data, &form_data.server_id).await)
+}
+
+// Alerts the rule already recorded stay in the log
+#[delete("/delete_alert_rule?<id>")]
+pub async fn delete_alert_rule(site_data: &State<Arc<Mutex<SiteData>>>, id: String) -> Result<RawHtml<String>, Status> {
+    let mut site_data = site_data.lock().await;
+    let rule = site_data.alert_rules.remove_by_key(&id).await.ok_or(Status::NotFound)?;
+    site_data.alerts.forget_rule(&id);
+
+    site_data.alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Failed to save alert rules!");
+    Ok(alert_rules_table(&site_data, &rule.get_server_id()).await)
+}
+
+const MAX_ALERTS_SHOWN: usize = 50;
+
+// The latest alerts that fired or resolved on a server, or on one of its tests, newest first
+#[get("/get_alert_events/<server_id>?<test_id>")]
+pub async fn get_alert_events(site_data: &State<Arc<Mutex<SiteData>>>, server_id: String, test_id: Option<String>) -> RawHtml<String> {
+    // Alerts are appended to the log with the lock held, so holding it here never reads an append part way through
+    let site_data = site_data.lock().await;
+    let log: MyVector<AlertEvent> = MyVector::load_from_file(ALERT_EVENTS_PATH).await;
+    drop(site_data);
+    let alerts: Vec<AlertEvent> = log
+        .as_slice()
+        .await
+        .iter()
+        .rev()
+        .filter(|alert| alert.get_server_id() == server_id && test_id.as_ref().is_none_or(|test_id| &alert.get_test_id() == test_id))
+        .take(MAX_ALERTS_SHOWN)
+        .cloned()
+        .collect();
+    RawHtml(templates::alert_events_table(&alerts).into_string())
+}
+
+// Webhooks, with how many deliveries each has waiting
+async fn webhooks_table(site_data: &SiteData, webhooks: &Webhooks) -> RawHtml<String> {
+    let mut rows = Vec::new();
+    for webhook in site_data.webhooks.as_slice().await {
+        rows.push((webhook.clone(), webhooks.pending(&webhook.get_id()).await));
+    }
+    RawHtml(templates::webhooks_table(&rows).into_string())
+}
+
+#[get("/get_webhooks")]
+pub async fn get_webhooks(site_data: &State<Arc<Mutex<SiteData>>>, webhooks: &State<Arc<Webhooks>>) -> RawHtml<String> {
+    webhooks_table(&*site_data.lock().await, webhooks).await
+}
+
+#[derive(FromForm)]
+pub struct CreateWebhookData {
+    #[field(validate = identifier())]
+    id: String,
+    #[field(validate = text(true, MAX_URL_LENGTH))]
+    url: String,
+    events: Vec<String>, // Names, e.g. test.created
+    #[field(validate = text(true, MAX_NAME_LENGTH))]
+    secret: String,
+}
+
+#[post("/create_webhook", data = "<form>")]
+pub async fn create_webhook(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    form: Form<Contextual<'_, CreateWebhookData>>,
+) -> Result<RawHtml<String>, ApiError> {
+    let form_data = validated(&form)?;
+    let mut errors = Vec::new();
+    let url = form_data.url.trim();
+    if let Err(error) = check_url(url) {
+        errors.push(format!("url: {}", error));
+    }
+    let events = match form_data.events.iter().map(|event| WebhookEvent::from_str(event)).collect::<Result<Vec<_>, _>>() {
+        Ok(events) if events.is_empty() => {
+            errors.push("events: choose at least one event".to_string());
+            events
+        }
+        Ok(events) => events,
+        Err(error) => {
+            errors.push(format!("events: {}", error));
+            Vec::new()
+        }
+    };
+    if !errors.is_empty() {
+        return Err((Status::UnprocessableEntity, errors.join("\n")));
+    }
+
+    let mut site_data = site_data.lock().await;
+    let webhook = Webhook::new(form_data.id.clone(), url.to_string(), events, form_data.secret.clone());
+    if site_data.webhooks.insert(webhook).await.is_err() {
+        return Err((Status::Conflict, format!("id: a webhook with the ID '{}' already exists", form_data.id)));
+    }
+
+    site_data.webhooks.save_to_file(WEBHOOKS_PATH).await.expect("Failed to save webhooks!");
+    Ok(webhooks_table(&site_data, webhooks).await)
+}
+
+// Deliveries still waiting for the webhook fail when they are next tried
+#[delete("/delete_webhook?<id>")]
+pub async fn delete_webhook(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    id: String,
+) -> Result<RawHtml<String>, Status> {
+    let mut site_data = site_data.lock().await;
+    site_data.webhooks.remove_by_key(&id).await.ok_or(Status::NotFound)?;
+
+    site_data.webhooks.save_to_file(WEBHOOKS_PATH).await.expect("Failed to save webhooks!");
+    Ok(webhooks_table(&site_data, webhooks).await)
+}
+
+const MAX_DELIVERIES_SHOWN: usize = 50;
+
+// The latest attempts to send to a webhook, newest first
+#[get("/get_webhook_deliveries/<webhook_id>")]
+pub async fn get_webhook_deliveries(webhooks: &State<Arc<Webhooks>>, webhook_id: String) -> RawHtml<String> {
+    let attempts = webhooks.attempts(&webhook_id, MAX_DELIVERIES_SHOWN).await;
+    RawHtml(templates::delivery_attempts_table(&attempts).into_string())
+}
+
 /// Struct for creating a new ScheduleEntry
 #[derive(FromForm)]
-struct CreateScheduleEntryData {
+pub struct CreateScheduleEntryData {
+    #[field(validate = identifier())]
     id: String,
-    datetime: String,
-    assignees: String,
-    test: String,
+    datetime: Timestamp,
+    assignees: Vec<String>, // Usernames, checked against the users
+    #[field(validate = identifier())]
+    server_id: String,
+    #[field(validate = identifier())]
+    test_id: String,
 }
 
-/// Struct for updating an existing ScheduleEntry
+/// Struct for updating an existing ScheduleEntry, whose assignees are changed with add_assignee and remove_assignee
 #[derive(FromForm)]
-struct UpdateScheduleEntryData {
+pub struct UpdateScheduleEntryData {
+    #[field(validate = identifier())]
     old_id: String,
+    #[field(validate = identifier())]
     id: String,
-    datetime: String,
-    assignees: String,
-    test: String,
+    datetime: Timestamp,
+    #[field(validate = identifier())]
+    server_id: String,
+    #[field(validate = identifier())]
+    test_id: String,
 }
 
 /// Get comma-separated schedule entry information
@@ -749,128 +1603,446 @@ pub async fn get_schedule_entry_info(
     schedule_entry_id: String
 ) -> String {
     let site_data = site_data.lock().await;
-    let schedule_index = match site_data.schedules.search(|s| s.get_id() == schedule_entry_id).await {
-        Some(index) => index,
+    let schedule = match site_data.schedules.get_by_key(&schedule_entry_id).await {
+        Some(schedule) => schedule,
         None => return "Schedule Entry Not Found".to_string(),
     };
-    let schedule = site_data.schedules.get(schedule_index).await.unwrap();
-    format!("{},{},{},{}",
+    format!("{},{},{},{},{}",
             schedule.get_id(),
             schedule.get_datetime(),
-            schedule.get_assignees(),
-            schedule.get_test()
+            schedule.get_assignees().join(" "),
+            schedule.get_server_id(),
+            schedule.get_test_id()
     )
 }
 
-#[get("/get_schedule_entries?<search>")]
+const SCHEDULE_SORT_FIELDS: &[&str] = &["datetime", "id", "assignees", "server", "test"];
+
+fn compare_schedules(a: &ScheduleEntry, b: &ScheduleEntry, field: &str) -> Ordering {
+    match field {
+        "id" => a.get_id().cmp(&b.get_id()),
+        "assignees" => a.get_assignees().cmp(&b.get_assignees()),
+        "server" => a.get_server_id().cmp(&b.get_server_id()).then_with(|| a.get_test_id().cmp(&b.get_test_id())),
+        "test" => a.get_test_id().cmp(&b.get_test_id()),
+        _ => a.get_datetime().cmp(&b.get_datetime()),
+    }
+}
+
+// With assignee, only the entries that user is assigned to, e.g. for "my assignments"
+#[get("/get_schedule_entries?<search>&<assignee>&<list..>")]
 pub async fn get_schedule_entries(
     site_data: &State<Arc<Mutex<SiteData>>>,
     search: Option<String>,
-) -> RawHtml<String> {
+    assignee: Option<String>,
+    list: ListOptions,
+) -> Result<TablePage, RawHtml<String>> {
+    let query = parse_search::<ScheduleEntry>(&search)?;
+    let assignee = assignee.filter(|assignee| !assignee.is_empty());
     let site_data = site_data.lock().await;
-    let mut output = String::new();
-
-    output.push_str("<table>");
-    output.push_str("<tr><th>ID</th><th>DateTime</th><th>Assignees</th><th>Test</th></tr>\n");
 
-    let schedules = if let Some(ref search_term) = search {
+    let mut schedules = if !query.is_empty() || assignee.is_some() {
         site_data
             .schedules
-            .search_all(|s| s.get_id().contains(search_term))
+            .search_all(|schedule| query.matches(schedule) && assignee.as_deref().is_none_or(|assignee| schedule.is_assigned(assignee)))
             .await
     } else {
-        site_data.schedules.clone()
+        (*site_data.schedules).clone()
     };
+    // Stored by ID, but shown in date order unless sorted by something else
+    let field = list.sort_field(SCHEDULE_SORT_FIELDS);
+    schedules.quick_sort_by(|a, b| list.order(compare_schedules(a, b, field))).await;
 
-    for i in 0..schedules.length {
-        let schedule = schedules.get(i).await.unwrap();
-        let id = schedule.get_id();
-
-        output.push_str(&format!(
-            "<tr onclick=\"window.location.href='/manage-scheduleentry?id={}'\" style=\"cursor:pointer\">",
-            id
-        ));
-        output.push_str(&format!(
-            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
-            id,
-            schedule.get_datetime(),
-            schedule.get_assignees(),
-            schedule.get_test()
-        ));
-        output.push_str("</tr>\n");
+    let mut pager = Pager::new("/api/get_schedule_entries", search.as_deref(), &list, SCHEDULE_SORT_FIELDS, schedules.length);
+    if let Some(assignee) = assignee.as_deref() {
+        pager = pager.with_parameter("assignee", assignee);
+    }
+    let mut output = html!("<table>");
+    output.push(html!("<tr>"));
+    output.push(pager.header("id", "ID"));
+    output.push(pager.header("datetime", "DateTime"));
+    output.push(pager.header("assignees", "Assignees"));
+    output.push(pager.header("server", "Server"));
+    output.push(pager.header("test", "Test"));
+    output.push(html!("</tr>\n"));
+
+    for schedule in schedules.page(list.offset(), list.per_page()).await {
+        output.push(templates::schedule_row(schedule));
     }
 
-    output.push_str("</table>");
-    RawHtml(output)
+    output.push(html!("</table>"));
+    Ok(pager.finish(output))
 }
 
 /// Create a new schedule entry
-#[post("/create_schedule_entry", data = "<form_data>")]
+#[post("/create_schedule_entry", data = "<form>")]
 pub async fn create_schedule_entry(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<CreateScheduleEntryData>
-) -> Status {
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    form: Form<Contextual<'_, CreateScheduleEntryData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
     let mut site_data = site_data.lock().await;
-
-    if site_data.schedules.search(|s| s.get_id() == form_data.id).await.is_some() {
-        return Status::Conflict;
-    }
+    let assignees = check_assignees(&site_data, &form_data.assignees).await?;
+    check_schedule_test(&site_data, &form_data.server_id, &form_data.test_id).await?;
 
     let schedule = ScheduleEntry::new(
         form_data.id.clone(),
-        form_data.datetime.clone().replace('T', " "),
-        form_data.assignees.clone(),
-        form_data.test.clone(),
+        form_data.datetime,
+        assignees,
+        form_data.server_id.clone(),
+        form_data.test_id.clone(),
     );
 
-    site_data.schedules.push(schedule).await;
+    let data = schedule_data(&schedule);
+    let created = schedule.clone();
+    if site_data.schedules.insert(schedule).await.is_err() {
+        return Err((Status::Conflict, format!("A schedule entry with the ID '{}' already exists", form_data.id)));
+    }
     site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
-    Status::Ok
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::ScheduleCreated, data).await;
+    notifier.schedule_changed(site_data.users.as_slice().await, None, Some(&created));
+    Ok(Status::Ok)
 }
 
 /// Update an existing schedule entry
-#[post("/update_schedule_entry", data = "<form_data>")]
+#[post("/update_schedule_entry", data = "<form>")]
 pub async fn update_schedule_entry(
     site_data: &State<Arc<Mutex<SiteData>>>,
-    form_data: Form<UpdateScheduleEntryData>
-) -> Status {
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    form: Form<Contextual<'_, UpdateScheduleEntryData>>
+) -> Result<Status, ApiError> {
+    let form_data = validated(&form)?;
     let mut site_data = site_data.lock().await;
 
-    let schedule_index = match site_data.schedules.search(|s| s.get_id() == form_data.old_id).await {
-        Some(index) => index,
-        None => return Status::NotFound,
-    };
+    if !site_data.schedules.contains_key(&form_data.old_id).await {
+        return Err((Status::NotFound, format!("Schedule entry '{}' does not exist", form_data.old_id)));
+    }
 
-    if form_data.old_id != form_data.id {
-        if site_data.schedules.search(|s| s.get_id() == form_data.id).await.is_some() {
-            return Status::Conflict;
-        }
+    if form_data.old_id != form_data.id && site_data.schedules.contains_key(&form_data.id).await {
+        return Err((Status::Conflict, format!("A schedule entry with the ID '{}' already exists", form_data.id)));
     }
+    check_schedule_test(&site_data, &form_data.server_id, &form_data.test_id).await?;
 
-    let schedule = site_data.schedules.get_mut(schedule_index).await.unwrap();
+    // Remove and re-insert, so the entry stays in order if its ID changes
+    let mut schedule = site_data.schedules.remove_by_key(&form_data.old_id).await.unwrap();
+    let old = schedule.clone();
     schedule.set_id(form_data.id.clone());
-    schedule.set_datetime(form_data.datetime.clone().replace('T', " "));
-    schedule.set_assignees(form_data.assignees.clone());
-    schedule.set_test(form_data.test.clone());
+    schedule.set_datetime(form_data.datetime);
+    schedule.set_server_id(form_data.server_id.clone());
+    schedule.set_test_id(form_data.test_id.clone());
+    let data = updated_schedule_data(&form_data.old_id, &schedule);
+    let new = schedule.clone();
+    let _ = site_data.schedules.insert(schedule).await; // Cannot conflict, checked above
 
     site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
-    Status::Ok
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::ScheduleUpdated, data).await;
+    notifier.schedule_changed(site_data.users.as_slice().await, Some(&old), Some(&new));
+    Ok(Status::Ok)
+}
+
+// Entries are for one of a server's tests, which has to exist
+async fn check_schedule_test(site_data: &SiteData, server_id: &str, test_id: &str) -> Result<(), ApiError> {
+    if !site_data.servers.contains_key(server_id).await {
+        return Err((Status::NotFound, format!("server_id: server '{}' does not exist", server_id)));
+    }
+    if find_test(site_data, server_id, test_id).await.is_none() {
+        return Err((Status::NotFound, format!("test_id: server '{}' has no test '{}'", server_id, test_id)));
+    }
+    Ok(())
+}
+
+// The usernames for an entry's assignees, without duplicates, if every one is a user
+async fn check_assignees(site_data: &SiteData, usernames: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut assignees: Vec<String> = Vec::new();
+    let mut errors = Vec::new();
+    for username in usernames.iter().map(|username| username.trim()).filter(|username| !username.is_empty()) {
+        if !site_data.users.contains_key(username).await {
+            errors.push(format!("assignees: '{}' is not a user", username));
+        } else if !assignees.iter().any(|assignee| assignee == username) {
+            assignees.push(username.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Err((Status::UnprocessableEntity, errors.join("\n")));
+    }
+    Ok(assignees)
+}
+
+// The webhook data for an entry that changed, which receivers know by its old ID
+fn updated_schedule_data(old_id: &str, schedule: &ScheduleEntry) -> Json {
+    let mut data = schedule_data(schedule);
+    if let Json::Object(fields) = &mut data {
+        fields.push(("old_id".to_string(), Json::String(old_id.to_string())));
+    }
+    data
+}
+
+// Save an entry whose assignees changed, and tell webhooks and the assignees
+async fn assignees_changed(site_data: &mut SiteData, webhooks: &Webhooks, notifier: &Notifier, old: &ScheduleEntry, new: ScheduleEntry) {
+    let data = updated_schedule_data(&old.get_id(), &new);
+    notifier.schedule_changed(site_data.users.as_slice().await, Some(old), Some(&new));
+    site_data.schedules.remove_by_key(&old.get_id()).await;
+    let _ = site_data.schedules.insert(new).await; // Same ID as before, so it cannot conflict
+
+    site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::ScheduleUpdated, data).await;
+}
+
+// The assignees of an entry, each with a button to remove them
+#[get("/get_assignees/<schedule_entry_id>")]
+pub async fn get_assignees(site_data: &State<Arc<Mutex<SiteData>>>, schedule_entry_id: String) -> Result<RawHtml<String>, Status> {
+    let site_data = site_data.lock().await;
+    let schedule = site_data.schedules.get_by_key(&schedule_entry_id).await.ok_or(Status::NotFound)?;
+    Ok(RawHtml(templates::assignees_list(&schedule, site_data.users.as_slice().await).into_string()))
+}
+
+#[derive(FromForm)]
+pub struct AddAssigneeData {
+    #[field(validate = identifier())]
+    schedule_entry_id: String,
+    #[field(validate = identifier())]
+    username: String,
+}
+
+#[post("/add_assignee", data = "<form>")]
+pub async fn add_assignee(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    form: Form<Contextual<'_, AddAssigneeData>>,
+) -> Result<RawHtml<String>, ApiError> {
+    let form_data = validated(&form)?;
+    let mut site_data = site_data.lock().await;
+    let old = match site_data.schedules.get_by_key(&form_data.schedule_entry_id).await {
+        Some(schedule) => schedule,
+        None => return Err((Status::NotFound, format!("Schedule entry '{}' does not exist", form_data.schedule_entry_id))),
+    };
+    if !site_data.users.contains_key(&form_data.username).await {
+        return Err((Status::UnprocessableEntity, format!("username: '{}' is not a user", form_data.username)));
+    }
+
+    let mut schedule = old.clone();
+    if !schedule.add_assignee(form_data.username.clone()) {
+        return Err((Status::Conflict, format!("username: '{}' is already assigned", form_data.username)));
+    }
+    let list = templates::assignees_list(&schedule, site_data.users.as_slice().await);
+    assignees_changed(&mut site_data, webhooks, notifier, &old, schedule).await;
+    Ok(RawHtml(list.into_string()))
+}
+
+#[delete("/remove_assignee?<schedule_entry_id>&<username>")]
+pub async fn remove_assignee(
+    site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
+    schedule_entry_id: String,
+    username: String,
+) -> Result<RawHtml<String>, Status> {
+    let mut site_data = site_data.lock().await;
+    let old = site_data.schedules.get_by_key(&schedule_entry_id).await.ok_or(Status::NotFound)?;
+
+    let mut schedule = old.clone();
+    if !schedule.remove_assignee(&username) {
+        return Err(Status::NotFound);
+    }
+    let list = templates::assignees_list(&schedule, site_data.users.as_slice().await);
+    assignees_changed(&mut site_data, webhooks, notifier, &old, schedule).await;
+    Ok(RawHtml(list.into_string()))
+}
+
+// A user was renamed, or deleted if new_username is None, so update the entries they are assigned to
+async fn update_assignments(site_data: &mut SiteData, webhooks: &Webhooks, username: &str, new_username: Option<&str>) {
+    let affected: Vec<ScheduleEntry> = site_data.schedules.search_all(|schedule| schedule.is_assigned(username)).await.as_slice().await.to_vec();
+    if affected.is_empty() {
+        return;
+    }
+
+    let mut events = Vec::new();
+    for old in &affected {
+        let schedule = site_data.schedules.get_mut_by_key(&old.get_id()).await.unwrap();
+        match new_username {
+            Some(new_username) => schedule.rename_assignee(username, new_username),
+            None => schedule.remove_assignee(username),
+        };
+        events.push(updated_schedule_data(&old.get_id(), schedule));
+    }
+    site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
+    for data in events {
+        webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::ScheduleUpdated, data).await;
+    }
+}
+
+// A test was renamed, or a server if test_id is None, so point the entries for it at its new ID,
+// or remove them if new_id is None after it is deleted, telling their assignees
+async fn update_schedule_tests(site_data: &mut SiteData, webhooks: &Webhooks, notifier: &Notifier, server_id: &str, test_id: Option<&str>, new_id: Option<&str>) {
+    let affected: Vec<ScheduleEntry> = site_data.schedules.search_all(|schedule| schedule.is_for(server_id, test_id)).await.as_slice().await.to_vec();
+    if affected.is_empty() {
+        return;
+    }
+
+    let mut events = Vec::new();
+    for old in &affected {
+        match new_id {
+            Some(new_id) => {
+                let schedule = site_data.schedules.get_mut_by_key(&old.get_id()).await.unwrap();
+                match test_id {
+                    Some(_) => schedule.set_test_id(new_id.to_string()),
+                    None => schedule.set_server_id(new_id.to_string()),
+                }
+                events.push((WebhookEvent::ScheduleUpdated, updated_schedule_data(&old.get_id(), schedule)));
+            }
+            None => {
+                site_data.schedules.remove_by_key(&old.get_id()).await;
+                notifier.schedule_changed(site_data.users.as_slice().await, Some(old), None);
+                events.push((WebhookEvent::ScheduleDeleted, schedule_data(old)));
+            }
+        }
+    }
+    site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
+    for (event, data) in events {
+        webhooks.send(site_data.webhooks.as_slice().await, event, data).await;
+    }
+}
+
+// Every user, as options for choosing assignees
+#[get("/get_user_options")]
+pub async fn get_user_options(site_data: &State<Arc<Mutex<SiteData>>>) -> RawHtml<String> {
+    let site_data = site_data.lock().await;
+    RawHtml(templates::user_options(site_data.users.as_slice().await).into_string())
 }
 
 /// Delete a schedule entry
 #[delete("/delete_schedule_entry?<schedule_entry_id>")]
 pub async fn delete_schedule_entry(
     site_data: &State<Arc<Mutex<SiteData>>>,
+    webhooks: &State<Arc<Webhooks>>,
+    notifier: &State<Arc<Notifier>>,
     schedule_entry_id: String
 ) -> Status {
     let mut site_data = site_data.lock().await;
 
-    let schedule_index = match site_data.schedules.search(|s| s.get_id() == schedule_entry_id).await {
-        Some(index) => index,
+    let schedule = match site_data.schedules.remove_by_key(&schedule_entry_id).await {
+        Some(schedule) => schedule,
         None => return Status::NotFound,
     };
 
-    site_data.schedules.remove(schedule_index).await;
     site_data.schedules.save_to_file("./data/schedules").await.expect("Failed to save schedules!");
+    webhooks.send(site_data.webhooks.as_slice().await, WebhookEvent::ScheduleDeleted, schedule_data(&schedule)).await;
+    notifier.schedule_changed(site_data.users.as_slice().await, Some(&schedule), None);
     Status::Ok
-}
\ No newline at end of file
+}
+
+/// Search users, servers, tests, data point comments and schedule entries at once.
+/// A field name in the query limits the search to the types that have that field, e.g. ram>=4096 only searches servers and data points
+#[get("/search?<q>")]
+pub async fn search(site_data: &State<Arc<Mutex<SiteData>>>, q: Option<String>) -> RawHtml<String> {
+    let query = match q.as_deref().map(Query::from_str) {
+        Some(Ok(query)) if !query.is_empty() => query,
+        Some(Err(error)) => return message(&error),
+        _ => return message("Type something to search for"),
+    };
+
+    let site_data = site_data.lock().await;
+    let mut output = Html::default();
+    let mut searched = false; // Whether any type has every field in the query
+
+    if query.check_fields::<User>().is_ok() {
+        searched = true;
+        let users = site_data.users.search_all(|user| query.matches(user)).await;
+        let mut results = Vec::new();
+        for user in users.as_slice().await {
+            let label = format!(
+                "{} ({} {})",
+                user.get_username(),
+                user.get_forename().unwrap_or_default(),
+                user.get_surname().unwrap_or_default()
+            );
+            results.push((url("/manage-user", &[("username", &user.get_username())]), label));
+        }
+        output.push(templates::search_group("Users", &results));
+    }
+
+    if query.check_fields::<Server>().is_ok() {
+        searched = true;
+        let servers = site_data.servers.search_all(|server| query.matches(server)).await;
+        let mut results = Vec::new();
+        for server in servers.as_slice().await {
+            let label = format!("{} ({})", server.get_id(), server.get_name());
+            results.push((url("/manage-server", &[("server_id", &server.get_id())]), label));
+        }
+        output.push(templates::search_group("Servers", &results));
+    }
+
+    // Shown after the tests, but found now, so the lock is not held while every test is read from disk
+    let mut schedule_group = None;
+    if query.check_fields::<ScheduleEntry>().is_ok() {
+        searched = true;
+        let schedules = site_data.schedules.search_all(|schedule| query.matches(schedule)).await;
+        let mut results = Vec::new();
+        for schedule in schedules.as_slice().await {
+            let label = format!("{} ({}, {}/{})", schedule.get_id(), schedule.get_datetime(), schedule.get_server_id(), schedule.get_test_id());
+            results.push((url("/manage-scheduleentry", &[("id", &schedule.get_id())]), label));
+        }
+        schedule_group = Some(templates::search_group("Schedule Entries", &results));
+    }
+
+    let servers = (*site_data.servers).clone();
+    drop(site_data);
+
+    let search_tests = query.check_fields::<Test>().is_ok();
+    let mut search_data_points = query.check_fields::<DataPoint>().is_ok(); // Or a custom metric of some test, checked below
+    let mut test_results = Vec::new();
+    let mut data_point_results = Vec::new();
+
+    for server in servers.as_slice().await {
+        let mut server = server.clone();
+        server.load_tests().await;
+
+        for test in server.tests.as_slice().await {
+            let link = url("/test-data", &[("server_id", &server.get_id()), ("test_id", &test.get_id())]);
+
+            if search_tests && query.matches(test) {
+                test_results.push((link.clone(), format!("{} / {}", server.get_id(), test.get_id())));
+            }
+
+            let metric_names: Vec<String> = test.metrics.as_slice().await.iter().map(Metric::get_name).collect();
+            if query.check_fields_with::<DataPoint>(&metric_names).is_ok() {
+                search_data_points = true;
+                // Only data points with a comment are worth finding from here
+                let data_points = test.data.search_all(|data_point| {
+                    data_point.get_comment().is_some() && query.matches(data_point)
+                }).await;
+                for data_point in data_points.as_slice().await {
+                    let label = format!(
+                        "{} / {} at {}: {}",
+                        server.get_id(),
+                        test.get_id(),
+                        data_point.get_time(),
+                        data_point.get_comment().unwrap_or_default()
+                    );
+                    data_point_results.push((link.clone(), label));
+                }
+            }
+        }
+    }
+
+    if search_tests {
+        searched = true;
+        output.push(templates::search_group("Tests", &test_results));
+    }
+    if search_data_points {
+        searched = true;
+        output.push(templates::search_group("Data Point Comments", &data_point_results));
+    }
+    if let Some(schedule_group) = schedule_group {
+        output.push(schedule_group);
+    }
+
+    if !searched {
+        return message("None of the fields in the search exist on the same type");
+    }
+
+    RawHtml(output.into_string())
+}
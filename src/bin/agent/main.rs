@@ -0,0 +1,168 @@
+// An agent to run on the machine under test. It samples RAM and CPU from /proc at an interval
+// and sends them to a test of a server through the bulk ingestion endpoint, e.g.
+//     cargo run --bin agent -- --server web_01 --test load --interval 5
+mod sampler;
+mod uploader;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::sampler::Sampler;
+use crate::uploader::{Endpoint, Sent, Uploader};
+
+const USAGE: &str = "Usage: agent --server <id> --test <id> [options]
+  --url <url>        Where the test management system runs (default http://127.0.0.1:8000)
+  --interval <secs>  Seconds between samples (default 5)
+  --batch <count>    Most samples sent in one request (default 500)
+  --buffer <count>   Most samples kept while the system cannot be reached (default 100000)
+  --proc <path>      Where /proc is mounted (default /proc)";
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    url: String,
+    server_id: String,
+    test_id: String,
+    interval: u64, // Seconds, whole so that every sample has its own time
+    batch: usize,
+    buffer: usize,
+    proc: PathBuf,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        let mut config = Config {
+            url: "http://127.0.0.1:8000".to_string(),
+            server_id: String::new(),
+            test_id: String::new(),
+            interval: 5,
+            batch: 500,
+            buffer: 100_000,
+            proc: PathBuf::from("/proc"),
+        };
+
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            match arg.as_str() {
+                "--url" => config.url = value,
+                "--server" => config.server_id = value,
+                "--test" => config.test_id = value,
+                "--interval" => config.interval = positive(&arg, &value)?,
+                "--batch" => config.batch = positive(&arg, &value)? as usize,
+                "--buffer" => config.buffer = positive(&arg, &value)? as usize,
+                "--proc" => config.proc = PathBuf::from(value),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        identifier("--server", &config.server_id)?;
+        identifier("--test", &config.test_id)?;
+        Ok(config)
+    }
+}
+
+fn positive(arg: &str, value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("{} must be a whole number above 0", arg)),
+    }
+}
+
+// The same rule the system has for IDs, so they can go in the URL as they are
+fn identifier(arg: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err(format!("{} is required", arg));
+    }
+    if !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("{} may only contain letters, numbers, - and _", arg));
+    }
+    Ok(())
+}
+
+// The current time to the second, as the system stores it
+fn now() -> String {
+    let now = OffsetDateTime::now_utc();
+    now.replace_nanosecond(0).unwrap_or(now).format(&Rfc3339).unwrap()
+}
+
+fn samples(count: usize) -> String {
+    format!("{} sample{}", count, if count == 1 { "" } else { "s" })
+}
+
+fn main() {
+    let config = Config::from_args(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    let endpoint = Endpoint::new(&config.url, &config.server_id, &config.test_id).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+    let mut sampler = Sampler::new(&config.proc).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let interval = Duration::from_secs(config.interval);
+    let mut uploader = Uploader::new(endpoint, config.buffer, config.batch, interval);
+    println!("Sending samples every {} s to test '{}' of server '{}' at {}", config.interval, config.test_id, config.server_id, config.url);
+
+    let mut next_sample = Instant::now() + interval; // CPU use is measured over the first interval
+    loop {
+        thread::sleep(next_sample.saturating_duration_since(Instant::now()));
+        // Never sooner than an interval after the last sample, even if sending took longer than that
+        next_sample = (next_sample + interval).max(Instant::now() + interval);
+
+        match sampler.sample() {
+            Ok((ram, cpu)) => uploader.add(&now(), ram, cpu),
+            Err(error) => eprintln!("Skipped a sample: {}", error),
+        }
+
+        let dropped = uploader.dropped;
+        loop {
+            match uploader.send(Instant::now()) {
+                Sent::Nothing => break,
+                Sent::Accepted(_) => continue, // Catch up on anything buffered
+                Sent::Rejected(count, reason) => eprintln!("The system refused {}, which were dropped: {}", samples(count), reason),
+                Sent::Unreachable(reason, delay) => {
+                    eprintln!("{}. Keeping {}, and trying again in {} s", reason, samples(uploader.pending()), delay.as_secs());
+                    break;
+                }
+            }
+        }
+        if uploader.dropped > dropped {
+            eprintln!("The buffer is full, so the oldest samples are being dropped ({} so far)", uploader.dropped);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use std::path::PathBuf;
+
+    fn parse(args: &str) -> Result<Config, String> {
+        Config::from_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_config() {
+        let config = parse("--server web_01 --test load --interval 1 --proc /host/proc").unwrap();
+        assert_eq!(config.server_id, "web_01");
+        assert_eq!(config.interval, 1);
+        assert_eq!(config.batch, 500);
+        assert_eq!(config.proc, PathBuf::from("/host/proc"));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert_eq!(parse("--test load").unwrap_err(), "--server is required");
+        assert_eq!(parse("--server web --test ../x").unwrap_err(), "--test may only contain letters, numbers, - and _");
+        assert_eq!(parse("--server web --test load --interval 0").unwrap_err(), "--interval must be a whole number above 0");
+        assert_eq!(parse("--server web --test load --verbose").unwrap_err(), "--verbose needs a value");
+        assert_eq!(parse("--server web --test load --colour red").unwrap_err(), "Unknown option --colour");
+    }
+}
@@ -0,0 +1,120 @@
+// Reading how much RAM and CPU the machine is using from /proc, as Linux reports it
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Memory in use, in MB. Memory the kernel says is available counts as free,
+// so caches it would give back to programs do not make the machine look full
+pub fn ram_used(meminfo: &str) -> Result<u32, String> {
+    let field = |name: &str| -> Result<u64, String> {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+            .ok_or_else(|| format!("meminfo has no {}", name))
+    };
+    let used = field("MemTotal")?.saturating_sub(field("MemAvailable")?);
+    Ok((used / 1024) as u32)
+}
+
+// Time all CPUs have spent since boot, in clock ticks, from the first line of /proc/stat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    pub fn parse(stat: &str) -> Result<Self, String> {
+        let line = stat.lines().find(|line| line.starts_with("cpu ")).ok_or("stat has no cpu line")?;
+        let values = line
+            .split_whitespace()
+            .skip(1)
+            .map(|value| value.parse::<u64>().map_err(|_| format!("'{}' in the cpu line is not a number", value)))
+            .collect::<Result<Vec<u64>, String>>()?;
+        if values.len() < 4 {
+            return Err("the cpu line is too short".to_string());
+        }
+
+        // user, nice, system, idle, iowait, irq, softirq, steal. Guest time after that is already counted in user
+        let total = values.iter().take(8).sum();
+        let idle = values[3] + values.get(4).copied().unwrap_or(0); // Waiting on disks is not work
+        Ok(Self { busy: total - idle, total })
+    }
+
+    // How busy the CPUs were between an earlier reading and this one, as a percentage
+    pub fn percent_since(&self, earlier: &CpuTimes) -> u32 {
+        let total = self.total.saturating_sub(earlier.total);
+        if total == 0 {
+            return 0;
+        }
+        let busy = self.busy.saturating_sub(earlier.busy);
+        ((busy * 100 + total / 2) / total).min(100) as u32
+    }
+}
+
+// Takes samples one after another, as CPU use can only be measured between two readings
+pub struct Sampler {
+    proc: PathBuf,
+    last: CpuTimes,
+}
+
+impl Sampler {
+    // proc is where /proc is mounted, which differs when watching a host from a container
+    pub fn new(proc: &Path) -> Result<Self, String> {
+        let last = CpuTimes::parse(&read(&proc.join("stat"))?)?;
+        ram_used(&read(&proc.join("meminfo"))?)?; // Fail at the start, not on the first sample
+        Ok(Self { proc: proc.to_path_buf(), last })
+    }
+
+    // RAM used in MB, and CPU used in percent since the last sample
+    pub fn sample(&mut self) -> Result<(u32, u32), String> {
+        let ram = ram_used(&read(&self.proc.join("meminfo"))?)?;
+        let times = CpuTimes::parse(&read(&self.proc.join("stat"))?)?;
+        let cpu = times.percent_since(&self.last);
+        self.last = times;
+        Ok((ram, cpu))
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| format!("Cannot read {}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ram_used, CpuTimes, Sampler};
+    use std::fs;
+
+    const MEMINFO: &str = "MemTotal:       16384000 kB\nMemFree:         1024000 kB\nMemAvailable:    8192000 kB\nBuffers:          102400 kB\n";
+
+    #[test]
+    fn test_ram_used() {
+        assert_eq!(ram_used(MEMINFO), Ok(8000));
+        assert_eq!(ram_used("MemTotal: 1024 kB\n"), Err("meminfo has no MemAvailable".to_string()));
+    }
+
+    #[test]
+    fn test_cpu_percent() {
+        let earlier = CpuTimes::parse("cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 50 0 50 350 50 0 0 0 0 0\n").unwrap();
+        let later = CpuTimes::parse("cpu  175 0 125 750 150 0 0 0 0 0\n").unwrap();
+        assert_eq!(later.percent_since(&earlier), 50); // 100 of 200 ticks busy
+        assert_eq!(earlier.percent_since(&earlier), 0);
+        assert!(CpuTimes::parse("cpu  1 2\n").is_err());
+        assert!(CpuTimes::parse("intr 1 2 3 4\n").is_err());
+    }
+
+    #[test]
+    fn test_sampler() {
+        let proc = std::env::temp_dir().join(format!("agent_proc_{}", std::process::id()));
+        fs::create_dir_all(&proc).unwrap();
+        fs::write(proc.join("meminfo"), MEMINFO).unwrap();
+        fs::write(proc.join("stat"), "cpu  0 0 0 100 0 0 0 0 0 0\n").unwrap();
+
+        let mut sampler = Sampler::new(&proc).unwrap();
+        fs::write(proc.join("stat"), "cpu  25 0 0 175 0 0 0 0 0 0\n").unwrap();
+        assert_eq!(sampler.sample(), Ok((8000, 25)));
+
+        fs::remove_dir_all(&proc).unwrap();
+        assert!(sampler.sample().unwrap_err().starts_with("Cannot read"));
+    }
+}
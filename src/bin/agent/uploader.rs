@@ -0,0 +1,250 @@
+// Sending samples to the bulk ingestion endpoint, as CSV, over plain HTTP.
+// Samples wait in a buffer until the server takes them, so an outage or a restart of the server loses nothing
+// unless it outlasts the buffer, and sending is retried less and less often while the server cannot be reached
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Where the data points of one test are sent
+#[derive(Clone, Debug, PartialEq)]
+pub struct Endpoint {
+    host: String, // With the port, as sent in the Host header
+    path: String,
+}
+
+impl Endpoint {
+    // url is where the system runs, e.g. http://127.0.0.1:8000, which may include a path it is served under
+    pub fn new(url: &str, server_id: &str, test_id: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("'{}' must start with http://", url))?;
+        let (host, prefix) = match rest.find('/') {
+            Some(index) => (&rest[..index], rest[index..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            return Err(format!("'{}' has no host", url));
+        }
+        let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+        Ok(Self { host, path: format!("{}/api/ingest/{}/{}", prefix, server_id, test_id) })
+    }
+}
+
+// How an attempt to send went
+#[derive(Debug, PartialEq)]
+pub enum Sent {
+    Nothing, // The buffer is empty, or it is not time to retry yet
+    Accepted(usize), // Data points the server added
+    Rejected(usize, String), // Data points the server refused, which are dropped as sending them again would not help
+    Unreachable(String, Duration), // Why, and how long until the next try. The data points are kept
+}
+
+pub struct Uploader {
+    endpoint: Endpoint,
+    pending: VecDeque<String>, // CSV rows, oldest first
+    capacity: usize,
+    batch: usize,
+    retry_delay: Duration, // After the first failure
+    failures: u32,
+    next_attempt: Option<Instant>,
+    pub dropped: u64, // Oldest samples thrown away because the buffer was full
+}
+
+impl Uploader {
+    // capacity is how many samples are kept while the server cannot be reached, batch how many are sent at once
+    pub fn new(endpoint: Endpoint, capacity: usize, batch: usize, retry_delay: Duration) -> Self {
+        Self { endpoint, pending: VecDeque::new(), capacity, batch, retry_delay, failures: 0, next_attempt: None, dropped: 0 }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn add(&mut self, time: &str, ram: u32, cpu: u32) {
+        if self.pending.len() == self.capacity {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back(format!("{},{},{}", time, ram, cpu));
+    }
+
+    // Send the oldest batch of samples, unless still waiting to retry
+    pub fn send(&mut self, now: Instant) -> Sent {
+        if self.pending.is_empty() || self.next_attempt.is_some_and(|next_attempt| now < next_attempt) {
+            return Sent::Nothing;
+        }
+
+        let count = self.pending.len().min(self.batch);
+        let mut body = String::from("time,ram,cpu\n");
+        for row in self.pending.iter().take(count) {
+            body.push_str(row);
+            body.push('\n');
+        }
+
+        match post(&self.endpoint, &body) {
+            Ok((status, _)) if (200..300).contains(&status) => {
+                self.pending.drain(..count);
+                self.failures = 0;
+                self.next_attempt = None;
+                Sent::Accepted(count)
+            }
+            // Busy or restarting, so worth trying again
+            Ok((status, message)) if status >= 500 || status == 408 || status == 429 => self.failed(now, format!("{} {}", status, message)),
+            Ok((status, message)) => {
+                self.pending.drain(..count);
+                Sent::Rejected(count, format!("{} {}", status, message))
+            }
+            Err(error) => self.failed(now, error),
+        }
+    }
+
+    fn failed(&mut self, now: Instant, reason: String) -> Sent {
+        let delay = self.retry_delay.saturating_mul(2u32.saturating_pow(self.failures)).min(MAX_RETRY_DELAY);
+        self.failures += 1;
+        self.next_attempt = Some(now + delay);
+        Sent::Unreachable(reason, delay)
+    }
+}
+
+// POST CSV to an endpoint, returning the status code and the body of the response
+fn post(endpoint: &Endpoint, body: &str) -> Result<(u16, String), String> {
+    let address = endpoint
+        .host
+        .to_socket_addrs()
+        .map_err(|error| format!("Cannot find {}: {}", endpoint.host, error))?
+        .next()
+        .ok_or_else(|| format!("Cannot find {}", endpoint.host))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|error| format!("Cannot connect to {}: {}", endpoint.host, error))?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|error| error.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|error| error.to_string())?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/csv\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).map_err(|error| format!("Cannot send to {}: {}", endpoint.host, error))?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).map_err(|error| format!("No response from {}: {}", endpoint.host, error))?;
+    parse_response(&String::from_utf8_lossy(&response))
+}
+
+fn parse_response(response: &str) -> Result<(u16, String), String> {
+    let status = response
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("HTTP/1."))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or("The response was not HTTP")?;
+    let body = response.split_once("\r\n\r\n").map(|(_, body)| body.trim()).unwrap_or_default();
+    Ok((status, body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, Endpoint, Sent, Uploader};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint = Endpoint::new("http://localhost:8000/", "web", "load").unwrap();
+        assert_eq!(endpoint, Endpoint { host: "localhost:8000".to_string(), path: "/api/ingest/web/load".to_string() });
+        let endpoint = Endpoint::new("http://example.com/tests", "web", "load").unwrap();
+        assert_eq!(endpoint, Endpoint { host: "example.com:80".to_string(), path: "/tests/api/ingest/web/load".to_string() });
+        assert!(Endpoint::new("https://example.com", "web", "load").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response("HTTP/1.1 200 OK\r\ncontent-length: 17\r\n\r\nAdded 2 data points"), Ok((200, "Added 2 data points".to_string())));
+        assert!(parse_response("SSH-2.0-OpenSSH").is_err());
+    }
+
+    // Answers each request with the next status, and returns the bodies it was sent
+    fn stand_in(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(reader.get_mut(), "HTTP/1.1 {} Status\r\nContent-Length: 2\r\n\r\nok", status).unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_send() {
+        let (url, handle) = stand_in(vec![503, 200, 200]);
+        let mut uploader = Uploader::new(Endpoint::new(&url, "web", "load").unwrap(), 10, 2, Duration::from_secs(5));
+        uploader.add("2024-05-01T12:00:00Z", 512, 20);
+        uploader.add("2024-05-01T12:00:05Z", 520, 25);
+        uploader.add("2024-05-01T12:00:10Z", 530, 30);
+
+        let now = Instant::now();
+        assert_eq!(uploader.send(now), Sent::Unreachable("503 ok".to_string(), Duration::from_secs(5)));
+        assert_eq!(uploader.send(now + Duration::from_secs(1)), Sent::Nothing); // Waits before trying again
+        assert_eq!(uploader.send(now + Duration::from_secs(5)), Sent::Accepted(2));
+        assert_eq!(uploader.send(now + Duration::from_secs(5)), Sent::Accepted(1));
+        assert_eq!(uploader.pending(), 0);
+
+        let bodies = handle.join().unwrap();
+        assert_eq!(bodies[0], "time,ram,cpu\n2024-05-01T12:00:00Z,512,20\n2024-05-01T12:00:05Z,520,25\n");
+        assert_eq!(bodies[1], bodies[0]); // The same samples again
+        assert_eq!(bodies[2], "time,ram,cpu\n2024-05-01T12:00:10Z,530,30\n");
+    }
+
+    #[test]
+    fn test_rejected_samples_are_dropped() {
+        let (url, handle) = stand_in(vec![422]);
+        let mut uploader = Uploader::new(Endpoint::new(&url, "web", "load").unwrap(), 10, 10, Duration::from_secs(5));
+        uploader.add("2024-05-01T12:00:00Z", 512, 20);
+        assert_eq!(uploader.send(Instant::now()), Sent::Rejected(1, "422 ok".to_string()));
+        assert_eq!(uploader.pending(), 0);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener); // Nothing is listening any more
+
+        let mut uploader = Uploader::new(Endpoint::new(&url, "web", "load").unwrap(), 2, 10, Duration::from_secs(5));
+        for second in 0..3 {
+            uploader.add(&format!("2024-05-01T12:00:0{}Z", second), 512, 20);
+        }
+        assert_eq!((uploader.pending(), uploader.dropped), (2, 1)); // The oldest made room
+
+        let now = Instant::now();
+        assert!(matches!(uploader.send(now), Sent::Unreachable(_, delay) if delay == Duration::from_secs(5)));
+        assert!(matches!(uploader.send(now + Duration::from_secs(5)), Sent::Unreachable(_, delay) if delay == Duration::from_secs(10)));
+        assert_eq!(uploader.pending(), 2);
+    }
+}
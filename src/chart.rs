@@ -0,0 +1,346 @@
+// Line charts of a test's data, drawn as SVG on the server so the pages need no charting library.
+// RAM is plotted against the left axis and CPU against the right, with each data point placed by its time
+use crate::html::{html, Html};
+use crate::models::DataPoint;
+use crate::timestamp::Timestamp;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 400.0;
+const LEFT: f64 = 70.0; // Room for the RAM axis labels
+const RIGHT: f64 = 60.0; // Room for the CPU axis labels
+const TOP: f64 = 40.0; // Room for the legend
+const BOTTOM: f64 = 60.0; // Room for the time labels
+
+const Y_TICKS: u32 = 5;
+const TIME_LABELS: i64 = 5;
+
+const RAM_COLOUR: &str = "#007bff";
+const CPU_COLOUR: &str = "#28a745";
+const LIMIT_COLOUR: &str = "#dc3545";
+const COMMENT_COLOUR: &str = "#fd7e14";
+const AXIS_COLOUR: &str = "#6c757d";
+
+// Chart RAM and CPU over time, with a line at the server's RAM and a marker at each data point with a comment
+// The data points must be in time order, as they are stored
+pub fn test_chart(data_points: &[DataPoint], server_ram: u32) -> Html {
+    let mut output = html!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"100%\" font-family=\"sans-serif\" font-size=\"12\" role=\"img\">\n",
+        WIDTH,
+        HEIGHT
+    );
+
+    if data_points.is_empty() {
+        output.push(html!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">No data points yet.</text>\n</svg>",
+            WIDTH / 2.0,
+            HEIGHT / 2.0
+        ));
+        return output;
+    }
+
+    // The server's RAM is always on the chart, so it is clear how close the test came to it
+    let highest_ram = data_points.iter().map(|data_point| data_point.get_ram()).max().unwrap_or(0);
+    let ram_scale = Scale::new(nice_ceiling(highest_ram.max(server_ram)));
+    let highest_cpu = data_points.iter().map(|data_point| data_point.get_cpu()).max().unwrap_or(0);
+    let cpu_scale = Scale::new(nice_ceiling(highest_cpu.max(100)));
+    let time_scale = TimeScale::new(data_points[0].get_time(), data_points[data_points.len() - 1].get_time());
+    let x = |index: usize| time_scale.x(data_points[index].get_time());
+
+    output.push(axes(&ram_scale, &cpu_scale));
+    output.push(time_labels(&time_scale));
+
+    // Server RAM reference line
+    let limit_y = ram_scale.y(server_ram);
+    output.push(html!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-dasharray=\"6 4\"><title>Server RAM: {} MB</title></line>\n",
+        LEFT,
+        number(limit_y),
+        WIDTH - RIGHT,
+        number(limit_y),
+        LIMIT_COLOUR,
+        server_ram
+    ));
+
+    // Comment markers, drawn under the lines
+    for (index, data_point) in data_points.iter().enumerate() {
+        if let Some(comment) = data_point.get_comment() {
+            output.push(html!(
+                "<g><title>{}: {}</title><line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-dasharray=\"2 3\"/><circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{}\"/></g>\n",
+                data_point.get_time(),
+                comment,
+                number(x(index)),
+                TOP,
+                number(x(index)),
+                HEIGHT - BOTTOM,
+                COMMENT_COLOUR,
+                number(x(index)),
+                TOP,
+                COMMENT_COLOUR
+            ));
+        }
+    }
+
+    let ram_points: Vec<(f64, f64)> = data_points.iter().enumerate().map(|(i, data_point)| (x(i), ram_scale.y(data_point.get_ram()))).collect();
+    let cpu_points: Vec<(f64, f64)> = data_points.iter().enumerate().map(|(i, data_point)| (x(i), cpu_scale.y(data_point.get_cpu()))).collect();
+    output.push(series(&ram_points, RAM_COLOUR));
+    output.push(series(&cpu_points, CPU_COLOUR));
+
+    output.push(legend());
+    output.push(html!("</svg>"));
+    output
+}
+
+// Maps values from 0 to max onto the height of the plot
+struct Scale {
+    max: u32,
+}
+
+impl Scale {
+    fn new(max: u32) -> Self {
+        Self { max }
+    }
+
+    fn y(&self, value: u32) -> f64 {
+        let plot_height = HEIGHT - TOP - BOTTOM;
+        HEIGHT - BOTTOM - plot_height * value as f64 / self.max as f64
+    }
+
+    fn tick(&self, i: u32) -> u32 {
+        (self.max as u64 * i as u64 / Y_TICKS as u64) as u32
+    }
+}
+
+// Maps times from the first to the last data point onto the width of the plot
+struct TimeScale {
+    start: i64, // Unix seconds
+    end: i64,
+}
+
+impl TimeScale {
+    fn new(start: Timestamp, end: Timestamp) -> Self {
+        Self {
+            start: start.unix_seconds(),
+            end: end.unix_seconds(),
+        }
+    }
+
+    fn x(&self, time: Timestamp) -> f64 {
+        let plot_width = WIDTH - LEFT - RIGHT;
+        if self.end <= self.start { // Only one time, so put it in the middle
+            return LEFT + plot_width / 2.0;
+        }
+        LEFT + plot_width * (time.unix_seconds() - self.start) as f64 / (self.end - self.start) as f64
+    }
+}
+
+// The smallest 1, 2 or 5 times a power of ten at or above value, so the axis ticks are round numbers
+fn nice_ceiling(value: u32) -> u32 {
+    let mut step: u64 = 1;
+    loop {
+        for multiple in [1, 2, 5] {
+            if step * multiple >= value as u64 {
+                return (step * multiple).min(u32::MAX as u64) as u32;
+            }
+        }
+        step *= 10;
+    }
+}
+
+fn axes(ram_scale: &Scale, cpu_scale: &Scale) -> Html {
+    let mut output = Html::default();
+    for i in 0..=Y_TICKS {
+        let y = ram_scale.y(ram_scale.tick(i)); // The same height on both axes
+        output.push(html!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-opacity=\"0.2\"/>\n",
+            LEFT,
+            number(y),
+            WIDTH - RIGHT,
+            number(y),
+            AXIS_COLOUR
+        ));
+        output.push(html!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>\n",
+            LEFT - 6.0,
+            number(y),
+            ram_scale.tick(i)
+        ));
+        output.push(html!(
+            "<text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\">{}</text>\n",
+            WIDTH - RIGHT + 6.0,
+            number(y),
+            cpu_scale.tick(i)
+        ));
+    }
+
+    output.push(html!(
+        "<path d=\"M {} {} V {} H {} V {}\" fill=\"none\" stroke=\"{}\"/>\n",
+        LEFT,
+        TOP,
+        HEIGHT - BOTTOM,
+        WIDTH - RIGHT,
+        TOP,
+        AXIS_COLOUR
+    ));
+
+    let middle = number(TOP + (HEIGHT - TOP - BOTTOM) / 2.0);
+    output.push(html!(
+        "<text transform=\"translate(16 {}) rotate(-90)\" text-anchor=\"middle\" fill=\"{}\">RAM (MB)</text>\n",
+        middle,
+        RAM_COLOUR
+    ));
+    output.push(html!(
+        "<text transform=\"translate({} {}) rotate(90)\" text-anchor=\"middle\" fill=\"{}\">CPU (%)</text>\n",
+        WIDTH - 16.0,
+        middle,
+        CPU_COLOUR
+    ));
+    output.push(html!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Time (UTC)</text>\n", LEFT + (WIDTH - LEFT - RIGHT) / 2.0, HEIGHT - 8.0));
+    output
+}
+
+// Evenly spaced times along the bottom, from the first data point to the last, with the date above the time
+fn time_labels(time_scale: &TimeScale) -> Html {
+    let labels = if time_scale.end > time_scale.start { TIME_LABELS } else { 1 };
+    let mut output = Html::default();
+    for i in 0..labels {
+        let seconds = if labels == 1 {
+            time_scale.start
+        } else {
+            time_scale.start + (time_scale.end - time_scale.start) * i / (labels - 1)
+        };
+        let time = match Timestamp::from_unix_seconds(seconds) {
+            Some(time) => time,
+            None => continue,
+        };
+        let (date, clock) = time.short().split_once(' ').map(|(date, clock)| (date.to_string(), clock.to_string())).unwrap_or_default();
+        let x = number(time_scale.x(time));
+        output.push(html!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\"><tspan x=\"{}\">{}</tspan><tspan x=\"{}\" dy=\"14\">{}</tspan></text>\n",
+            x,
+            HEIGHT - BOTTOM + 16.0,
+            x,
+            date,
+            x,
+            clock
+        ));
+    }
+    output
+}
+
+fn series(points: &[(f64, f64)], colour: &str) -> Html {
+    let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", number(*x), number(*y))).collect();
+    let mut output = html!(
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+        points.join(" "),
+        colour
+    );
+    if points.len() == 1 { // A single point has no line, so mark it
+        let (x, y) = points[0].split_once(',').unwrap();
+        output.push(html!("<circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"{}\"/>\n", x, y, colour));
+    }
+    output
+}
+
+fn legend() -> Html {
+    let mut output = Html::default();
+    let entries = [("RAM", RAM_COLOUR), ("CPU", CPU_COLOUR), ("Server RAM", LIMIT_COLOUR), ("Comment", COMMENT_COLOUR)];
+    for (i, (name, colour)) in entries.iter().enumerate() {
+        let x = LEFT + i as f64 * 110.0;
+        output.push(html!(
+            "<rect x=\"{}\" y=\"10\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"{}\" y=\"20\">{}</text>\n",
+            x,
+            colour,
+            x + 16.0,
+            name
+        ));
+    }
+    output
+}
+
+// Coordinates to one decimal place, which is finer than a pixel
+fn number(value: f64) -> String {
+    format!("{:.1}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nice_ceiling, test_chart, Scale, TimeScale, BOTTOM, HEIGHT, LEFT, RIGHT, TOP, WIDTH};
+    use crate::models::DataPoint;
+    use crate::timestamp::Timestamp;
+    use std::str::FromStr;
+
+    fn time(time: &str) -> Timestamp {
+        Timestamp::from_str(time).unwrap()
+    }
+
+    fn data_point(at: &str, ram: u32, cpu: u32, comment: Option<&str>) -> DataPoint {
+        let mut data_point = DataPoint::new(time(at), ram, cpu);
+        data_point.set_comment(comment.map(str::to_string));
+        data_point
+    }
+
+    #[test]
+    fn test_nice_ceiling() {
+        assert_eq!(nice_ceiling(0), 1);
+        assert_eq!(nice_ceiling(1), 1);
+        assert_eq!(nice_ceiling(3), 5);
+        assert_eq!(nice_ceiling(100), 100);
+        assert_eq!(nice_ceiling(101), 200);
+        assert_eq!(nice_ceiling(16384), 20000);
+        assert_eq!(nice_ceiling(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn test_scale() {
+        let scale = Scale::new(200);
+        assert_eq!(scale.y(0), HEIGHT - BOTTOM);
+        assert_eq!(scale.y(200), TOP);
+        assert_eq!(scale.tick(5), 200);
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let scale = TimeScale::new(time("2024-05-01T10:00:00Z"), time("2024-05-01T11:00:00Z"));
+        assert_eq!(scale.x(time("2024-05-01T10:00:00Z")), LEFT);
+        assert_eq!(scale.x(time("2024-05-01T12:00:00+01:00")), WIDTH - RIGHT); // The same instant as the end
+        assert_eq!(scale.x(time("2024-05-01T10:15:00Z")), LEFT + (WIDTH - LEFT - RIGHT) / 4.0); // By time, not by position
+
+        let single = TimeScale::new(time("2024-05-01"), time("2024-05-01"));
+        assert_eq!(single.x(time("2024-05-01")), LEFT + (WIDTH - LEFT - RIGHT) / 2.0);
+    }
+
+    #[test]
+    fn test_chart_contents() {
+        let data = [
+            data_point("2024-05-01T10:00:00Z", 1000, 20, None),
+            data_point("2024-05-01T10:01:00Z", 3000, 80, Some("spike")),
+            data_point("2024-05-01T10:04:00Z", 2000, 50, None),
+        ];
+        let output = test_chart(&data, 4000).to_string();
+
+        assert!(output.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(output.ends_with("</svg>"));
+        assert_eq!(output.matches("<polyline").count(), 2);
+        assert!(output.contains("<title>Server RAM: 4000 MB</title>"));
+        assert!(output.contains("<title>2024-05-01T10:01:00Z: spike</title>"));
+        assert_eq!(output.matches("<circle").count(), 1); // Only the commented point
+        assert!(output.contains(">5000</text>")); // RAM axis rounded up from the server's 4000 MB
+        assert!(output.contains(">2024-05-01</tspan>"));
+        assert!(output.contains(">10:00</tspan>") && output.contains(">10:01</tspan>") && output.contains(">10:04</tspan>"));
+    }
+
+    #[test]
+    fn test_chart_escapes_text() {
+        let data = [data_point("2024-05-01", 1, 1, Some("</title><script>alert(1)</script>"))];
+        let output = test_chart(&data, 1).to_string();
+        assert!(!output.contains("<script>"), "{}", output);
+        assert!(output.contains("&lt;/title&gt;&lt;script&gt;"));
+    }
+
+    #[test]
+    fn test_empty_chart() {
+        let output = test_chart(&[], 1024).to_string();
+        assert!(output.contains("No data points yet."));
+        assert!(!output.contains("<polyline"));
+    }
+}
@@ -0,0 +1,147 @@
+// Changes to data points as they happen, for pages watching a test.
+// Handlers publish to the bus once a change is saved, and each open event stream subscribes to it
+// and passes on the changes to its own test
+use std::fmt::{Display, Formatter};
+use rocket::response::stream::Event;
+use rocket::tokio::sync::broadcast;
+use crate::json::Json;
+use crate::models::DataPoint;
+use crate::timestamp::Timestamp;
+
+// Changes kept for streams that have fallen behind. A stream that misses more, e.g. during a large ingest,
+// is told to reload instead
+const EVENT_BUFFER: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Created => write!(f, "created"),
+            Change::Updated => write!(f, "updated"),
+            Change::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DataPointEvent {
+    pub server_id: String,
+    pub test_id: String,
+    pub change: Change,
+    pub time: Timestamp, // Of the data point, or what it was before an update
+    pub data_point: Option<DataPoint>, // As it is now, unless deleted
+}
+
+impl DataPointEvent {
+    pub fn new(server_id: &str, test_id: &str, change: Change, time: Timestamp, data_point: Option<DataPoint>) -> Self {
+        Self { server_id: server_id.to_string(), test_id: test_id.to_string(), change, time, data_point }
+    }
+
+    pub fn is_for(&self, server_id: &str, test_id: &str) -> bool {
+        self.server_id == server_id && self.test_id == test_id
+    }
+
+    // Server-sent, named by the change
+    pub fn to_event(&self) -> Event {
+        Event::data(self.data()).event(self.change.to_string())
+    }
+
+    // The data point as JSON, e.g. {"time":"2024-05-01T12:00:00Z","ram":512,"cpu":20,"comment":null,"metrics":{"latency":1.5}}.
+    // Updates give the time before the change as old_time, and deletions only the time
+    fn data(&self) -> String {
+        let mut fields = Vec::new();
+        match &self.data_point {
+            Some(data_point) => {
+                fields.push(("time".to_string(), Json::String(data_point.get_time().to_string())));
+                if self.change == Change::Updated {
+                    fields.push(("old_time".to_string(), Json::String(self.time.to_string())));
+                }
+                fields.push(("ram".to_string(), Json::Number(data_point.get_ram().to_string())));
+                fields.push(("cpu".to_string(), Json::Number(data_point.get_cpu().to_string())));
+                fields.push(("comment".to_string(), data_point.get_comment().map(Json::String).unwrap_or(Json::Null)));
+                let metrics = data_point.get_metrics().iter().map(|(name, value)| (name.clone(), Json::Number(value.to_string()))).collect();
+                fields.push(("metrics".to_string(), Json::Object(metrics)));
+            }
+            None => fields.push(("time".to_string(), Json::String(self.time.to_string()))),
+        }
+        Json::Object(fields).to_string()
+    }
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<DataPointEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(EVENT_BUFFER).0 }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: DataPointEvent) {
+        let _ = self.sender.send(event); // Only fails when no one is watching
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DataPointEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, DataPointEvent, EventBus};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use rocket::tokio::sync::broadcast::error::RecvError;
+    use crate::models::DataPoint;
+    use crate::timestamp::Timestamp;
+
+    fn data_point(time: &str) -> DataPoint {
+        let mut data_point = DataPoint::new(Timestamp::from_str(time).unwrap(), 512, 20);
+        data_point.set_metrics(BTreeMap::from([("latency".to_string(), 1.5)]));
+        data_point
+    }
+
+    #[rocket::async_test]
+    async fn test_publish() {
+        let bus = EventBus::default();
+        bus.publish(DataPointEvent::new("web", "load", Change::Created, Timestamp::now(), None)); // No one to receive it
+
+        let mut receiver = bus.subscribe();
+        let time = Timestamp::from_str("2024-05-01T12:00:00Z").unwrap();
+        bus.publish(DataPointEvent::new("web", "load", Change::Deleted, time, None));
+        let event = receiver.recv().await.unwrap();
+        assert!(event.is_for("web", "load"));
+        assert!(!event.is_for("web", "other"));
+        assert_eq!((event.change, event.time), (Change::Deleted, time));
+    }
+
+    #[rocket::async_test]
+    async fn test_lagging_receiver() {
+        let bus = EventBus::default();
+        let mut receiver = bus.subscribe();
+        for _ in 0..super::EVENT_BUFFER + 1 {
+            bus.publish(DataPointEvent::new("web", "load", Change::Created, Timestamp::now(), None));
+        }
+        assert!(matches!(receiver.recv().await, Err(RecvError::Lagged(1))));
+    }
+
+    #[test]
+    fn test_data() {
+        let old_time = Timestamp::from_str("2024-05-01T11:00:00Z").unwrap();
+        let event = DataPointEvent::new("web", "load", Change::Updated, old_time, Some(data_point("2024-05-01T12:00:00Z")));
+        assert_eq!(
+            event.data(),
+            r#"{"time":"2024-05-01T12:00:00Z","old_time":"2024-05-01T11:00:00Z","ram":512,"cpu":20,"comment":null,"metrics":{"latency":1.5}}"#
+        );
+        let event = DataPointEvent::new("web", "load", Change::Deleted, old_time, None);
+        assert_eq!(event.data(), r#"{"time":"2024-05-01T11:00:00Z"}"#);
+    }
+}
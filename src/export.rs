@@ -0,0 +1,260 @@
+// Downloads of test data, as CSV, a JSON array or NDJSON.
+// Columns are always in the same order: test (when several tests are exported together), time, ram, cpu, comment,
+// then each custom metric by name. Files can be sent back to the bulk ingestion endpoint as they are
+use std::str::FromStr;
+use rocket::http::{ContentType, Header};
+use rocket::Responder;
+use crate::json::Json;
+use crate::models::DataPoint;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    fn content_type(&self) -> ContentType {
+        match self {
+            ExportFormat::Csv => ContentType::CSV,
+            ExportFormat::Json => ContentType::JSON,
+            ExportFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!("format: '{}' is not csv, json or ndjson", s)),
+        }
+    }
+}
+
+// A file for the browser to save rather than show
+#[derive(Responder)]
+pub struct Download {
+    body: String,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Text(String),
+    Number(String),
+    Empty,
+}
+
+// The rows of an export, before they are written in a format
+pub struct Export {
+    with_test: bool,
+    metrics: Vec<String>, // Names of the custom metric columns, in order
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Export {
+    // with_test adds a first column saying which test each data point is from
+    pub fn new(with_test: bool, mut metrics: Vec<String>) -> Self {
+        metrics.sort();
+        metrics.dedup();
+        Self { with_test, metrics, rows: Vec::new() }
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns = Vec::new();
+        if self.with_test {
+            columns.push("test".to_string());
+        }
+        columns.extend(["time", "ram", "cpu", "comment"].map(String::from));
+        columns.extend(self.metrics.iter().cloned());
+        columns
+    }
+
+    pub fn add(&mut self, test_id: &str, data_point: &DataPoint) {
+        let mut row = Vec::new();
+        if self.with_test {
+            row.push(Cell::Text(test_id.to_string()));
+        }
+        row.push(Cell::Text(data_point.get_time().to_string()));
+        row.push(Cell::Number(data_point.get_ram().to_string()));
+        row.push(Cell::Number(data_point.get_cpu().to_string()));
+        row.push(data_point.get_comment().map(Cell::Text).unwrap_or(Cell::Empty));
+        for metric in &self.metrics {
+            row.push(data_point.get_metric(metric).map(|value| Cell::Number(value.to_string())).unwrap_or(Cell::Empty));
+        }
+        self.rows.push(row);
+    }
+
+    // The export as a file named name, with the format's extension
+    pub fn download(&self, format: ExportFormat, name: &str) -> Download {
+        let body = match format {
+            ExportFormat::Csv => self.to_csv(),
+            ExportFormat::Json => Json::Array(self.objects()).to_string(),
+            ExportFormat::Ndjson => self.objects().iter().map(|object| format!("{}\n", object)).collect(),
+        };
+        // Names are made of IDs, which cannot contain quotes, so need no escaping here
+        let disposition = format!("attachment; filename=\"{}.{}\"", name, format.extension());
+        Download { body, content_type: format.content_type(), disposition: Header::new("Content-Disposition", disposition) }
+    }
+
+    // RFC 4180, with CRLF line endings and a byte order mark so Excel reads it as UTF-8
+    fn to_csv(&self) -> String {
+        let mut output = String::from("\u{feff}");
+        output.push_str(&self.columns().join(","));
+        output.push_str("\r\n");
+        for row in &self.rows {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Text(text) => csv_text(text),
+                    Cell::Number(number) => number.clone(),
+                    Cell::Empty => String::new(),
+                })
+                .collect();
+            output.push_str(&cells.join(","));
+            output.push_str("\r\n");
+        }
+        output
+    }
+
+    fn objects(&self) -> Vec<Json> {
+        let columns = self.columns();
+        self.rows
+            .iter()
+            .map(|row| {
+                let fields = columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| {
+                        let value = match cell {
+                            Cell::Text(text) => Json::String(text.clone()),
+                            Cell::Number(number) => Json::Number(number.clone()),
+                            Cell::Empty => Json::Null,
+                        };
+                        (column.clone(), value)
+                    })
+                    .collect();
+                Json::Object(fields)
+            })
+            .collect()
+    }
+}
+
+// Quote text that would otherwise split the cell or row, doubling any quotes inside it.
+// Text that a spreadsheet would run as a formula is quoted and starts with ', so it shows as text.
+// Text that already starts with ' gets another, so ingestion can always take one off
+fn csv_text(text: &str) -> String {
+    let text = if text.starts_with(['=', '+', '-', '@', '\t', '\r', '\'']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    };
+    if text.contains([',', '"', '\n', '\r']) || text.starts_with('\'') || text.trim() != text {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_text, Export, ExportFormat};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use crate::ingest::{read_data_points, IngestFormat};
+    use crate::models::{DataPoint, Test};
+    use crate::timestamp::Timestamp;
+
+    fn data_point(time: &str, comment: Option<&str>, metrics: &[(&str, f64)]) -> DataPoint {
+        let mut data_point = DataPoint::new(Timestamp::from_str(time).unwrap(), 512, 20);
+        data_point.set_comment(comment.map(String::from));
+        data_point.set_metrics(metrics.iter().map(|(name, value)| (name.to_string(), *value)).collect::<BTreeMap<_, _>>());
+        data_point
+    }
+
+    fn export() -> Export {
+        let mut export = Export::new(true, vec!["latency".to_string(), "errors".to_string()]);
+        export.add("test", &data_point("2024-05-01T12:00:00Z", Some("said \"hi\""), &[("latency", 1.5)]));
+        export.add("other", &data_point("2024-05-01T13:00:00+01:00", None, &[("errors", 2.0), ("removed", 1.0)]));
+        export
+    }
+
+    #[test]
+    fn test_csv() {
+        let output = export().to_csv();
+        assert_eq!(
+            output,
+            "\u{feff}test,time,ram,cpu,comment,errors,latency\r\n\
+             test,2024-05-01T12:00:00Z,512,20,\"said \"\"hi\"\"\",,1.5\r\n\
+             other,2024-05-01T13:00:00+01:00,512,20,,2,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let download = export().download(ExportFormat::Json, "server");
+        assert_eq!(
+            download.body,
+            r#"[{"test":"test","time":"2024-05-01T12:00:00Z","ram":512,"cpu":20,"comment":"said \"hi\"","errors":null,"latency":1.5},{"test":"other","time":"2024-05-01T13:00:00+01:00","ram":512,"cpu":20,"comment":null,"errors":2,"latency":null}]"#
+        );
+        assert_eq!(download.disposition.value(), "attachment; filename=\"server.json\"");
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut export = Export::new(false, Vec::new());
+        export.add("test", &data_point("2024-05-01T12:00:00Z", None, &[]));
+        export.add("test", &data_point("2024-05-01T12:01:00Z", Some("b"), &[]));
+        let download = export.download(ExportFormat::Ndjson, "server_test");
+        assert_eq!(
+            download.body,
+            "{\"time\":\"2024-05-01T12:00:00Z\",\"ram\":512,\"cpu\":20,\"comment\":null}\n{\"time\":\"2024-05-01T12:01:00Z\",\"ram\":512,\"cpu\":20,\"comment\":\"b\"}\n"
+        );
+        assert_eq!(download.content_type.to_string(), "application/x-ndjson");
+    }
+
+    #[test]
+    fn test_csv_text() {
+        assert_eq!(csv_text("plain"), "plain");
+        assert_eq!(csv_text("a,b"), "\"a,b\"");
+        assert_eq!(csv_text("=SUM(A1)"), "\"'=SUM(A1)\""); // Not run as a formula
+        assert_eq!(csv_text("-low"), "\"'-low\"");
+        assert_eq!(csv_text(" padded"), "\" padded\"");
+        assert_eq!(csv_text("'quoted"), "\"''quoted\"");
+    }
+
+    #[rocket::async_test]
+    async fn test_csv_round_trip() {
+        let comments = ["-low", "=SUM(A1)", "'quoted", "+1", "@home", "said \"hi\""];
+        let mut export = Export::new(false, Vec::new());
+        for (minute, comment) in comments.iter().enumerate() {
+            export.add("test", &data_point(&format!("2024-05-01T12:0{}:00Z", minute), Some(comment), &[]));
+        }
+
+        // Sent back to the bulk ingestion endpoint, every comment is as it was
+        let data_points = read_data_points(&Test::new("test".to_string()), IngestFormat::Csv, &export.to_csv()).await.unwrap();
+        let read: Vec<String> = data_points.as_slice().await.iter().map(|data_point| data_point.get_comment().unwrap()).collect();
+        assert_eq!(read, comments);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(ExportFormat::from_str("CSV"), Ok(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_str("xlsx"), Err("format: 'xlsx' is not csv, json or ndjson".to_string()));
+    }
+}
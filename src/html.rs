@@ -0,0 +1,176 @@
+use std::fmt::{Display, Formatter};
+
+// Markup that is safe to put straight into a page.
+// Only built by the html! macro, which escapes every value put into it, or from trusted strings with Html::raw
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Html(String);
+
+impl Html {
+    // Wrap markup without escaping it, only for strings that cannot contain user input
+    pub fn raw(markup: impl Into<String>) -> Self {
+        Self(markup.into())
+    }
+
+    // Escape text so it shows as-is
+    pub fn text(text: &str) -> Self {
+        Self(escape(text))
+    }
+
+    pub fn push(&mut self, other: Html) {
+        self.0.push_str(&other.0);
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Display for Html {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// How a value is written into markup by the html! macro
+pub trait ToHtml {
+    fn to_html(&self) -> String;
+}
+
+impl ToHtml for Html {
+    fn to_html(&self) -> String {
+        self.0.clone() // Already safe
+    }
+}
+
+impl ToHtml for str {
+    fn to_html(&self) -> String {
+        escape(self)
+    }
+}
+
+impl ToHtml for String {
+    fn to_html(&self) -> String {
+        escape(self)
+    }
+}
+
+impl<T: ToHtml> ToHtml for Option<T> {
+    fn to_html(&self) -> String {
+        match self {
+            Some(value) => value.to_html(),
+            None => String::new(),
+        }
+    }
+}
+
+impl<T: ToHtml + ?Sized> ToHtml for &T {
+    fn to_html(&self) -> String {
+        (**self).to_html()
+    }
+}
+
+// Numbers cannot contain markup, so are written as they are
+macro_rules! number_to_html {
+    ($($number:ty),*) => {
+        $(impl ToHtml for $number {
+            fn to_html(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+number_to_html!(u32, u64, usize, i32, i64, f64);
+
+// Like format!, but every argument is HTML-escaped unless it is already Html.
+// Safe for element content and quoted attribute values
+macro_rules! html {
+    ($template:literal $(, $argument:expr)* $(,)?) => {
+        $crate::html::Html::raw(format!($template $(, $crate::html::ToHtml::to_html(&$argument))*))
+    };
+}
+
+pub(crate) use html;
+
+// Replace the characters that could end an element or a quoted attribute
+pub fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+// Percent-encode a value so it can be put in a query string
+pub fn encode_query_value(value: &str) -> String {
+    let mut output = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => output.push(byte as char),
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    output
+}
+
+// Build a URL from a path and query parameters, encoding each value.
+// The result still needs escaping to go in an attribute, which html! does
+pub fn url(path: &str, parameters: &[(&str, &str)]) -> String {
+    let mut output = path.to_string();
+    for (i, (name, value)) in parameters.iter().enumerate() {
+        output.push(if i == 0 { '?' } else { '&' });
+        output.push_str(name);
+        output.push('=');
+        output.push_str(&encode_query_value(value));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_query_value, escape, url, Html};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<script>alert('x')</script>"), "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;");
+        assert_eq!(escape("a \"b\" & c"), "a &quot;b&quot; &amp; c");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_macro_escapes_arguments() {
+        let name = "<b>\"bob\"</b>".to_string();
+        let comment: Option<String> = None;
+        let output = html!("<td title=\"{}\">{}{}{}{}</td>", name, name, 42u32, comment, Html::raw("<br>"));
+        assert_eq!(output.into_string(), "<td title=\"&lt;b&gt;&quot;bob&quot;&lt;/b&gt;\">&lt;b&gt;&quot;bob&quot;&lt;/b&gt;42<br></td>");
+    }
+
+    #[test]
+    fn test_push() {
+        let mut output = html!("<ul>");
+        output.push(html!("<li>{}</li>", "a<b"));
+        output.push(Html::text("</ul>"));
+        assert_eq!(output.into_string(), "<ul><li>a&lt;b</li>&lt;/ul&gt;");
+    }
+
+    #[test]
+    fn test_encode_query_value() {
+        assert_eq!(encode_query_value("abc-123_.~"), "abc-123_.~");
+        assert_eq!(encode_query_value("a&b=c d"), "a%26b%3Dc%20d");
+        assert_eq!(encode_query_value("'\"<"), "%27%22%3C");
+        assert_eq!(encode_query_value("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(url("/page", &[]), "/page");
+        assert_eq!(url("/test-data", &[("server_id", "a b"), ("test_id", "x&y")]), "/test-data?server_id=a%20b&test_id=x%26y");
+    }
+}
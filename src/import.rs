@@ -0,0 +1,583 @@
+// Importing the results of a test run, from a JUnit XML report or a simple JSON report.
+// Each test case becomes a test of the server, created the first time it is seen, and each run of it a data point
+// at the time of the report. How long it took and whether it passed are kept as the custom metrics duration (seconds)
+// and passed (1 or 0, left out when skipped), with the outcome and any message as the comment.
+// Reports do not say how much RAM or CPU was used, so both are recorded as 0
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use rocket::http::ContentType;
+use crate::ingest::MAX_LISTED_ERRORS;
+use crate::json::Json;
+use crate::keyed_vector::KeyedVector;
+use crate::models::{DataPoint, Metric, MetricKind, Test};
+use crate::timestamp::Timestamp;
+use crate::validation::{MAX_COMMENT_LENGTH, MAX_ID_LENGTH};
+use crate::xml::Element;
+
+pub const DURATION_METRIC: &str = "duration";
+pub const PASSED_METRIC: &str = "passed";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    JUnit,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn from_content_type(content_type: &ContentType) -> Option<Self> {
+        let (top, sub) = (content_type.top(), content_type.sub());
+        if (top == "application" || top == "text") && sub == "xml" {
+            Some(Self::JUnit)
+        } else if top == "application" && sub == "json" {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    Error, // The test itself broke, rather than an assertion failing
+    Skipped,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "passed"),
+            Outcome::Failed => write!(f, "failed"),
+            Outcome::Error => write!(f, "error"),
+            Outcome::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    // Runners word these differently, so the common spellings are all accepted
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "passed" | "pass" | "success" | "ok" => Ok(Self::Passed),
+            "failed" | "fail" | "failure" => Ok(Self::Failed),
+            "error" | "errored" => Ok(Self::Error),
+            "skipped" | "skip" | "ignored" | "pending" => Ok(Self::Skipped),
+            _ => Err(format!("'{}' is not passed, failed, error or skipped", s)),
+        }
+    }
+}
+
+// One run of a test case, as the report gives it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    pub name: String, // As the report names it, e.g. LoginTest.testLogin
+    pub time: Option<Timestamp>, // When it ran, if the report says
+    pub duration: Option<f64>, // Seconds
+    pub outcome: Outcome,
+    pub message: Option<String>,
+}
+
+// Read every test case of a report.
+// Errors are one line per problem, e.g. "testLogin: time: must be a number of seconds"
+pub fn read_report(format: ReportFormat, body: &str) -> Result<Vec<TestResult>, Vec<String>> {
+    let body = body.trim_start_matches('\u{feff}');
+    let (results, mut errors) = match format {
+        ReportFormat::JUnit => junit_results(body)?,
+        ReportFormat::Json => json_results(body)?,
+    };
+
+    if !errors.is_empty() {
+        if errors.len() > MAX_LISTED_ERRORS {
+            let more = errors.len() - MAX_LISTED_ERRORS;
+            errors.truncate(MAX_LISTED_ERRORS);
+            errors.push(format!("and {} more errors", more));
+        }
+        return Err(errors);
+    }
+    if results.is_empty() {
+        return Err(vec!["The report has no test cases".to_string()]);
+    }
+    Ok(results)
+}
+
+type Results = (Vec<TestResult>, Vec<String>);
+
+// <testsuites> holding <testsuite>s, or a single <testsuite>, each holding <testcase>s.
+// Suites can be nested, and a test case without a timestamp of its own takes its suite's
+fn junit_results(body: &str) -> Result<Results, Vec<String>> {
+    let root = Element::parse(body).map_err(|error| vec![error])?;
+    if root.name != "testsuites" && root.name != "testsuite" {
+        return Err(vec![format!("Expected <testsuites> or <testsuite>, not <{}>", root.name)]);
+    }
+    let mut results = (Vec::new(), Vec::new());
+    junit_suite(&root, None, &mut results);
+    Ok(results)
+}
+
+fn junit_suite(suite: &Element, time: Option<Timestamp>, results: &mut Results) {
+    let time = match junit_timestamp(suite) {
+        Ok(Some(own)) => Some(own),
+        Ok(None) => time,
+        Err(error) => {
+            results.1.push(format!("testsuite {}: {}", suite.attribute("name").unwrap_or_default(), error));
+            return;
+        }
+    };
+
+    for child in &suite.children {
+        match child.name.as_str() {
+            "testsuite" => junit_suite(child, time, results),
+            "testcase" => {
+                let label = match child.attribute("name") {
+                    Some(name) if !name.trim().is_empty() => name.to_string(),
+                    _ => format!("test case {}", results.0.len() + results.1.len() + 1),
+                };
+                match junit_case(child, time) {
+                    Ok(result) => results.0.push(result),
+                    Err(error) => results.1.push(format!("{}: {}", label, error)),
+                }
+            }
+            _ => {} // Properties, and output captured from the whole suite
+        }
+    }
+}
+
+fn junit_timestamp(element: &Element) -> Result<Option<Timestamp>, String> {
+    match element.attribute("timestamp") {
+        Some(timestamp) => Timestamp::from_str(timestamp).map(Some).map_err(|error| format!("timestamp: {}", error)),
+        None => Ok(None),
+    }
+}
+
+fn junit_case(case: &Element, time: Option<Timestamp>) -> Result<TestResult, String> {
+    let name = case.attribute("name").map(str::trim).unwrap_or_default();
+    if name.is_empty() {
+        return Err("name: cannot be empty".to_string());
+    }
+    // Only the class itself, as package names would make most IDs too long
+    let name = match case.attribute("classname").and_then(|class| class.rsplit('.').next()).map(str::trim) {
+        Some(class) if !class.is_empty() => format!("{}.{}", class, name),
+        _ => name.to_string(),
+    };
+
+    let duration = match case.attribute("time") {
+        Some(seconds) => Some(seconds_value(&seconds.replace(',', "")).map_err(|error| format!("time: {}", error))?),
+        None => None,
+    };
+
+    let mut outcome = Outcome::Passed;
+    let mut message = None;
+    for child in &case.children {
+        let child_outcome = match child.name.as_str() {
+            "failure" => Outcome::Failed,
+            "error" => Outcome::Error,
+            "skipped" => Outcome::Skipped,
+            _ => continue, // e.g. system-out
+        };
+        outcome = child_outcome;
+        message = child
+            .attribute("message")
+            .map(String::from)
+            .or_else(|| child.text.lines().map(str::trim).find(|line| !line.is_empty()).map(String::from))
+            .filter(|message| !message.trim().is_empty());
+        break;
+    }
+
+    Ok(TestResult { name, time: junit_timestamp(case)?.or(time), duration, outcome, message })
+}
+
+fn seconds_value(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(seconds),
+        _ => Err("must be a number of seconds".to_string()),
+    }
+}
+
+// {"timestamp": "2024-05-01T12:00:00Z", "tests": [{"name": "login", "outcome": "passed", "duration": 1.5, "message": null}]}.
+// Only name and outcome are needed, and fields not listed here are left alone, so runners can add their own
+fn json_results(body: &str) -> Result<Results, Vec<String>> {
+    let fields = match Json::parse(body).map_err(|error| vec![error])? {
+        Json::Object(fields) => fields,
+        other => return Err(vec![format!("Expected an object with a tests array, not {}", other.kind())]),
+    };
+    let time = match field(&fields, "timestamp") {
+        Some(Json::String(timestamp)) => Some(Timestamp::from_str(timestamp).map_err(|error| vec![format!("timestamp: {}", error)])?),
+        None | Some(Json::Null) => None,
+        Some(other) => return Err(vec![format!("timestamp: expected text, not {}", other.kind())]),
+    };
+    let tests = match field(&fields, "tests") {
+        Some(Json::Array(tests)) => tests,
+        Some(other) => return Err(vec![format!("tests: expected an array, not {}", other.kind())]),
+        None => return Err(vec!["tests: is missing".to_string()]),
+    };
+
+    let mut results = (Vec::new(), Vec::new());
+    for (index, test) in tests.iter().enumerate() {
+        match json_test(test, time) {
+            Ok(result) => results.0.push(result),
+            Err(error) => results.1.push(format!("test {}: {}", index + 1, error)),
+        }
+    }
+    Ok(results)
+}
+
+fn json_test(test: &Json, time: Option<Timestamp>) -> Result<TestResult, String> {
+    let fields = match test {
+        Json::Object(fields) => fields,
+        other => return Err(format!("expected an object, not {}", other.kind())),
+    };
+    let name = match json_text(fields, "name")? {
+        Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => return Err("name: cannot be empty".to_string()),
+    };
+    let outcome = match json_text(fields, "outcome")? {
+        Some(outcome) => Outcome::from_str(&outcome).map_err(|error| format!("outcome: {}", error))?,
+        None => return Err("outcome: is missing".to_string()),
+    };
+    let duration = match field(fields, "duration") {
+        Some(Json::Number(seconds)) => Some(seconds_value(seconds).map_err(|error| format!("duration: {}", error))?),
+        None | Some(Json::Null) => None,
+        Some(other) => return Err(format!("duration: expected a number, not {}", other.kind())),
+    };
+    let time = match json_text(fields, "timestamp")? {
+        Some(timestamp) => Some(Timestamp::from_str(&timestamp).map_err(|error| format!("timestamp: {}", error))?),
+        None => time,
+    };
+    let message = json_text(fields, "message")?.filter(|message| !message.trim().is_empty());
+
+    Ok(TestResult { name, time, duration, outcome, message })
+}
+
+fn field<'a>(fields: &'a [(String, Json)], name: &str) -> Option<&'a Json> {
+    fields.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+}
+
+fn json_text(fields: &[(String, Json)], name: &str) -> Result<Option<String>, String> {
+    match field(fields, name) {
+        Some(Json::String(text)) => Ok(Some(text.clone())),
+        None | Some(Json::Null) => Ok(None),
+        Some(other) => Err(format!("{}: expected text, not {}", name, other.kind())),
+    }
+}
+
+// What an import did, to save and to tell the user
+#[derive(Default)]
+pub struct ImportReport {
+    pub created: Vec<String>, // IDs of the tests created
+    pub changed: Vec<String>, // IDs of every test given a data point, including the created ones
+    pub added: Vec<(String, DataPoint)>, // Each data point added, with the ID of its test, in the order of the report
+    pub skipped: Vec<String>, // Why each result that was not added was left out
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.created.is_empty() {
+            writeln!(f, "Created {}: {}", count(self.created.len(), "test"), self.created.join(", "))?;
+        }
+        write!(f, "Added {}", count(self.added.len(), "data point"))?;
+        if !self.changed.is_empty() {
+            write!(f, " to {}", count(self.changed.len(), "test"))?;
+        }
+        if !self.skipped.is_empty() {
+            write!(f, "\nSkipped {}:", count(self.skipped.len(), "result"))?;
+            for reason in self.skipped.iter().take(MAX_LISTED_ERRORS) {
+                write!(f, "\n{}", reason)?;
+            }
+            if self.skipped.len() > MAX_LISTED_ERRORS {
+                write!(f, "\nand {} more", self.skipped.len() - MAX_LISTED_ERRORS)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn count(number: usize, noun: &str) -> String {
+    format!("{} {}{}", number, noun, if number == 1 { "" } else { "s" })
+}
+
+// Add results to a server's tests, creating any that do not exist yet.
+// now is the time given to results from reports that do not say when they ran
+pub async fn import_results(tests: &mut KeyedVector<Test>, results: &[TestResult], now: Timestamp) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for result in results {
+        let test_id = match test_id(&result.name) {
+            Some(test_id) => test_id,
+            None => {
+                report.skipped.push(format!("{}: has no letters or numbers to make a test ID from", result.name));
+                continue;
+            }
+        };
+        if !tests.contains_key(&test_id).await {
+            let _ = tests.insert(Test::new(test_id.clone())).await; // Cannot conflict, checked above
+            report.created.push(test_id.clone());
+        }
+
+        let test = tests.get_mut_by_key(&test_id).await.unwrap();
+        match add_result(test, result, now).await {
+            Ok(data_point) => {
+                if !report.changed.contains(&test_id) {
+                    report.changed.push(test_id.clone());
+                }
+                report.added.push((test_id, data_point));
+            }
+            Err(reason) => report.skipped.push(format!("{}: {}", result.name, reason)),
+        }
+    }
+
+    report
+}
+
+async fn add_result(test: &mut Test, result: &TestResult, now: Timestamp) -> Result<DataPoint, String> {
+    let time = result.time.unwrap_or(now);
+    if test.find_time(time).await.is_some() { // Most likely the same report, imported again
+        return Err(format!("test '{}' already has a data point at {}", test.get_id(), time));
+    }
+
+    let mut metrics = BTreeMap::new();
+    if let Some(duration) = result.duration {
+        metrics.insert(DURATION_METRIC.to_string(), duration);
+    }
+    match result.outcome {
+        Outcome::Passed => metrics.insert(PASSED_METRIC.to_string(), 1.0),
+        Outcome::Failed | Outcome::Error => metrics.insert(PASSED_METRIC.to_string(), 0.0),
+        Outcome::Skipped => None, // Neither, so it does not count towards the pass rate
+    };
+
+    // A test may already have a metric of the same name, set up by hand
+    let mut missing = Vec::new();
+    for (name, value) in &metrics {
+        match test.metrics.get_by_key(name).await {
+            Some(metric) => {
+                metric.parse_value(&value.to_string()).map_err(|error| format!("{}: {}", name, error))?;
+            }
+            None => missing.push(import_metric(name)),
+        }
+    }
+    for metric in missing {
+        let _ = test.metrics.insert(metric).await; // Cannot conflict, checked above
+    }
+
+    let mut data_point = DataPoint::new(time, 0, 0);
+    data_point.set_comment(Some(comment(result)));
+    data_point.set_metrics(metrics);
+    test.data.insert_sorted(data_point.clone()).await;
+    Ok(data_point)
+}
+
+fn import_metric(name: &str) -> Metric {
+    match name {
+        DURATION_METRIC => Metric::new(name.to_string(), "s".to_string(), MetricKind::Decimal),
+        _ => Metric::new(name.to_string(), String::new(), MetricKind::Integer),
+    }
+}
+
+// e.g. "failed: expected 1 but was 2". Comments are stored in comma-separated files,
+// so commas and line breaks are taken out, and long messages are cut short
+fn comment(result: &TestResult) -> String {
+    let text = match &result.message {
+        Some(message) => format!("{}: {}", result.outcome, message),
+        None => result.outcome.to_string(),
+    };
+    let words: Vec<&str> = text.split(|c: char| c == ',' || c.is_whitespace()).filter(|word| !word.is_empty()).collect();
+    words.join(" ").chars().take(MAX_COMMENT_LENGTH).collect::<String>().trim_end().to_string()
+}
+
+// The ID of the test a result is added to. A name that is already a valid ID is used as it is.
+// Otherwise the characters IDs do not allow are replaced, and the start of a hash of the whole name is added,
+// so names that look alike, such as a.b and a_b, always get different tests, whichever is imported first
+fn test_id(name: &str) -> Option<String> {
+    if name.len() <= MAX_ID_LENGTH && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Some(name.to_string()).filter(|name| !name.is_empty());
+    }
+
+    let mut base = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            base.push(c);
+        } else if !base.is_empty() && !base.ends_with('_') {
+            base.push('_');
+        }
+    }
+    let base = base.trim_end_matches('_');
+    if base.is_empty() {
+        return None;
+    }
+
+    let hash = hmac_sha256::Hash::hash(name.as_bytes());
+    let suffix = format!("_{:02x}{:02x}{:02x}{:02x}", hash[0], hash[1], hash[2], hash[3]);
+    Some(format!("{}{}", &base[..base.len().min(MAX_ID_LENGTH - suffix.len())], suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comment, import_results, read_report, test_id, Outcome, ReportFormat, TestResult, DURATION_METRIC, PASSED_METRIC};
+    use std::str::FromStr;
+    use crate::keyed_vector::KeyedVector;
+    use crate::models::{Metric, MetricKind, Test};
+    use crate::timestamp::Timestamp;
+
+    const JUNIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="login" timestamp="2024-05-01T12:00:00" tests="3">
+    <properties><property name="os" value="linux"/></properties>
+    <testcase name="testLogin" classname="com.example.LoginTest" time="1.5"/>
+    <testcase name="testLogout" classname="com.example.LoginTest" time="0.25">
+      <failure message="expected 1, got 2" type="AssertionError">stack trace</failure>
+    </testcase>
+    <testcase name="testSignup" classname="com.example.LoginTest" time="0"><skipped/></testcase>
+  </testsuite>
+  <testsuite name="other">
+    <testcase name="crashes" time="1,200.5"><error>
+      NullPointerException
+    </error></testcase>
+  </testsuite>
+</testsuites>"#;
+
+    fn time(text: &str) -> Timestamp {
+        Timestamp::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_junit() {
+        let results = read_report(ReportFormat::JUnit, JUNIT).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0], TestResult {
+            name: "LoginTest.testLogin".to_string(),
+            time: Some(time("2024-05-01T12:00:00Z")),
+            duration: Some(1.5),
+            outcome: Outcome::Passed,
+            message: None,
+        });
+        assert_eq!((results[1].outcome, results[1].message.as_deref()), (Outcome::Failed, Some("expected 1, got 2")));
+        assert_eq!(results[2].outcome, Outcome::Skipped);
+        assert_eq!(results[3], TestResult {
+            name: "crashes".to_string(),
+            time: None, // Its suite has no timestamp
+            duration: Some(1200.5),
+            outcome: Outcome::Error,
+            message: Some("NullPointerException".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_junit_errors() {
+        let report = r#"<testsuite timestamp="yesterday"><testcase name="a"/></testsuite>"#;
+        assert_eq!(read_report(ReportFormat::JUnit, report).unwrap_err(), vec!["testsuite : timestamp: 'yesterday' is not a date and time, e.g. 2024-05-01T14:30:00Z"]);
+        let report = r#"<testsuite><testcase name="a" time="slow"/><testcase/></testsuite>"#;
+        assert_eq!(read_report(ReportFormat::JUnit, report).unwrap_err(), vec!["a: time: must be a number of seconds", "test case 2: name: cannot be empty"]);
+        assert_eq!(read_report(ReportFormat::JUnit, "<html/>").unwrap_err(), vec!["Expected <testsuites> or <testsuite>, not <html>"]);
+        assert_eq!(read_report(ReportFormat::JUnit, "<testsuites/>").unwrap_err(), vec!["The report has no test cases"]);
+    }
+
+    #[test]
+    fn test_json() {
+        let report = r#"{"timestamp": "2024-05-01T12:00:00Z", "runner": "custom", "tests": [
+            {"name": "login", "outcome": "PASS", "duration": 1.5},
+            {"name": "logout", "outcome": "failed", "message": "timed out", "timestamp": "2024-05-01T12:05:00Z"}
+        ]}"#;
+        let results = read_report(ReportFormat::Json, report).unwrap();
+        assert_eq!(results[0], TestResult { name: "login".to_string(), time: Some(time("2024-05-01T12:00:00Z")), duration: Some(1.5), outcome: Outcome::Passed, message: None });
+        assert_eq!(results[1], TestResult {
+            name: "logout".to_string(),
+            time: Some(time("2024-05-01T12:05:00Z")),
+            duration: None,
+            outcome: Outcome::Failed,
+            message: Some("timed out".to_string()),
+        });
+
+        let report = r#"{"tests": [{"name": "a", "outcome": "maybe"}, {"outcome": "passed", "duration": -1}, 3]}"#;
+        assert_eq!(read_report(ReportFormat::Json, report).unwrap_err(), vec![
+            "test 1: outcome: 'maybe' is not passed, failed, error or skipped",
+            "test 2: name: cannot be empty",
+            "test 3: expected an object, not a number",
+        ]);
+        assert_eq!(read_report(ReportFormat::Json, "[]").unwrap_err(), vec!["Expected an object with a tests array, not an array"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_import_results() {
+        let mut tests = KeyedVector::new();
+        let _ = tests.insert(Test::new("LoginTest_testLogin_e8dfc221".to_string())).await;
+        let results = read_report(ReportFormat::JUnit, JUNIT).unwrap();
+        let now = time("2024-06-01T00:00:00Z");
+
+        let report = import_results(&mut tests, &results, now).await;
+        assert_eq!(report.created, vec!["LoginTest_testLogout_2dbb195b", "LoginTest_testSignup_9e04652a", "crashes"]);
+        assert_eq!(report.added.len(), 4);
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.to_string(), "Created 3 tests: LoginTest_testLogout_2dbb195b, LoginTest_testSignup_9e04652a, crashes\nAdded 4 data points to 4 tests");
+
+        let login = tests.get_by_key("LoginTest_testLogin_e8dfc221").await.unwrap();
+        let data_point = login.data.get(0).await.unwrap();
+        assert_eq!(data_point.get_time(), time("2024-05-01T12:00:00Z"));
+        assert_eq!((data_point.get_metric(DURATION_METRIC), data_point.get_metric(PASSED_METRIC)), (Some(1.5), Some(1.0)));
+        assert_eq!(login.metrics.get_by_key(DURATION_METRIC).await.unwrap().label(), "duration (s)");
+
+        let crashes = tests.get_by_key("crashes").await.unwrap().data.get(0).await.unwrap();
+        assert_eq!(crashes.get_time(), now);
+        assert_eq!(crashes.get_comment().as_deref(), Some("error: NullPointerException"));
+        let skipped = tests.get_by_key("LoginTest_testSignup_9e04652a").await.unwrap().data.get(0).await.unwrap();
+        assert_eq!(skipped.get_metric(PASSED_METRIC), None);
+
+        // Importing the same report again adds nothing
+        let report = import_results(&mut tests, &results[..1], now).await;
+        assert_eq!(report.to_string(), "Added 0 data points\nSkipped 1 result:\nLoginTest.testLogin: test 'LoginTest_testLogin_e8dfc221' already has a data point at 2024-05-01T12:00:00Z");
+    }
+
+    #[rocket::async_test]
+    async fn test_existing_metrics_are_checked() {
+        let mut test = Test::new("slow".to_string());
+        let _ = test.metrics.insert(Metric::new(DURATION_METRIC.to_string(), "ms".to_string(), MetricKind::Integer)).await;
+        let mut tests = KeyedVector::new();
+        let _ = tests.insert(test).await;
+
+        let result = TestResult { name: "slow".to_string(), time: None, duration: Some(0.5), outcome: Outcome::Passed, message: None };
+        let report = import_results(&mut tests, &[result], Timestamp::now()).await;
+        assert_eq!(report.skipped, vec!["slow: duration: must be a whole number"]);
+        assert!(tests.get_by_key("slow").await.unwrap().metrics.get_by_key(PASSED_METRIC).await.is_none()); // Nothing was changed
+    }
+
+    #[test]
+    fn test_test_id() {
+        assert_eq!(test_id("test_login-1").as_deref(), Some("test_login-1")); // Already a valid ID
+        assert_eq!(test_id("LoginTest.test login[1]").as_deref(), Some("LoginTest_test_login_1_b675c204"));
+        assert_eq!(test_id("LoginTest.test_login(1)").as_deref(), Some("LoginTest_test_login_1_c7485550"));
+        assert_eq!(test_id("..."), None);
+        assert_eq!(test_id(""), None);
+        assert_eq!(test_id(&"a".repeat(100)).unwrap().len(), 64);
+        assert_ne!(test_id(&"a".repeat(100)), test_id(&"a".repeat(101)));
+    }
+
+    #[rocket::async_test]
+    async fn test_names_that_look_alike() {
+        let result = |name: &str| TestResult { name: name.to_string(), time: None, duration: None, outcome: Outcome::Passed, message: None };
+        let forwards = [result("a_b"), result("a.b")];
+        let backwards = [result("a.b"), result("a_b")];
+
+        // Each name gets the same test whichever comes first, in one report or across several
+        for (first, second) in [(&forwards, &backwards), (&backwards, &forwards)] {
+            let mut tests = KeyedVector::new();
+            let report = import_results(&mut tests, first, time("2024-06-01T00:00:00Z")).await;
+            let mut created = report.created.clone();
+            created.sort();
+            assert_eq!(created, vec!["a_b", "a_b_2e7336dc"]);
+
+            let report = import_results(&mut tests, second, time("2024-06-02T00:00:00Z")).await;
+            assert!(report.created.is_empty());
+            assert_eq!(report.added.len(), 2);
+            assert_eq!(tests.get_by_key("a_b").await.unwrap().data.length, 2);
+            assert_eq!(tests.get_by_key("a_b_2e7336dc").await.unwrap().data.length, 2);
+        }
+    }
+
+    #[test]
+    fn test_comment() {
+        let result = TestResult { name: "a".to_string(), time: None, duration: None, outcome: Outcome::Failed, message: Some("expected 1,\n got 2".to_string()) };
+        assert_eq!(comment(&result), "failed: expected 1 got 2");
+    }
+}
@@ -0,0 +1,412 @@
+// Reading many data points at once, for tests that record thousands of samples.
+// A body is CSV with a header row, a JSON array of objects, or NDJSON (one object per line).
+// Columns and fields are named like a data point: time, ram, cpu, comment, then the test's custom metrics by name.
+// Every row is checked, and the data points are only returned if every row is valid
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use rocket::http::ContentType;
+use crate::json::Json;
+use crate::models::{DataPoint, Test, RESERVED_METRIC_NAMES};
+use crate::my_vector::MyVector;
+use crate::timestamp::Timestamp;
+use crate::validation::{check_number, check_text, MAX_COMMENT_LENGTH, MAX_CPU_PERCENT, MAX_RAM};
+
+// Beyond this, the rest of the errors are counted instead of listed
+pub const MAX_LISTED_ERRORS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IngestFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl IngestFormat {
+    pub fn from_content_type(content_type: &ContentType) -> Option<Self> {
+        let (top, sub) = (content_type.top(), content_type.sub());
+        if top == "text" && sub == "csv" {
+            Some(Self::Csv)
+        } else if top == "application" && sub == "json" {
+            Some(Self::Json)
+        } else if top == "application" && (sub == "x-ndjson" || sub == "ndjson" || sub == "jsonl") {
+            Some(Self::Ndjson)
+        } else {
+            None
+        }
+    }
+}
+
+// One row of the body, before it is checked
+struct Row {
+    label: String, // Where it is in the body, e.g. "line 3"
+    fields: Result<Vec<(String, Value)>, String>, // Or why the row could not be read at all
+}
+
+enum Value {
+    Missing, // Empty, or null
+    Given(String),
+    Invalid(String), // Why it cannot be a value, e.g. it is a JSON array
+}
+
+// Read and check every row of a body against a test.
+// Errors are one "line 3: ram: must be a whole number" line per problem
+pub async fn read_data_points(test: &Test, format: IngestFormat, body: &str) -> Result<MyVector<DataPoint>, Vec<String>> {
+    let rows = match format {
+        IngestFormat::Csv => csv_rows(test, body).await?,
+        IngestFormat::Json => json_array_rows(body)?,
+        IngestFormat::Ndjson => ndjson_rows(body),
+    };
+    if rows.is_empty() {
+        return Err(vec!["No data points were given".to_string()]);
+    }
+
+    let mut existing = HashSet::new();
+    for data_point in test.data.as_slice().await {
+        existing.insert(data_point.get_time());
+    }
+    let mut given: HashMap<Timestamp, String> = HashMap::new(); // Where each time in the body first appears
+
+    let mut data_points = MyVector::new();
+    let mut errors = Vec::new();
+    for row in rows {
+        let fields = match row.fields {
+            Ok(fields) => fields,
+            Err(error) => {
+                errors.push(format!("{}: {}", row.label, error));
+                continue;
+            }
+        };
+
+        let data_point = match data_point(test, &fields).await {
+            Ok(data_point) => data_point,
+            Err(field_errors) => {
+                errors.extend(field_errors.into_iter().map(|error| format!("{}: {}", row.label, error)));
+                continue;
+            }
+        };
+
+        // Data points are looked up by time, so it must be unique within the test
+        let time = data_point.get_time();
+        if existing.contains(&time) {
+            errors.push(format!("{}: time: there is already a data point at '{}'", row.label, time));
+        } else if let Some(first) = given.get(&time) {
+            errors.push(format!("{}: time: '{}' is also given on {}", row.label, time, first));
+        } else {
+            given.insert(time, row.label);
+            data_points.push(data_point).await;
+        }
+    }
+
+    if !errors.is_empty() {
+        if errors.len() > MAX_LISTED_ERRORS {
+            let more = errors.len() - MAX_LISTED_ERRORS;
+            errors.truncate(MAX_LISTED_ERRORS);
+            errors.push(format!("and {} more errors", more));
+        }
+        return Err(errors);
+    }
+
+    data_points.quick_sort().await; // Kept in time order, like the test's data
+    Ok(data_points)
+}
+
+// Check the fields of one row, and make the data point
+async fn data_point(test: &Test, fields: &[(String, Value)]) -> Result<DataPoint, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    let (mut time, mut ram, mut cpu, mut comment) = (None, None, None, None);
+    let mut metrics = BTreeMap::new();
+
+    for (name, value) in fields {
+        if !seen.insert(name.as_str()) {
+            errors.push(format!("{}: is given more than once", name));
+            continue;
+        }
+        let value = match value {
+            Value::Given(value) if !value.trim().is_empty() => value,
+            Value::Invalid(error) => {
+                errors.push(format!("{}: {}", name, error));
+                continue;
+            }
+            _ => continue, // Left out, which is only a problem for the required fields below
+        };
+
+        let result = match name.as_str() {
+            "time" => Timestamp::from_str(value).map(|value| time = Some(value)),
+            "ram" => check_number(value, MAX_RAM).map(|value| ram = Some(value)),
+            "cpu" => check_number(value, MAX_CPU_PERCENT).map(|value| cpu = Some(value)),
+            "comment" => check_text(value, false, MAX_COMMENT_LENGTH).map(|_| comment = Some(value.clone())),
+            name => match test.metrics.get_by_key(name).await {
+                Some(metric) => metric.parse_value(value).map(|value| {
+                    metrics.insert(name.to_string(), value);
+                }),
+                None => Err("is not a metric of this test".to_string()),
+            },
+        };
+        if let Err(error) = result {
+            errors.push(format!("{}: {}", name, error));
+        }
+    }
+
+    for (name, missing) in [("time", time.is_none()), ("ram", ram.is_none()), ("cpu", cpu.is_none())] {
+        if missing && !errors.iter().any(|error| error.starts_with(&format!("{}: ", name))) {
+            errors.push(format!("{}: is required", name));
+        }
+    }
+
+    match (time, ram, cpu) {
+        (Some(time), Some(ram), Some(cpu)) if errors.is_empty() => {
+            let mut data_point = DataPoint::new(time, ram, cpu);
+            data_point.set_comment(comment);
+            data_point.set_metrics(metrics);
+            Ok(data_point)
+        }
+        _ => Err(errors),
+    }
+}
+
+// CSV with a header row naming the columns. The header is checked once, rather than on every row
+async fn csv_rows(test: &Test, body: &str) -> Result<Vec<Row>, Vec<String>> {
+    let body = body.strip_prefix('\u{feff}').unwrap_or(body); // Byte order mark, as written by exports and spreadsheets
+    let mut lines = body.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (header_number, header) = match lines.next() {
+        Some(header) => header,
+        None => return Ok(Vec::new()),
+    };
+
+    let columns: Vec<String> = match split_csv_line(header) {
+        Ok(columns) => columns.into_iter().map(|column| column.trim().to_string()).collect(),
+        Err(error) => return Err(vec![format!("line {}: {}", header_number + 1, error)]),
+    };
+    let mut errors = Vec::new();
+    for (index, column) in columns.iter().enumerate() {
+        if columns[..index].contains(column) {
+            errors.push(format!("line {}: {}: is given more than once", header_number + 1, column));
+        } else if !RESERVED_METRIC_NAMES.contains(&column.as_str()) && !test.metrics.contains_key(column).await {
+            errors.push(format!("line {}: {}: is not a metric of this test", header_number + 1, column));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut rows = Vec::new();
+    for (number, line) in lines {
+        let fields = match split_csv_line(line) {
+            Ok(values) if values.len() == columns.len() => Ok(columns.iter().cloned().zip(values.into_iter().map(Value::Given)).collect()),
+            Ok(values) => Err(format!("expected {} values, found {}", columns.len(), values.len())),
+            Err(error) => Err(error),
+        };
+        rows.push(Row { label: format!("line {}", number + 1), fields });
+    }
+    Ok(rows)
+}
+
+// Split a line of CSV into its values. Values may be quoted, with "" for a quote inside them.
+// Exports start quoted text with ' when a spreadsheet would run it as a formula, so that ' is taken back off
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    let mut was_quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            (true, '"') => {
+                quoted = false;
+                if !matches!(chars.peek(), None | Some(',')) {
+                    return Err("a quoted value must be followed by a comma".to_string());
+                }
+            }
+            (false, '"') if value.trim().is_empty() => {
+                value.clear();
+                quoted = true;
+                was_quoted = true;
+            }
+            (false, ',') => values.push(unescape(std::mem::take(&mut value), std::mem::take(&mut was_quoted))),
+            (_, c) => value.push(c),
+        }
+    }
+    if quoted {
+        return Err("a quoted value is not closed".to_string());
+    }
+    values.push(unescape(value, was_quoted));
+    Ok(values)
+}
+
+fn unescape(value: String, was_quoted: bool) -> String {
+    match value.strip_prefix('\'') {
+        Some(text) if was_quoted => text.to_string(),
+        _ => value,
+    }
+}
+
+fn json_array_rows(body: &str) -> Result<Vec<Row>, Vec<String>> {
+    match Json::parse(body) {
+        Ok(Json::Array(values)) => Ok(values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| json_row(format!("row {}", index + 1), value))
+            .collect()),
+        Ok(value) => Err(vec![format!("Expected an array of data points, found {}", value.kind())]),
+        Err(error) => Err(vec![format!("Invalid JSON: {}", error)]),
+    }
+}
+
+// Each line is read on its own, so one bad line does not hide the problems on the others
+fn ndjson_rows(body: &str) -> Vec<Row> {
+    body.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let label = format!("line {}", number + 1);
+            match Json::parse(line) {
+                Ok(value) => json_row(label, value),
+                Err(error) => Row { label, fields: Err(format!("invalid JSON: {}", error)) },
+            }
+        })
+        .collect()
+}
+
+fn json_row(label: String, value: Json) -> Row {
+    let fields = match value {
+        Json::Object(fields) => fields,
+        value => return Row { label, fields: Err(format!("expected an object, found {}", value.kind())) },
+    };
+
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Json::Null => Value::Missing,
+                Json::Number(value) | Json::String(value) => Value::Given(value),
+                value => Value::Invalid(format!("must be a number or text, not {}", value.kind())),
+            };
+            (name, value)
+        })
+        .collect();
+    Row { label, fields: Ok(fields) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_data_points, split_csv_line, IngestFormat, MAX_LISTED_ERRORS};
+    use rocket::http::ContentType;
+    use crate::models::{DataPoint, Metric, MetricKind, Test};
+    use crate::timestamp::Timestamp;
+    use std::str::FromStr;
+
+    async fn test() -> Test {
+        let mut test = Test::new("test".to_string());
+        let _ = test.metrics.insert(Metric::new("latency".to_string(), "ms".to_string(), MetricKind::Decimal)).await;
+        let _ = test.metrics.insert(Metric::new("errors".to_string(), String::new(), MetricKind::Integer)).await;
+        test.data.push(DataPoint::new(Timestamp::from_str("2024-05-01T09:00:00Z").unwrap(), 1, 1)).await;
+        test
+    }
+
+    async fn read(format: IngestFormat, body: &str) -> Result<Vec<String>, Vec<String>> {
+        let data_points = read_data_points(&test().await, format, body).await?;
+        Ok(data_points.as_slice().await.iter().map(DataPoint::to_string).collect())
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(IngestFormat::from_content_type(&ContentType::CSV), Some(IngestFormat::Csv));
+        assert_eq!(IngestFormat::from_content_type(&ContentType::JSON), Some(IngestFormat::Json));
+        assert_eq!(IngestFormat::from_content_type(&ContentType::new("application", "x-ndjson")), Some(IngestFormat::Ndjson));
+        assert_eq!(IngestFormat::from_content_type(&ContentType::Form), None);
+    }
+
+    #[rocket::async_test]
+    async fn test_csv() {
+        let body = "time,ram,cpu,latency,comment\n2024-05-01T10:00:00Z,512,20,1.5,\"warm, up\"\n\n2024-05-01T09:30:00Z,256,10,,\n";
+        assert_eq!(read(IngestFormat::Csv, body).await.unwrap_err(), vec!["line 2: comment: cannot contain commas or new lines"]);
+
+        let body = "time,ram,cpu,latency,comment\n2024-05-01T10:00:00Z,512,20,1.5,\"said \"\"hi\"\"\"\n\n2024-05-01T09:30:00Z,256,10,,\n";
+        assert_eq!(read(IngestFormat::Csv, body).await.unwrap(), vec![
+            "2024-05-01T09:30:00Z,256,10,", // In time order, with no value for latency
+            "2024-05-01T10:00:00Z,512,20,said \"hi\",latency=1.5",
+        ]);
+    }
+
+    #[rocket::async_test]
+    async fn test_csv_from_a_spreadsheet() {
+        let body = "\u{feff}time,ram,cpu\r\n2024-05-01T10:00:00Z,1,2\r\n";
+        assert_eq!(read(IngestFormat::Csv, body).await.unwrap(), vec!["2024-05-01T10:00:00Z,1,2,"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_csv_header() {
+        assert_eq!(read(IngestFormat::Csv, "time,ram,cpu,disk,ram\n").await.unwrap_err(), vec![
+            "line 1: disk: is not a metric of this test",
+            "line 1: ram: is given more than once",
+        ]);
+        assert_eq!(read(IngestFormat::Csv, "time,ram,cpu\n").await.unwrap_err(), vec!["No data points were given"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_every_row_is_checked() {
+        let body = "time,ram,cpu,errors\nyesterday,-1,101,1.5\n2024-05-01T09:00:00Z,1,1,\n2024-05-01T10:00:00Z,1\n2024-05-01T11:00:00Z,1,1,\n2024-05-01T11:00:00Z,2,2,\n";
+        assert_eq!(read(IngestFormat::Csv, body).await.unwrap_err(), vec![
+            "line 2: time: 'yesterday' is not a date and time, e.g. 2024-05-01T14:30:00Z",
+            "line 2: ram: must be a whole number",
+            "line 2: cpu: value must be between 0 and 100",
+            "line 2: errors: must be a whole number",
+            "line 3: time: there is already a data point at '2024-05-01T09:00:00Z'",
+            "line 4: expected 4 values, found 2",
+            "line 6: time: '2024-05-01T11:00:00Z' is also given on line 5",
+        ]);
+    }
+
+    #[rocket::async_test]
+    async fn test_json_array() {
+        let body = r#"[{"time": "2024-05-01T11:00:00+01:00", "ram": 512, "cpu": "20", "latency": 2, "comment": null}]"#;
+        assert_eq!(read(IngestFormat::Json, body).await.unwrap(), vec!["2024-05-01T11:00:00+01:00,512,20,,latency=2"]);
+
+        let body = r#"[{"time": "2024-05-01T10:00:00Z", "ram": 1, "cpu": 1, "disk": 1}, 5, {"ram": true}]"#;
+        assert_eq!(read(IngestFormat::Json, body).await.unwrap_err(), vec![
+            "row 1: disk: is not a metric of this test",
+            "row 2: expected an object, found a number",
+            "row 3: ram: must be a number or text, not true or false",
+            "row 3: time: is required",
+            "row 3: cpu: is required",
+        ]);
+
+        assert_eq!(read(IngestFormat::Json, "{}").await.unwrap_err(), vec!["Expected an array of data points, found an object"]);
+        assert_eq!(read(IngestFormat::Json, "[").await.unwrap_err(), vec!["Invalid JSON: unexpected end of the document at character 2"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_ndjson() {
+        let body = "{\"time\": \"2024-05-01T10:00:00Z\", \"ram\": 1, \"cpu\": 2, \"errors\": 3}\n\n{\"time\": \"2024-05-01T11:00:00Z\", \"ram\": 1, \"cpu\": 2}\n";
+        assert_eq!(read(IngestFormat::Ndjson, body).await.unwrap(), vec![
+            "2024-05-01T10:00:00Z,1,2,,errors=3",
+            "2024-05-01T11:00:00Z,1,2,",
+        ]);
+
+        let body = "{\"time\": \"2024-05-01T10:00:00Z\", \"ram\": 1, \"cpu\": 2}\n{\"time\": \n";
+        assert_eq!(read(IngestFormat::Ndjson, body).await.unwrap_err(), vec!["line 2: invalid JSON: unexpected end of the document at character 10"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_errors_are_limited() {
+        let body = "time,ram,cpu\n".to_string() + &"x,1,1\n".repeat(MAX_LISTED_ERRORS + 5);
+        let errors = read(IngestFormat::Csv, &body).await.unwrap_err();
+        assert_eq!(errors.len(), MAX_LISTED_ERRORS + 1);
+        assert_eq!(errors.last().unwrap(), "and 5 more errors");
+    }
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(split_csv_line("a,,\"b,c\", d").unwrap(), vec!["a", "", "b,c", " d"]);
+        assert_eq!(split_csv_line("\"a\"\"b\"").unwrap(), vec!["a\"b"]);
+        assert!(split_csv_line("\"a").is_err());
+        assert!(split_csv_line("\"a\"b").is_err());
+        assert_eq!(split_csv_line("\"'-low\",'-low,\"''a\"").unwrap(), vec!["-low", "'-low", "'a"]); // Only quoted text is unescaped
+    }
+}
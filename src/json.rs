@@ -0,0 +1,348 @@
+// A small JSON reader and writer, for data points sent to and exported from the API.
+// Numbers are kept as written, so whole numbers can be told apart from decimals when checked against a metric
+use std::fmt::{Display, Formatter};
+use std::str::Chars;
+use std::iter::Peekable;
+
+// Deeper than any data point goes, and keeps a hostile document from overflowing the stack
+const MAX_DEPTH: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String), // As written, e.g. 1.50
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>), // In the order written
+}
+
+impl Json {
+    // Read a whole document, which must be a single value with nothing after it
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut reader = Reader { chars: text.chars().peekable(), position: 0 };
+        let value = reader.value(0)?;
+        reader.skip_whitespace();
+        if reader.chars.peek().is_some() {
+            return Err(reader.error("expected the end of the document"));
+        }
+        Ok(value)
+    }
+
+    // The kind of value, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "true or false",
+            Json::Number(_) => "a number",
+            Json::String(_) => "text",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+// Written compactly, on one line, so it can also be used for NDJSON
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: usize, // In characters, for error messages
+}
+
+impl Reader<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at character {}", message, self.position + 1)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.next();
+        }
+    }
+
+    // Errors point at the unexpected character, so it is only read if it is the one expected
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.chars.peek() != Some(&expected) {
+            return Err(self.error(&format!("expected '{}'", expected)));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        self.skip_whitespace();
+        if depth >= MAX_DEPTH && matches!(self.chars.peek(), Some('{' | '[')) {
+            return Err(self.error("arrays and objects are nested too deeply"));
+        }
+        match self.chars.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.word("true", Json::Bool(true)),
+            Some('f') => self.word("false", Json::Bool(false)),
+            Some('n') => self.word("null", Json::Null),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of the document")),
+        }
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            self.expect(expected).map_err(|_| self.error(&format!("expected '{}'", word)))?;
+        }
+        Ok(value)
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return Err(self.error("expected a field name"));
+            }
+            let name = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            fields.push((name, self.value(depth + 1)?));
+
+            self.skip_whitespace();
+            if self.chars.peek() == Some(&'}') {
+                self.next();
+                return Ok(Json::Object(fields));
+            }
+            self.expect(',').map_err(|_| self.error("expected ',' or '}'"))?;
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.next();
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.chars.peek() == Some(&']') {
+                self.next();
+                return Ok(Json::Array(values));
+            }
+            self.expect(',').map_err(|_| self.error("expected ',' or ']'"))?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut output = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(output),
+                Some('\\') => match self.next() {
+                    Some('"') => output.push('"'),
+                    Some('\\') => output.push('\\'),
+                    Some('/') => output.push('/'),
+                    Some('b') => output.push('\u{8}'),
+                    Some('f') => output.push('\u{c}'),
+                    Some('n') => output.push('\n'),
+                    Some('r') => output.push('\r'),
+                    Some('t') => output.push('\t'),
+                    Some('u') => output.push(self.unicode_escape()?),
+                    _ => return Err(self.error("unknown escape")),
+                },
+                Some(c) if c < ' ' => return Err(self.error("control characters must be escaped")),
+                Some(c) => output.push(c),
+                None => return Err(self.error("unterminated text")),
+            }
+        }
+    }
+
+    // After \u, including the second half of a surrogate pair
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let first = self.hex_digits()?;
+        if !(0xD800..0xDC00).contains(&first) {
+            return char::from_u32(first).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+
+        if self.next() != Some('\\') || self.next() != Some('u') {
+            return Err(self.error("unpaired surrogate"));
+        }
+        let second = self.hex_digits()?;
+        if !(0xDC00..0xE000).contains(&second) {
+            return Err(self.error("unpaired surrogate"));
+        }
+        char::from_u32(0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16)).ok_or_else(|| self.error("expected four hex digits"))?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut output = String::new();
+        if self.chars.peek() == Some(&'-') {
+            output.push('-');
+            self.next();
+        }
+
+        match self.chars.peek() {
+            Some('0') => {
+                output.push('0');
+                self.next();
+            }
+            Some('1'..='9') => self.digits(&mut output),
+            _ => return Err(self.error("expected a digit")),
+        }
+
+        if self.chars.peek() == Some(&'.') {
+            output.push('.');
+            self.next();
+            if !matches!(self.chars.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.digits(&mut output);
+        }
+
+        if let Some(&e @ ('e' | 'E')) = self.chars.peek() {
+            output.push(e);
+            self.next();
+            if let Some(&sign @ ('+' | '-')) = self.chars.peek() {
+                output.push(sign);
+                self.next();
+            }
+            if !matches!(self.chars.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.digits(&mut output);
+        }
+
+        Ok(Json::Number(output))
+    }
+
+    fn digits(&mut self, output: &mut String) {
+        while let Some(&c @ '0'..='9') = self.chars.peek() {
+            output.push(c);
+            self.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, MAX_DEPTH};
+
+    #[test]
+    fn test_parse() {
+        let json = Json::parse(r#" {"time": "2024-05-01T12:00:00Z", "ram": 512, "cpu": -1.5e3, "ok": true, "note": null, "list": [1, []]} "#).unwrap();
+        assert_eq!(json, Json::Object(vec![
+            ("time".to_string(), Json::String("2024-05-01T12:00:00Z".to_string())),
+            ("ram".to_string(), Json::Number("512".to_string())),
+            ("cpu".to_string(), Json::Number("-1.5e3".to_string())),
+            ("ok".to_string(), Json::Bool(true)),
+            ("note".to_string(), Json::Null),
+            ("list".to_string(), Json::Array(vec![Json::Number("1".to_string()), Json::Array(vec![])])),
+        ]));
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        assert_eq!(Json::parse(r#""a\"b\\c\né😀""#).unwrap(), Json::String("a\"b\\c\né😀".to_string()));
+        assert!(Json::parse(r#""\ud83d""#).is_err()); // Half of a pair
+    }
+
+    #[test]
+    fn test_write() {
+        let json = Json::Object(vec![
+            ("text".to_string(), Json::String("a\"b\\c\n\u{1}é".to_string())),
+            ("list".to_string(), Json::Array(vec![Json::Number("1.5".to_string()), Json::Null, Json::Bool(false)])),
+        ]);
+        let written = json.to_string();
+        assert_eq!(written, r#"{"text":"a\"b\\c\n\u0001é","list":[1.5,null,false]}"#);
+        assert_eq!(Json::parse(&written).unwrap(), json); // Reads back the same
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Json::parse("[1, 2").unwrap_err(), "expected ',' or ']' at character 6");
+        assert_eq!(Json::parse("{\"a\" 1}").unwrap_err(), "expected ':' at character 6");
+        assert_eq!(Json::parse("[1] 2").unwrap_err(), "expected the end of the document at character 5");
+        for invalid in ["", "01", "1.", "-", "tru", "{a: 1}", "[1,]", "\"a\nb\""] {
+            assert!(Json::parse(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(Json::parse(&"[".repeat(100_000)).unwrap_err(), "arrays and objects are nested too deeply at character 101");
+        let deep = "{\"a\":".repeat(1000) + "1" + &"}".repeat(1000);
+        assert!(Json::parse(&deep).unwrap_err().starts_with("arrays and objects are nested too deeply"));
+        let nested = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(Json::parse(&nested).is_ok());
+    }
+}
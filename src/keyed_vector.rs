@@ -0,0 +1,217 @@
+use std::io;
+use std::ops::Deref;
+use std::str::FromStr;
+use crate::my_vector::MyVector;
+
+// Anything with a primary key, so that it can be stored in a KeyedVector
+pub trait Keyed {
+    fn get_key(&self) -> String;
+}
+
+// A MyVector that is always kept ordered by primary key.
+// Keys are unique, and lookups by key use a binary search instead of a linear search
+#[derive(Clone)]
+pub struct KeyedVector<T> {
+    inner: MyVector<T>,
+}
+
+// Allows read-only access to the underlying vector (length, get, search_all, etc)
+// There is no DerefMut, and MyVector only changes through &mut self, so elements can only be added through insert, keeping the order intact
+impl<T> Deref for KeyedVector<T> {
+    type Target = MyVector<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[allow(dead_code)] // General-purpose container, not every method is used by the site yet
+impl<T> KeyedVector<T>
+where
+    T: Keyed,
+    T: Clone + PartialOrd,
+    T: ToString + FromStr,
+    T::Err: std::fmt::Debug,
+{
+    pub fn new() -> KeyedVector<T> {
+        Self {
+            inner: MyVector::new(),
+        }
+    }
+
+    // Wrap an existing vector, sorting it by key
+    pub async fn from_vector(mut vector: MyVector<T>) -> KeyedVector<T> {
+        vector.quick_sort_by(|a, b| a.get_key().cmp(&b.get_key())).await;
+        Self {
+            inner: vector,
+        }
+    }
+
+    // Load from a file, then sort by key
+    pub async fn load_from_file(file_path: &str) -> KeyedVector<T> {
+        Self::from_vector(MyVector::load_from_file(file_path).await).await
+    }
+
+    pub async fn save_to_file(&self, file_path: &str) -> io::Result<()> {
+        self.inner.save_to_file(file_path).await
+    }
+
+    // Binary search for the index of the element with this key
+    pub async fn index_of(&self, key: &str) -> Option<u32> {
+        self.inner
+            .binary_search_by(|a| a.get_key().as_str().cmp(key))
+            .await
+            .ok()
+    }
+
+    pub async fn contains_key(&self, key: &str) -> bool {
+        self.index_of(key).await.is_some()
+    }
+
+    // Get a clone of the element with this key
+    pub async fn get_by_key(&self, key: &str) -> Option<T> {
+        let index = self.index_of(key).await?;
+        self.inner.get(index).await
+    }
+
+    // Get the element with this key as a mutable reference.
+    // The key must not be changed through this reference, use remove_by_key and insert instead
+    pub async fn get_mut_by_key(&mut self, key: &str) -> Option<&mut T> {
+        let index = self.index_of(key).await?;
+        self.inner.get_mut(index).await
+    }
+
+    // Insert an element in key order, returns the index it was inserted at.
+    // If the key is already taken, the element is handed back as the error
+    pub async fn insert(&mut self, value: T) -> Result<u32, T> {
+        let key = value.get_key();
+        match self.inner.binary_search_by(|a| a.get_key().cmp(&key)).await {
+            Ok(_) => Err(value), // Key already exists
+            Err(index) => {
+                self.inner.insert(index, value).await;
+                Ok(index)
+            }
+        }
+    }
+
+    // Find keys that appear more than once, which can only come from data loaded from a file
+    pub async fn duplicate_keys(&self) -> Vec<String> {
+        let mut duplicates = Vec::new();
+        for i in 1..self.inner.length {
+            let previous = self.inner.get(i - 1).await.unwrap().get_key();
+            let current = self.inner.get(i).await.unwrap().get_key();
+            if previous == current && !duplicates.contains(&current) { // Sorted, so duplicates are next to each other
+                duplicates.push(current);
+            }
+        }
+        duplicates
+    }
+
+    // Remove the element with this key, returning it if it existed
+    pub async fn remove_by_key(&mut self, key: &str) -> Option<T> {
+        let index = self.index_of(key).await?;
+        self.inner.remove(index).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keyed, KeyedVector};
+    use crate::my_vector::MyVector;
+    use rocket::tokio;
+
+    impl Keyed for i32 {
+        fn get_key(&self) -> String {
+            format!("{:03}", self) // Pad so that string order matches numeric order
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_vector_sorts_by_key() {
+        let mut vec = MyVector::new();
+        vec.push(30).await;
+        vec.push(10).await;
+        vec.push(20).await;
+
+        let keyed = KeyedVector::from_vector(vec).await;
+
+        assert_eq!(keyed.get(0).await.unwrap(), 10);
+        assert_eq!(keyed.get(1).await.unwrap(), 20);
+        assert_eq!(keyed.get(2).await.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_insert_keeps_order() {
+        let mut keyed = KeyedVector::new();
+        assert_eq!(keyed.insert(5).await, Ok(0));
+        assert_eq!(keyed.insert(1).await, Ok(0));
+        assert_eq!(keyed.insert(3).await, Ok(1));
+        assert_eq!(keyed.insert(9).await, Ok(3));
+
+        assert_eq!(keyed.length, 4);
+        assert_eq!(keyed.get(0).await.unwrap(), 1);
+        assert_eq!(keyed.get(3).await.unwrap(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_insert_duplicate_rejected() {
+        let mut keyed = KeyedVector::new();
+        keyed.insert(1).await.unwrap();
+        keyed.insert(2).await.unwrap();
+
+        assert_eq!(keyed.insert(2).await, Err(2)); // Value is handed back
+        assert_eq!(keyed.length, 2);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_keys() {
+        let mut vec = MyVector::new();
+        for value in [3, 1, 3, 2, 1, 3] {
+            vec.push(value).await;
+        }
+
+        let keyed = KeyedVector::from_vector(vec).await;
+
+        assert_eq!(keyed.duplicate_keys().await, vec!["001".to_string(), "003".to_string()]);
+        assert!(KeyedVector::<i32>::new().duplicate_keys().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_by_key() {
+        let mut keyed = KeyedVector::new();
+        for value in [40, 10, 30, 20, 50] {
+            keyed.insert(value).await.unwrap();
+        }
+
+        assert_eq!(keyed.index_of("030").await, Some(2));
+        assert_eq!(keyed.get_by_key("050").await, Some(50));
+        assert_eq!(keyed.get_by_key("025").await, None);
+        assert!(keyed.contains_key("010").await);
+        assert!(!keyed.contains_key("060").await);
+    }
+
+    #[tokio::test]
+    async fn test_get_mut_by_key() {
+        let mut keyed = KeyedVector::new();
+        keyed.insert(1).await.unwrap();
+        keyed.insert(2).await.unwrap();
+
+        // Changing the value without changing its key keeps the order valid
+        let value = keyed.get_mut_by_key("002").await.unwrap();
+        assert_eq!(*value, 2);
+        assert!(keyed.get_mut_by_key("003").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_remove_by_key() {
+        let mut keyed = KeyedVector::new();
+        keyed.insert(1).await.unwrap();
+        keyed.insert(2).await.unwrap();
+        keyed.insert(3).await.unwrap();
+
+        assert_eq!(keyed.remove_by_key("002").await, Some(2));
+        assert_eq!(keyed.remove_by_key("002").await, None);
+        assert_eq!(keyed.length, 2);
+        assert_eq!(keyed.get_by_key("003").await, Some(3));
+    }
+}
@@ -1,45 +1,87 @@
 mod my_vector;
+mod keyed_vector;
 mod api;
 mod models;
+mod pagination;
+mod query;
+mod html;
+mod templates;
+mod validation;
+mod static_files;
+mod stats;
+mod chart;
+mod timestamp;
+mod migration;
+mod json;
+mod ingest;
+mod export;
+mod xml;
+mod import;
+mod events;
+mod alerts;
+mod webhooks;
+mod notifications;
 
-use std::net::IpAddr;
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use rocket::{get, routes, tokio, uri, Config};
-use rocket::fs::NamedFile;
-use rocket::http::Status;
+use rocket::{catchers, get, routes, tokio, uri, State};
+use rocket::fairing::AdHoc;
 use rocket::response::Redirect;
-use rocket::yansi::Paint;
-use crate::api::{create_datapoint, create_schedule_entry, create_server, create_test, create_user, delete_datapoint, delete_schedule_entry, delete_server, delete_test, delete_user, get_datapoint_info, get_schedule_entries, get_schedule_entry_info, get_server_info, get_servers, get_servers_manager, get_test_data, get_test_info, get_tests, get_user_info, get_users, update_datapoint, update_schedule_entry, update_server, update_test, update_user};
+use crate::api::{create_datapoint, create_schedule_entry, create_server, create_test, create_user, delete_datapoint, delete_schedule_entry, delete_server, delete_test, delete_user, get_datapoint_info, get_schedule_entries, get_schedule_entry_info, get_assignees, add_assignee, remove_assignee, get_user_options, get_server_info, get_servers, get_servers_manager, get_test_data, get_test_chart, get_test_info, get_metrics, get_metric_inputs, create_metric, delete_metric, ingest_datapoints, import_report, export_test, export_server, test_events, get_alert_rules, create_alert_rule, delete_alert_rule, get_alert_events, get_webhooks, create_webhook, delete_webhook, get_webhook_deliveries, get_test_stats, get_tests, get_user_info, get_users, search, update_datapoint, update_schedule_entry, update_server, update_test, update_user};
 use crate::models::SiteData;
+use crate::keyed_vector::KeyedVector;
 use crate::my_vector::MyVector;
+use crate::stats::StatsCache;
+use crate::events::EventBus;
+use crate::alerts::{AlertStates, ALERT_EVENTS_PATH, ALERT_RULES_PATH};
+use crate::webhooks::{Webhooks, WEBHOOKS_PATH, WEBHOOK_DATA_DIRECTORY};
+use crate::notifications::{Notifier, REMINDERS_PATH};
+use crate::static_files::{catch_all, not_found, IfNoneMatch, StaticFiles, StaticResponse};
 
 #[rocket::main]
+#[allow(clippy::result_large_err)] // rocket::Error is large, but only returned once on shutdown
 async fn main() -> Result<(), rocket::Error> {
 
-    let mut user_list = MyVector::load_from_file("./data/users").await; // Load users
-    
-    user_list.quick_sort().await;
+    // Times must be timestamps before anything is loaded, or lines that cannot be read would be lost
+    let data_directory = Path::new("./data");
+    let report = migration::migrate(data_directory).await.expect("Cannot migrate data!");
+    report.log(data_directory);
+
+    let user_list = KeyedVector::load_from_file("./data/users").await; // Load users, sorted by username
     user_list.save_to_file("./data/users").await.expect("Cannot save users!"); // Ensure users are sorted on start
     
-    let mut server_list = MyVector::load_from_file("./data/servers").await; // Load servers 
-    
-    server_list.quick_sort().await;
+    let server_list = KeyedVector::load_from_file("./data/servers").await; // Load servers, sorted by ID
     server_list.save_to_file("./data/servers").await.expect("Cannot save servers!"); // Ensure servers are sorted on start
     
-    let mut schedule = MyVector::load_from_file("./data/schedules").await; // Load schedule
-    
-    schedule.quick_sort().await;
+    let schedule = KeyedVector::load_from_file("./data/schedules").await; // Load schedule, sorted by ID
     schedule.save_to_file("./data/schedules").await.expect("Cannot save schedule!"); // Ensure schedule is sorted on start
     
-    let site_data = Arc::new(Mutex::new(SiteData {
+    let alert_rules = KeyedVector::load_from_file(ALERT_RULES_PATH).await; // Load alert rules, sorted by ID
+    alert_rules.save_to_file(ALERT_RULES_PATH).await.expect("Cannot save alert rules!");
+    let alert_events = MyVector::load_from_file(ALERT_EVENTS_PATH).await; // Only read, as new events are appended
+
+    let webhooks = KeyedVector::load_from_file(WEBHOOKS_PATH).await; // Load webhooks, sorted by ID
+    webhooks.save_to_file(WEBHOOKS_PATH).await.expect("Cannot save webhooks!");
+
+    let site_data = SiteData {
         users: user_list,
         servers: server_list,
         schedules: schedule,
-    }));
+        stats: StatsCache::default(), // Filled in as tests are viewed
+        alert_rules,
+        alerts: AlertStates::from_events(alert_events.as_slice().await),
+        webhooks,
+    };
+    check_integrity(&site_data).await;
+    let site_data = Arc::new(Mutex::new(site_data));
+    let webhook_queue = Arc::new(Webhooks::load(WEBHOOK_DATA_DIRECTORY).await); // Deliveries left from the last run are sent again
 
-    let _ = rocket::build() // Create a new webserver
+    let rocket = rocket::build(); // Create a new webserver
+    let static_files = StaticFiles::from_config(rocket.figment()).expect("Cannot find the public directory!");
+    let notifier = Arc::new(Notifier::from_config(rocket.figment(), REMINDERS_PATH).await); // Sends nothing without smtp_host
+
+    let _ = rocket
         .mount("/api", routes![
             get_users, get_servers,
             get_servers_manager, get_tests,
@@ -53,33 +95,103 @@ async fn main() -> Result<(), rocket::Error> {
             get_test_info, create_schedule_entry,
             update_schedule_entry, delete_schedule_entry,
             get_schedule_entries, get_schedule_entry_info,
+            get_assignees, add_assignee, remove_assignee, get_user_options,
+            search, get_test_stats, get_test_chart,
+            get_metrics, get_metric_inputs, create_metric, delete_metric,
+            ingest_datapoints, import_report, export_test, export_server,
+            test_events,
+            get_alert_rules, create_alert_rule, delete_alert_rule, get_alert_events,
+            get_webhooks, create_webhook, delete_webhook, get_webhook_deliveries,
         ]) // All API calls
         .mount("/", routes![index, login, catch_all]) // All public-facing pages
+        .register("/", catchers![not_found]) // Serve the 404 page for anything that cannot be found
+        .attach(AdHoc::on_liftoff("Webhook deliveries", {
+            let (webhook_queue, site_data) = (webhook_queue.clone(), site_data.clone());
+            move |rocket| Box::pin(async move {
+                tokio::spawn(webhook_queue.run(site_data, rocket.shutdown())); // Sends deliveries in the background
+            })
+        }))
+        .attach(AdHoc::on_liftoff("Schedule reminders", {
+            let (notifier, site_data) = (notifier.clone(), site_data.clone());
+            move |rocket| Box::pin(async move {
+                tokio::spawn(notifier.run(site_data, rocket.shutdown()));
+            })
+        }))
         .manage(site_data) // Share the site data with the web-server, so that data can be shown to the user
+        .manage(static_files)
+        .manage(EventBus::default()) // Data point changes, for pages watching a test
+        .manage(webhook_queue)
+        .manage(notifier)
         .launch() // Start the web server
         .await?;
 
     Ok(())
 }
 
-#[get("/<path..>")] // Ran when accessing anything not specified. Checks the ./public directory
-async fn catch_all(path: PathBuf) -> Result<NamedFile, Status> {
-    let mut file_path = path.clone();
-
-    if file_path.extension().is_none() { // If file has no extension, assume html file
-        file_path.set_extension("html");
+// Reports any primary keys that are duplicated in the data on disk.
+// New duplicates are rejected by the API, but older data may still contain them
+async fn check_integrity(site_data: &SiteData) {
+    for username in site_data.users.duplicate_keys().await {
+        eprintln!("Integrity check: username '{}' appears more than once in ./data/users", username);
     }
+    for server_id in site_data.servers.duplicate_keys().await {
+        eprintln!("Integrity check: server ID '{}' appears more than once in ./data/servers", server_id);
+    }
+    for schedule_id in site_data.schedules.duplicate_keys().await {
+        eprintln!("Integrity check: schedule entry ID '{}' appears more than once in ./data/schedules", schedule_id);
+    }
+    for schedule in site_data.schedules.as_slice().await {
+        for username in schedule.get_assignees() {
+            if !site_data.users.contains_key(&username).await {
+                eprintln!("Integrity check: schedule entry '{}' is assigned to '{}', who is not a user", schedule.get_id(), username);
+            }
+        }
+        // Entries saved before they had a server are reported here, to be given one on their page
+        let test_exists = match site_data.servers.get_by_key(&schedule.get_server_id()).await {
+            Some(mut server) => {
+                server.load_tests().await;
+                server.tests.contains_key(&schedule.get_test_id()).await
+            }
+            None => false,
+        };
+        if !test_exists {
+            eprintln!(
+                "Integrity check: schedule entry '{}' is for the test '{}' on server '{}', which does not exist",
+                schedule.get_id(), schedule.get_test_id(), schedule.get_server_id()
+            );
+        }
+    }
+    for rule_id in site_data.alert_rules.duplicate_keys().await {
+        eprintln!("Integrity check: alert rule ID '{}' appears more than once in {}", rule_id, ALERT_RULES_PATH);
+    }
+    for webhook_id in site_data.webhooks.duplicate_keys().await {
+        eprintln!("Integrity check: webhook ID '{}' appears more than once in {}", webhook_id, WEBHOOKS_PATH);
+    }
+
+    // Data points are looked up by time, so times should be unique within each test
+    for i in 0..site_data.servers.length {
+        let mut server = site_data.servers.get(i).await.unwrap();
+        server.load_tests().await;
 
-    let path = format!("./public/{}", file_path.display());
-    let path = PathBuf::from(path);
-    NamedFile::open(path) //Gets the path from the ./public file
-        .await
-        .map_err(|_| Status::NotFound) // Return not found on an error
+        for test in server.tests.as_slice().await {
+            let data = test.data.as_slice().await;
+            for (k, pair) in data.windows(2).enumerate() {
+                let time = pair[1].get_time();
+                // Kept in time order, so the same time is always next to itself, and only reported once
+                if pair[0].get_time() == time && (k == 0 || data[k - 1].get_time() != time) {
+                    eprintln!(
+                        "Integrity check: time '{}' appears more than once in ./data/tests/{}/{}",
+                        time, server.get_id(), test.get_id()
+                    );
+                }
+            }
+        }
+    }
 }
 
 #[get("/login")] // Ran when accessing /login, used for redirecting from index
-async fn login() -> NamedFile {
-    NamedFile::open("./public/login.html").await.expect("Could not find file!")
+async fn login(files: &State<StaticFiles>, if_none_match: IfNoneMatch) -> StaticResponse {
+    files.serve(Path::new("login.html"), &if_none_match).await
 }
 
 #[get("/")] // Ran when accessing /, redirects to the login page
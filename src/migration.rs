@@ -0,0 +1,320 @@
+// Converts the data files from free-form times to timestamps.
+// Runs on every start, before anything is loaded, and leaves files alone once every time is a timestamp.
+// Lines whose time cannot be read would otherwise be dropped when loaded, so they are moved under
+// ./data/unparseable instead, and listed in ./data/migration-report.
+// Schedule assignees were once free text, so any that are not usernames are taken out and listed in the report too
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use rocket::tokio::fs;
+use rocket::tokio::io::{self, AsyncWriteExt};
+use crate::timestamp::Timestamp;
+
+const REPORT_FILE: &str = "migration-report";
+const UNPARSEABLE_DIRECTORY: &str = "unparseable";
+const TEMPORARY_FILE: &str = "migrating";
+
+#[derive(Default)]
+pub struct MigrationReport {
+    pub converted: u32, // Times that were read and rewritten as timestamps
+    pub unparseable: Vec<String>, // One line per line of data that was moved out
+    pub assignees: Vec<String>, // One line per schedule entry that had assignees taken out
+}
+
+// A data file and which comma-separated field holds its time
+struct TimeColumn {
+    path: PathBuf, // Relative to the data directory
+    column: usize,
+    chronological: bool, // Data points are kept in time order
+}
+
+pub async fn migrate(data_directory: &Path) -> io::Result<MigrationReport> {
+    let mut files = vec![TimeColumn { path: PathBuf::from("schedules"), column: 1, chronological: false }];
+
+    let tests_directory = data_directory.join("tests");
+    if tests_directory.is_dir() {
+        let mut servers = fs::read_dir(&tests_directory).await?;
+        while let Some(server) = servers.next_entry().await? {
+            if !server.path().is_dir() {
+                continue;
+            }
+            let mut tests = fs::read_dir(server.path()).await?;
+            while let Some(test) = tests.next_entry().await? {
+                let path = Path::new("tests").join(server.file_name()).join(test.file_name());
+                files.push(TimeColumn { path, column: 0, chronological: true });
+            }
+        }
+    }
+
+    let mut report = MigrationReport::default();
+    for file in files {
+        migrate_file(data_directory, &file, &mut report).await?;
+    }
+    migrate_assignees(data_directory, &mut report).await?;
+
+    if !report.unparseable.is_empty() || report.converted > 0 || !report.assignees.is_empty() {
+        write_report(data_directory, &report).await?;
+    }
+    Ok(report)
+}
+
+async fn migrate_file(data_directory: &Path, file: &TimeColumn, report: &mut MigrationReport) -> io::Result<()> {
+    let path = data_directory.join(&file.path);
+    let contents = match fs::read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+
+    let mut changed = false;
+    let mut kept: Vec<(Option<Timestamp>, String)> = Vec::new();
+    let mut unparseable = String::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let mut fields: Vec<String> = line.split(',').map(str::to_string).collect();
+        if line.is_empty() || fields.len() <= file.column {
+            kept.push((None, line.to_string())); // Not a record the loader would read, so not this migration's business
+            continue;
+        }
+
+        match Timestamp::from_str(&fields[file.column]) {
+            Ok(time) => {
+                let written = time.to_string();
+                if written != fields[file.column] {
+                    report.converted += 1;
+                    changed = true;
+                    fields[file.column] = written;
+                }
+                kept.push((Some(time), fields.join(",")));
+            }
+            Err(_) => {
+                report.unparseable.push(format!(
+                    "{} line {}: '{}' is not a date and time",
+                    file.path.display(),
+                    number + 1,
+                    fields[file.column]
+                ));
+                unparseable.push_str(line);
+                unparseable.push('\n');
+                changed = true;
+            }
+        }
+    }
+
+    if file.chronological && !kept.is_sorted_by_key(|(time, _)| *time) {
+        kept.sort_by_key(|(time, _)| *time); // Stable, so lines without a time keep their order
+        changed = true;
+    }
+
+    if !changed {
+        return Ok(());
+    }
+
+    // Keep the unreadable lines safe before they are taken out of the data file
+    if !unparseable.is_empty() {
+        let moved_to = data_directory.join(UNPARSEABLE_DIRECTORY).join(&file.path);
+        if let Some(parent) = moved_to.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut moved = fs::OpenOptions::new().create(true).append(true).open(moved_to).await?;
+        moved.write_all(unparseable.as_bytes()).await?;
+        moved.flush().await?; // Tokio finishes writes in the background otherwise
+        moved.sync_all().await?;
+    }
+
+    let mut output = String::new();
+    for (_, line) in kept {
+        output.push_str(&line);
+        output.push('\n');
+    }
+    replace_file(data_directory, &path, output.as_bytes()).await
+}
+
+// Assignees are kept as usernames separated by spaces, but were once free text such as "John Smith",
+// which would be read as the users John and Smith. Only the words that are usernames are kept
+async fn migrate_assignees(data_directory: &Path, report: &mut MigrationReport) -> io::Result<()> {
+    let path = data_directory.join("schedules");
+    let contents = match fs::read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    let users = match fs::read_to_string(data_directory.join("users")).await {
+        Ok(users) => users,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error),
+    };
+    let usernames: HashSet<&str> = users.lines().filter_map(|line| line.split(',').next()).collect();
+
+    let mut changed = false;
+    let mut output = String::new();
+    for (number, line) in contents.lines().enumerate() {
+        let mut fields: Vec<&str> = line.split(',').collect();
+        if fields.len() > 2 {
+            let assignees: Vec<&str> = fields[2].split_whitespace().filter(|word| usernames.contains(word)).collect();
+            if assignees.len() != fields[2].split_whitespace().count() {
+                report.assignees.push(format!(
+                    "schedules line {}: '{}' are not all users, kept '{}'",
+                    number + 1,
+                    fields[2],
+                    assignees.join(" ")
+                ));
+                let kept = assignees.join(" ");
+                fields[2] = &kept;
+                output.push_str(&fields.join(","));
+                output.push('\n');
+                changed = true;
+                continue;
+            }
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    if changed {
+        replace_file(data_directory, &path, output.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+// Write to a spare file and rename it over path, so a crash part way through leaves the old data.
+// The spare file is outside ./data/tests, so a leftover one is never loaded as a test
+async fn replace_file(data_directory: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temporary = data_directory.join(TEMPORARY_FILE);
+    let mut file = fs::File::create(&temporary).await?;
+    file.write_all(contents).await?;
+    file.flush().await?;
+    file.sync_all().await?;
+    fs::rename(&temporary, path).await
+}
+
+// Add this run to the report, keeping earlier runs
+async fn write_report(data_directory: &Path, report: &MigrationReport) -> io::Result<()> {
+    let mut output = format!(
+        "Migration at {}: converted {} times to timestamps, moved {} lines that could not be read to {}\n",
+        Timestamp::now(),
+        report.converted,
+        report.unparseable.len(),
+        data_directory.join(UNPARSEABLE_DIRECTORY).display()
+    );
+    for line in &report.unparseable {
+        output.push_str(line);
+        output.push('\n');
+    }
+    if !report.assignees.is_empty() {
+        output.push_str(&format!("Took assignees that are not users out of {} schedule entries:\n", report.assignees.len()));
+    }
+    for line in &report.assignees {
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(data_directory.join(REPORT_FILE)).await?;
+    file.write_all(output.as_bytes()).await?;
+    file.flush().await
+}
+
+impl MigrationReport {
+    pub fn log(&self, data_directory: &Path) {
+        if self.converted > 0 {
+            eprintln!("Migration: converted {} times to timestamps", self.converted);
+        }
+        if !self.unparseable.is_empty() {
+            eprintln!(
+                "Migration: moved {} lines with times that could not be read, see {}",
+                self.unparseable.len(),
+                data_directory.join(REPORT_FILE).display()
+            );
+        }
+        if !self.assignees.is_empty() {
+            eprintln!(
+                "Migration: took assignees that are not users out of {} schedule entries, see {}",
+                self.assignees.len(),
+                data_directory.join(REPORT_FILE).display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::migrate;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn setup(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("migration_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("tests/server")).unwrap();
+        base
+    }
+
+    #[rocket::async_test]
+    async fn test_migrates_times() {
+        let base = setup("times");
+        fs::write(base.join("tests/server/test"), "2024-05-01 10:00,1,2,\ntest,3,4,hi\n2024-04-30T09:00:00+01:00,5,6,\n").unwrap();
+        fs::write(base.join("schedules"), "a,2024-05-01T12:30,,t\nb,Date,,t\n").unwrap();
+
+        let report = migrate(&base).await.unwrap();
+        assert_eq!(report.converted, 2);
+        assert_eq!(report.unparseable, vec![
+            "schedules line 2: 'Date' is not a date and time",
+            "tests/server/test line 2: 'test' is not a date and time",
+        ]);
+
+        // Rewritten as timestamps in time order, with the unreadable lines moved out
+        assert_eq!(
+            fs::read_to_string(base.join("tests/server/test")).unwrap(),
+            "2024-04-30T09:00:00+01:00,5,6,\n2024-05-01T10:00:00Z,1,2,\n"
+        );
+        assert_eq!(fs::read_to_string(base.join("schedules")).unwrap(), "a,2024-05-01T12:30:00Z,,t\n");
+        assert_eq!(fs::read_to_string(base.join("unparseable/tests/server/test")).unwrap(), "test,3,4,hi\n");
+        assert_eq!(fs::read_to_string(base.join("unparseable/schedules")).unwrap(), "b,Date,,t\n");
+        assert!(!base.join("migrating").exists());
+
+        let written = fs::read_to_string(base.join("migration-report")).unwrap();
+        assert!(written.contains("converted 2 times"), "{}", written);
+        assert!(written.contains("tests/server/test line 2: 'test' is not a date and time"), "{}", written);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_free_text_assignees() {
+        let base = setup("assignees");
+        fs::write(base.join("users"), "jsmith,John,Smith,Developer,\nbob,Bob,,Manager,\n").unwrap();
+        fs::write(base.join("schedules"), "a,2024-05-01T12:00:00Z,John Smith,web,load\nb,2024-05-02T12:00:00Z,jsmith bob,web,load\nc,2024-05-03T12:00:00Z,bob Bob,load\n").unwrap();
+
+        let report = migrate(&base).await.unwrap();
+        assert_eq!(report.assignees, vec![
+            "schedules line 1: 'John Smith' are not all users, kept ''",
+            "schedules line 3: 'bob Bob' are not all users, kept 'bob'",
+        ]);
+        assert_eq!(
+            fs::read_to_string(base.join("schedules")).unwrap(),
+            "a,2024-05-01T12:00:00Z,,web,load\nb,2024-05-02T12:00:00Z,jsmith bob,web,load\nc,2024-05-03T12:00:00Z,bob,load\n"
+        );
+        let written = fs::read_to_string(base.join("migration-report")).unwrap();
+        assert!(written.contains("schedules line 1: 'John Smith' are not all users"), "{}", written);
+
+        // Nothing is left to take out the next time
+        assert!(migrate(&base).await.unwrap().assignees.is_empty());
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_migrated_data_is_left_alone() {
+        let base = setup("again");
+        let data = "2024-05-01T10:00:00Z,1,2,\n2024-05-01T11:00:00Z,3,4,\n";
+        fs::write(base.join("tests/server/test"), data).unwrap();
+
+        let report = migrate(&base).await.unwrap();
+        assert_eq!(report.converted, 0);
+        assert!(report.unparseable.is_empty());
+        assert_eq!(fs::read_to_string(base.join("tests/server/test")).unwrap(), data);
+        assert!(!base.join("migration-report").exists());
+
+        fs::remove_dir_all(base).unwrap();
+    }
+}
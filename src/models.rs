@@ -3,15 +3,33 @@ mod server;
 mod test;
 mod data_point;
 mod schedule_entry;
+mod metric;
+mod alert_rule;
+mod alert_event;
+mod webhook;
+mod webhook_delivery;
 
+pub use metric::RESERVED_METRIC_NAMES;
+pub use test::schema_path;
+
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use rocket::FromFormField;
+use crate::keyed_vector::KeyedVector;
 use crate::my_vector::MyVector;
+use crate::alerts::AlertStates;
+use crate::stats::StatsCache;
+use crate::timestamp::Timestamp;
 
 pub struct SiteData { // Used to package all site data together for ease of use
-    pub users: MyVector<User>,
-    pub servers: MyVector<Server>,
-    pub schedules: MyVector<ScheduleEntry>,
+    pub users: KeyedVector<User>, // Keyed by username
+    pub servers: KeyedVector<Server>, // Keyed by ID
+    pub schedules: KeyedVector<ScheduleEntry>, // Keyed by ID
+    pub stats: StatsCache, // Summary statistics of each test, by server and test ID
+    pub alert_rules: KeyedVector<AlertRule>, // Keyed by ID
+    pub alerts: AlertStates, // Which alerts are firing, by rule, server and test ID
+    pub webhooks: KeyedVector<Webhook>, // Keyed by ID
 }
 
 #[derive(Clone)]
@@ -20,9 +38,10 @@ pub struct User {
     forename: Option<String>,
     surname: Option<String>,
     position: Position, // Access levels not yet implemented
+    email: Option<String>, // Where schedule notifications are sent
 }
 
-#[derive(Clone)] // Can copy the position
+#[derive(Clone, Debug, FromFormField)] // Can copy the position, and read it from a form
 pub enum Position { // The positions that a user can have
     Developer,
     Manager,
@@ -31,8 +50,8 @@ pub enum Position { // The positions that a user can have
 impl Display for Position { // Allows to_string to be ran
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Position::Developer => write!(f, "{}", "Developer"),
-            Position::Manager => write!(f, "{}", "Manager")
+            Position::Developer => write!(f, "Developer"),
+            Position::Manager => write!(f, "Manager")
         }
     }
 }
@@ -54,27 +73,130 @@ pub struct Server {
     created_by: String, // Foreign key
     ram: u32, // In MB
     cpu: u32, // Number of cores
-    pub tests: MyVector<Test>,
+    pub tests: KeyedVector<Test>,
 }
 
 #[derive(Clone)]
 pub struct Test {
     id: String, // Primary key
     pub data: MyVector<DataPoint>,
+    pub metrics: KeyedVector<Metric>, // Custom metrics recorded alongside RAM and CPU, keyed by name
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, FromFormField)]
+pub enum MetricKind { // What values a custom metric can have
+    Integer, // e.g. error counts
+    Decimal, // e.g. latency in milliseconds
+}
+
+#[derive(Clone)]
+pub struct Metric {
+    name: String, // Primary key, within a test
+    unit: String, // Shown next to the name, e.g. MB/s
+    kind: MetricKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, FromFormField)]
+pub enum Comparator { // How an alert rule compares a value with its threshold
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+#[derive(Clone)]
+pub struct AlertRule {
+    id: String, // Primary key
+    server_id: String, // Foreign key
+    test_id: Option<String>, // Foreign key, or every test of the server if None
+    metric: String, // ram, cpu or the name of a custom metric
+    comparator: Comparator,
+    threshold: f64,
+    of_server_ram: bool, // The threshold is a percentage of the server's RAM, rather than in MB
+    duration: u32, // Seconds the threshold must stay crossed before the alert fires
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertChange {
+    Fired,
+    Resolved,
+}
+
+#[derive(Clone)]
+pub struct AlertEvent {
+    time: Timestamp, // Of the data point that fired or resolved the alert
+    rule_id: String,
+    server_id: String,
+    test_id: String,
+    change: AlertChange,
+    value: f64, // Of the metric at that data point
+    limit: f64, // The threshold it was compared with, e.g. a percentage of the server's RAM in MB
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WebhookEvent { // What happened, as sent to webhooks, e.g. test.created
+    TestCreated,
+    DataPointsIngested,
+    AlertFired,
+    AlertResolved,
+    ScheduleCreated,
+    ScheduleUpdated,
+    ScheduleDeleted,
+}
+
+#[derive(Clone)]
+pub struct Webhook {
+    id: String, // Primary key
+    url: String, // Where events are posted, over plain HTTP
+    events: Vec<WebhookEvent>, // The events it is sent
+    secret: String, // Signs each payload, so the receiver can tell it came from here
+}
+
+#[derive(Clone)]
+pub struct WebhookDelivery { // An event waiting to be sent to a webhook
+    id: String, // Primary key, also sent to the receiver so it can spot a delivery it already has
+    webhook_id: String, // Foreign key
+    event: WebhookEvent,
+    attempts: u32, // Made so far
+    next_attempt: Timestamp,
+    payload: String, // JSON, last as it contains commas
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    Retrying, // Failed, and will be tried again
+    Failed, // Failed too many times, so given up on
+}
+
+#[derive(Clone)]
+pub struct DeliveryAttempt { // One attempt to send a delivery, for the delivery log
+    time: Timestamp,
+    delivery_id: String,
+    webhook_id: String,
+    event: WebhookEvent,
+    attempt: u32, // Counting from 1
+    status: Option<u16>, // The HTTP status of the response, if there was one
+    outcome: DeliveryOutcome,
+    message: String, // Why it failed, last as it may contain commas
 }
 
 #[derive(Clone)]
 pub struct DataPoint {
-    time: String,
+    time: Timestamp,
     ram: u32,
     cpu: u32,
     comment: Option<String>,
+    metrics: BTreeMap<String, f64>, // Values of the test's custom metrics, by name, if recorded
 }
 
 #[derive(Clone)]
 pub struct ScheduleEntry {
     id: String, // Primary Key
-    datetime: String,
-    assignees: String,
-    test: String,
-}
\ No newline at end of file
+    datetime: Timestamp,
+    assignees: Vec<String>, // Usernames, in the order they were assigned
+    server_id: String, // The test is ./data/tests/<server_id>/<test_id>
+    test_id: String,
+}
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,116 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::models::{AlertChange, AlertEvent};
+use crate::my_vector::SafePointer;
+use crate::timestamp::Timestamp;
+
+impl Display for AlertChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlertChange::Fired => write!(f, "Fired"),
+            AlertChange::Resolved => write!(f, "Resolved"),
+        }
+    }
+}
+
+impl FromStr for AlertChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Fired" => Ok(Self::Fired),
+            "Resolved" => Ok(Self::Resolved),
+            _ => Err(format!("Unknown alert change '{}'", s)),
+        }
+    }
+}
+
+impl AlertEvent {
+    pub fn new(time: Timestamp, rule_id: String, server_id: String, test_id: String, change: AlertChange, value: f64, limit: f64) -> Self {
+        Self {
+            time,
+            rule_id,
+            server_id,
+            test_id,
+            change,
+            value,
+            limit,
+        }
+    }
+
+    pub fn get_time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn get_rule_id(&self) -> String {
+        self.rule_id.clone()
+    }
+
+    pub fn get_server_id(&self) -> String {
+        self.server_id.clone()
+    }
+
+    pub fn get_test_id(&self) -> String {
+        self.test_id.clone()
+    }
+
+    pub fn get_change(&self) -> AlertChange {
+        self.change
+    }
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn get_limit(&self) -> f64 {
+        self.limit
+    }
+}
+
+impl Display for AlertEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{},{},{},{}", self.time, self.rule_id, self.server_id, self.test_id, self.change, self.value, self.limit)
+    }
+}
+
+impl FromStr for AlertEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        if parts.len() != 7 {
+            return Err("Invalid string format: expected time,rule,server,test,change,value,limit".to_string());
+        }
+
+        let number = |part: &str| match part.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(format!("Invalid number '{}'", part)),
+        };
+
+        Ok(Self {
+            time: Timestamp::from_str(parts[0])?,
+            rule_id: parts[1].to_string(),
+            server_id: parts[2].to_string(),
+            test_id: parts[3].to_string(),
+            change: AlertChange::from_str(parts[4])?,
+            value: number(parts[5])?,
+            limit: number(parts[6])?,
+        })
+    }
+}
+
+impl PartialEq for AlertEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for AlertEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.time.partial_cmp(&other.time) // Sort chronologically
+    }
+}
+
+unsafe impl Send for SafePointer<AlertEvent> {}
+unsafe impl Sync for SafePointer<AlertEvent> {}
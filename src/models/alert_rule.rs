@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::keyed_vector::Keyed;
+use crate::models::{AlertRule, Comparator, DataPoint};
+use crate::my_vector::SafePointer;
+
+impl Comparator {
+    pub fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparator::Above => value > threshold,
+            Comparator::AtLeast => value >= threshold,
+            Comparator::Below => value < threshold,
+            Comparator::AtMost => value <= threshold,
+        }
+    }
+
+    // How the comparison reads in a sentence, e.g. "cpu above 90"
+    pub fn words(&self) -> &'static str {
+        match self {
+            Comparator::Above => "above",
+            Comparator::AtLeast => "at least",
+            Comparator::Below => "below",
+            Comparator::AtMost => "at most",
+        }
+    }
+}
+
+impl Display for Comparator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparator::Above => write!(f, "Above"),
+            Comparator::AtLeast => write!(f, "AtLeast"),
+            Comparator::Below => write!(f, "Below"),
+            Comparator::AtMost => write!(f, "AtMost"),
+        }
+    }
+}
+
+impl FromStr for Comparator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Above" => Ok(Self::Above),
+            "AtLeast" => Ok(Self::AtLeast),
+            "Below" => Ok(Self::Below),
+            "AtMost" => Ok(Self::AtMost),
+            _ => Err(format!("Unknown comparator '{}'", s)),
+        }
+    }
+}
+
+impl AlertRule {
+    #[allow(clippy::too_many_arguments)] // One for each field, as with the other models
+    pub fn new(id: String, server_id: String, test_id: Option<String>, metric: String, comparator: Comparator, threshold: f64, of_server_ram: bool, duration: u32) -> Self {
+        Self {
+            id,
+            server_id,
+            test_id,
+            metric,
+            comparator,
+            threshold,
+            of_server_ram,
+            duration,
+        }
+    }
+
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub fn get_server_id(&self) -> String {
+        self.server_id.clone()
+    }
+
+    pub fn set_server_id(&mut self, server_id: String) {
+        self.server_id = server_id;
+    }
+
+    pub fn get_test_id(&self) -> Option<String> {
+        self.test_id.clone()
+    }
+
+    pub fn set_test_id(&mut self, test_id: Option<String>) {
+        self.test_id = test_id;
+    }
+
+    pub fn get_duration(&self) -> u32 {
+        self.duration
+    }
+
+    // Whether the rule watches a test, either by name or as one of its server's tests
+    pub fn covers(&self, server_id: &str, test_id: &str) -> bool {
+        self.server_id == server_id && self.test_id.as_deref().is_none_or(|id| id == test_id)
+    }
+
+    // The threshold in the metric's own units, given the server's RAM in MB
+    pub fn limit(&self, server_ram: u32) -> f64 {
+        if self.of_server_ram {
+            self.threshold * server_ram as f64 / 100.0
+        } else {
+            self.threshold
+        }
+    }
+
+    // The value the rule watches, if the data point recorded it
+    pub fn value_of(&self, data_point: &DataPoint) -> Option<f64> {
+        match self.metric.as_str() {
+            "ram" => Some(data_point.get_ram() as f64),
+            "cpu" => Some(data_point.get_cpu() as f64),
+            metric => data_point.get_metric(metric),
+        }
+    }
+
+    pub fn is_crossed(&self, value: f64, server_ram: u32) -> bool {
+        self.comparator.compare(value, self.limit(server_ram))
+    }
+
+    // The condition, e.g. "ram at least 90% of the server's RAM for 60 s"
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} {} {}", self.metric, self.comparator.words(), self.threshold);
+        if self.of_server_ram {
+            description.push_str("% of the server's RAM");
+        }
+        if self.duration > 0 {
+            description.push_str(&format!(" for {} s", self.duration));
+        }
+        description
+    }
+}
+
+impl Display for AlertRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.id,
+            self.server_id,
+            self.test_id.clone().unwrap_or_default(),
+            self.metric,
+            self.comparator,
+            self.threshold,
+            self.of_server_ram,
+            self.duration,
+        )
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        if parts.len() != 8 {
+            return Err("Invalid string format: expected id,server,test,metric,comparator,threshold,of_server_ram,duration".to_string());
+        }
+
+        Ok(Self {
+            id: parts[0].to_string(),
+            server_id: parts[1].to_string(),
+            test_id: if parts[2].is_empty() { None } else { Some(parts[2].to_string()) },
+            metric: parts[3].to_string(),
+            comparator: Comparator::from_str(parts[4])?,
+            threshold: match parts[5].parse::<f64>() {
+                Ok(threshold) if threshold.is_finite() => threshold,
+                _ => return Err(format!("Invalid threshold '{}'", parts[5])),
+            },
+            of_server_ram: parts[6].parse::<bool>().map_err(|_| format!("Invalid of_server_ram '{}'", parts[6]))?,
+            duration: parts[7].parse::<u32>().map_err(|_| format!("Invalid duration '{}'", parts[7]))?,
+        })
+    }
+}
+
+impl PartialEq for AlertRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for AlertRule {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.id.partial_cmp(&other.id)
+    }
+}
+
+impl Keyed for AlertRule {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
+unsafe impl Send for SafePointer<AlertRule> {}
+unsafe impl Sync for SafePointer<AlertRule> {}
@@ -1,16 +1,20 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use crate::models::DataPoint;
 use crate::my_vector::SafePointer;
+use crate::query::Searchable;
+use crate::timestamp::Timestamp;
 
 impl DataPoint {
-    pub fn new(time: String, ram: u32, cpu: u32) -> Self {
+    pub fn new(time: Timestamp, ram: u32, cpu: u32) -> Self {
         Self{
             time,
             ram,
             cpu,
             comment: None,
+            metrics: BTreeMap::new(),
         }
     }
 
@@ -18,7 +22,7 @@ impl DataPoint {
         self.comment = Some(comment)
     }
 
-    pub fn set_time(&mut self, time: String) {
+    pub fn set_time(&mut self, time: Timestamp) {
         self.time = time
     }
 
@@ -30,8 +34,8 @@ impl DataPoint {
         self.cpu = cpu
     }
 
-    pub fn get_time(&self) -> String {
-        self.time.clone()
+    pub fn get_time(&self) -> Timestamp {
+        self.time
     }
 
     pub fn get_ram(&self) -> u32 {
@@ -49,16 +53,31 @@ impl DataPoint {
     pub fn set_comment(&mut self, comment: Option<String>) {
         self.comment = comment;
     }
+
+    pub fn get_metric(&self, name: &str) -> Option<f64> {
+        self.metrics.get(name).copied()
+    }
+
+    pub fn get_metrics(&self) -> &BTreeMap<String, f64> {
+        &self.metrics
+    }
+
+    pub fn set_metrics(&mut self, metrics: BTreeMap<String, f64>) {
+        self.metrics = metrics;
+    }
 }
 
 impl Display for DataPoint {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let str = format!("{},{},{},{}",
+        let mut str = format!("{},{},{},{}",
             self.time,
             self.ram,
             self.cpu,
             self.comment.clone().unwrap_or_default(),
         );
+        for (name, value) in &self.metrics { // Custom metrics follow as name=value, so removing one from the test cannot shift the others
+            str.push_str(&format!(",{}={}", name, value));
+        }
 
         write!(f, "{}", str)
     }
@@ -69,15 +88,25 @@ impl FromStr for DataPoint {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.split(',').collect();
-        if parts.len() != 4 {
+        if parts.len() < 4 {
             return Err("Invalid string format".to_string())
         }
 
+        let mut metrics = BTreeMap::new();
+        for part in &parts[4..] {
+            let (name, value) = part.split_once('=').ok_or("Invalid metric, expected name=value")?;
+            match value.parse::<f64>() {
+                Ok(value) if value.is_finite() && !name.is_empty() => metrics.insert(name.to_string(), value),
+                _ => return Err(format!("Invalid value for metric '{}'", name)),
+            };
+        }
+
         Ok(Self {
-            time: String::from_str(parts[0]).unwrap_or_default(),
+            time: Timestamp::from_str(parts[0])?,
             ram: parts[1].parse::<u32>().unwrap_or_default(),
             cpu: parts[2].parse::<u32>().unwrap_or_default(),
-            comment: if !parts[3].is_empty() { Some(String::from_str(parts[3]).unwrap_or_default()) } else { None }
+            comment: if !parts[3].is_empty() { Some(String::from_str(parts[3]).unwrap_or_default()) } else { None },
+            metrics,
         })
     }
 }
@@ -90,9 +119,29 @@ impl PartialEq<Self> for DataPoint {
 
 impl PartialOrd for DataPoint {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.cpu.partial_cmp(&other.cpu) // Sort by CPU
+        self.time.partial_cmp(&other.time) // Sort chronologically
     }
 }
 
 unsafe impl Send for SafePointer<DataPoint> {}
-unsafe impl Sync for SafePointer<DataPoint> {}
\ No newline at end of file
+unsafe impl Sync for SafePointer<DataPoint> {}
+
+impl Searchable for DataPoint {
+    const FIELDS: &'static [&'static str] = &["time", "ram", "cpu", "comment"];
+    const DEFAULT_FIELDS: &'static [&'static str] = &["time", "comment"];
+
+    fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "time" => Some(self.get_time().to_string()),
+            "ram" => Some(self.get_ram().to_string()),
+            "cpu" => Some(self.get_cpu().to_string()),
+            "comment" => self.get_comment(),
+            // Any other field is a custom metric. Query fields are lower case, so the name is matched ignoring case
+            metric => self
+                .metrics
+                .iter()
+                .find(|(name, _)| name.to_lowercase() == metric)
+                .map(|(_, value)| value.to_string()),
+        }
+    }
+}
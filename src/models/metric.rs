@@ -0,0 +1,117 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::keyed_vector::Keyed;
+use crate::models::{Metric, MetricKind};
+use crate::my_vector::SafePointer;
+
+// Names already used by the built-in columns of a data point
+pub const RESERVED_METRIC_NAMES: &[&str] = &["time", "ram", "cpu", "comment"];
+
+impl Metric {
+    pub fn new(name: String, unit: String, kind: MetricKind) -> Self {
+        Self {
+            name,
+            unit,
+            kind,
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn get_unit(&self) -> String {
+        self.unit.clone()
+    }
+
+    pub fn get_kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    // The name with its unit, for column headers
+    pub fn label(&self) -> String {
+        if self.unit.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.unit)
+        }
+    }
+
+    // Read a value of this metric, as typed into a form or an import
+    pub fn parse_value(&self, value: &str) -> Result<f64, String> {
+        let value = value.trim();
+        match self.kind {
+            MetricKind::Integer => value.parse::<i64>().map(|value| value as f64).map_err(|_| "must be a whole number".to_string()),
+            MetricKind::Decimal => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(number),
+                _ => Err("must be a number".to_string()),
+            },
+        }
+    }
+}
+
+impl Display for MetricKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricKind::Integer => write!(f, "Integer"),
+            MetricKind::Decimal => write!(f, "Decimal"),
+        }
+    }
+}
+
+impl FromStr for MetricKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Integer" => Ok(Self::Integer),
+            "Decimal" => Ok(Self::Decimal),
+            _ => Err(format!("Unknown metric kind '{}'", s)),
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.name, self.unit, self.kind)
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        if parts.len() != 3 {
+            return Err("Invalid string format: expected name,unit,kind".to_string());
+        }
+
+        Ok(Self {
+            name: parts[0].to_string(),
+            unit: parts[1].to_string(),
+            kind: MetricKind::from_str(parts[2])?,
+        })
+    }
+}
+
+impl PartialEq for Metric {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for Metric {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.name.partial_cmp(&other.name)
+    }
+}
+
+impl Keyed for Metric {
+    fn get_key(&self) -> String {
+        self.get_name()
+    }
+}
+
+unsafe impl Send for SafePointer<Metric> {}
+unsafe impl Sync for SafePointer<Metric> {}
@@ -1,16 +1,20 @@
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use crate::keyed_vector::Keyed;
 use crate::models::ScheduleEntry;
 use crate::my_vector::SafePointer;
+use crate::query::Searchable;
+use crate::timestamp::Timestamp;
 
 impl ScheduleEntry {
-    pub fn new(id: String, datetime: String, assignees: String, test: String) -> Self {
+    pub fn new(id: String, datetime: Timestamp, assignees: Vec<String>, server_id: String, test_id: String) -> Self {
         Self {
             id,
             datetime,
             assignees,
-            test,
+            server_id,
+            test_id,
         }
     }
 
@@ -22,39 +26,80 @@ impl ScheduleEntry {
         self.id = id;
     }
 
-    pub fn get_datetime(&self) -> String {
-        self.datetime.clone()
+    pub fn get_datetime(&self) -> Timestamp {
+        self.datetime
     }
 
-    pub fn set_datetime(&mut self, datetime: String) {
+    pub fn set_datetime(&mut self, datetime: Timestamp) {
         self.datetime = datetime;
     }
 
-    pub fn get_assignees(&self) -> String {
+    pub fn get_assignees(&self) -> Vec<String> {
         self.assignees.clone()
     }
 
-    pub fn set_assignees(&mut self, assignees: String) {
-        self.assignees = assignees;
+    pub fn is_assigned(&self, username: &str) -> bool {
+        self.assignees.iter().any(|assignee| assignee == username)
     }
 
-    pub fn get_test(&self) -> String {
-        self.test.clone()
+    // Returns false if they were already assigned
+    pub fn add_assignee(&mut self, username: String) -> bool {
+        if self.is_assigned(&username) {
+            return false;
+        }
+        self.assignees.push(username);
+        true
+    }
+
+    // Returns false if they were not assigned
+    pub fn remove_assignee(&mut self, username: &str) -> bool {
+        let count = self.assignees.len();
+        self.assignees.retain(|assignee| assignee != username);
+        self.assignees.len() != count
+    }
+
+    // Follow a user being renamed
+    pub fn rename_assignee(&mut self, username: &str, new_username: &str) -> bool {
+        match self.assignees.iter_mut().find(|assignee| *assignee == username) {
+            Some(assignee) => {
+                *assignee = new_username.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_server_id(&self) -> String {
+        self.server_id.clone()
+    }
+
+    pub fn set_server_id(&mut self, server_id: String) {
+        self.server_id = server_id;
+    }
+
+    pub fn get_test_id(&self) -> String {
+        self.test_id.clone()
+    }
+
+    pub fn set_test_id(&mut self, test_id: String) {
+        self.test_id = test_id;
     }
 
-    pub fn set_test(&mut self, test: String) {
-        self.test = test;
+    // Whether the entry is for a test, or for one of a server's tests if test_id is None
+    pub fn is_for(&self, server_id: &str, test_id: Option<&str>) -> bool {
+        self.server_id == server_id && test_id.is_none_or(|test_id| self.test_id == test_id)
     }
 }
 
 impl Display for ScheduleEntry {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let formatted = format!(
-            "{},{},{},{}",
+            "{},{},{},{},{}",
             self.id,
             self.datetime,
-            self.assignees,
-            self.test,
+            self.assignees.join(" "),
+            self.server_id,
+            self.test_id,
         );
         write!(f, "{}", formatted)
     }
@@ -65,15 +110,19 @@ impl FromStr for ScheduleEntry {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.split(',').collect();
-        if parts.len() != 4 {
-            return Err("Invalid string format: expected 4 parts separated by commas.".to_string());
-        }
+        // Entries saved before they had a server only name the test, so are kept with an empty server to be fixed
+        let (server_id, test_id) = match parts.len() {
+            4 => ("", parts[3]),
+            5 => (parts[3], parts[4]),
+            _ => return Err("Invalid string format: expected id,datetime,assignees,server,test".to_string()),
+        };
 
         Ok(Self {
             id: parts[0].to_string(),
-            datetime: parts[1].to_string(),
-            assignees: parts[2].to_string(),
-            test: parts[3].to_string(),
+            datetime: Timestamp::from_str(parts[1])?,
+            assignees: parts[2].split_whitespace().map(String::from).collect(), // Separated by spaces
+            server_id: server_id.to_string(),
+            test_id: test_id.to_string(),
         })
     }
 }
@@ -90,5 +139,27 @@ impl PartialOrd for ScheduleEntry {
     }
 }
 
+impl Keyed for ScheduleEntry {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
 unsafe impl Send for SafePointer<ScheduleEntry> {}
 unsafe impl Sync for SafePointer<ScheduleEntry> {}
+
+impl Searchable for ScheduleEntry {
+    const FIELDS: &'static [&'static str] = &["id", "datetime", "assignees", "server", "test"];
+    const DEFAULT_FIELDS: &'static [&'static str] = &["id", "assignees", "server", "test"];
+
+    fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "id" => Some(self.get_id()),
+            "datetime" => Some(self.get_datetime().to_string()),
+            "assignees" => Some(self.assignees.join(" ")),
+            "server" => Some(self.get_server_id()),
+            "test" => Some(self.get_test_id()),
+            _ => None,
+        }
+    }
+}
@@ -2,9 +2,11 @@ use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 use std::path::Path;
 use std::str::FromStr;
-use rocket::tokio::fs;
-use crate::models::{Server, Test};
+use rocket::tokio::{fs, io};
+use crate::keyed_vector::{Keyed, KeyedVector};
+use crate::models::{schema_path, Server, Test};
 use crate::my_vector::{MyVector, SafePointer};
+use crate::query::Searchable;
 
 impl Server {
     pub fn new(id: String, name: String, created_by: String, ram: u32, cpu: u32) -> Self {
@@ -14,7 +16,7 @@ impl Server {
             created_by,
             ram,
             cpu,
-            tests: MyVector::new(),
+            tests: KeyedVector::new(),
         }
     }
 
@@ -38,30 +40,24 @@ impl Server {
         self.cpu
     }
 
-    pub async fn set_id(&mut self, new_id: String) {
-        let old_path = format!("./data/tests/{}", self.id);
-        let new_path = format!("./data/tests/{}", new_id);
-
-        if Path::new(&old_path).exists() {
-            if !Path::new(&new_path).exists() {
-                fs::create_dir_all(&new_path).await.unwrap();
+    // Moves the server's files to directories named after the new ID. If any cannot be moved,
+    // the ones already moved are put back, and the server keeps its old ID
+    pub async fn set_id(&mut self, new_id: String) -> io::Result<()> {
+        if new_id != self.id { // Moving a directory onto itself would delete it
+            // Test data and custom metric schemas are both kept in a directory named after the server
+            let directories = ["./data/tests", "./data/schemas"];
+            for (i, directory) in directories.iter().enumerate() {
+                if let Err(error) = move_directory(&format!("{}/{}", directory, self.id), &format!("{}/{}", directory, new_id)).await {
+                    for directory in &directories[..=i] {
+                        let _ = move_directory(&format!("{}/{}", directory, new_id), &format!("{}/{}", directory, self.id)).await;
+                    }
+                    return Err(error);
+                }
             }
-
-            let mut entries = fs::read_dir(&old_path).await.unwrap();
-            while let Ok(Some(entry)) = entries.next_entry().await {
-                let file_path = entry.path();
-                let file_name = file_path.file_name().unwrap();
-                let new_file_path = Path::new(&new_path).join(file_name);
-
-                // Move the file to the new directory
-                fs::rename(file_path, new_file_path).await.unwrap();
-            }
-
-            // Remove the old directory
-            fs::remove_dir_all(&old_path).await.unwrap();
         }
 
         self.id = new_id;
+        Ok(())
     }
 
     pub fn set_name(&mut self, name: String) {
@@ -87,7 +83,7 @@ impl Server {
             fs::create_dir_all(&path).await.unwrap();
         }
 
-        let mut tests = MyVector::new();
+        let mut tests = KeyedVector::new();
 
         let mut entries = fs::read_dir(&path).await.unwrap();
 
@@ -98,7 +94,11 @@ impl Server {
                 let data = MyVector::load_from_file(file_path.to_str().unwrap()).await;
                 let mut test = Test::new(test_id.to_string());
                 test.data = data;
-                tests.push(test).await;
+                let schema = schema_path(&self.id, test_id);
+                if Path::new(&schema).exists() { // Most tests only record RAM and CPU
+                    test.metrics = KeyedVector::load_from_file(&schema).await;
+                }
+                let _ = tests.insert(test).await; // File names are unique, so this cannot conflict
             }
         }
         
@@ -106,16 +106,37 @@ impl Server {
     }
 
     pub async fn delete_tests_directory(&self) {
-        let path = format!("./data/tests/{}", self.id);
+        for directory in ["./data/tests", "./data/schemas"] {
+            let path = format!("{}/{}", directory, self.id);
 
-        if Path::new(&path).exists() {
-            if let Err(e) = fs::remove_dir_all(&path).await {
-                eprintln!("Failed to delete directory {}: {}", path, e);
+            if Path::new(&path).exists() {
+                if let Err(e) = fs::remove_dir_all(&path).await {
+                    eprintln!("Failed to delete directory {}: {}", path, e);
+                }
             }
         }
     }
 }
 
+// Move every file from one directory into another, then remove the first
+async fn move_directory(old_path: &str, new_path: &str) -> io::Result<()> {
+    if Path::new(old_path).exists() {
+        if !Path::new(new_path).exists() {
+            fs::create_dir_all(new_path).await?;
+        }
+
+        let mut entries = fs::read_dir(old_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            // Move the file to the new directory
+            fs::rename(entry.path(), Path::new(new_path).join(entry.file_name())).await?;
+        }
+
+        // Remove the old directory
+        fs::remove_dir_all(old_path).await?;
+    }
+    Ok(())
+}
+
 impl Display for Server {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let str = format!("{},{},{},{},{}",
@@ -145,7 +166,7 @@ impl FromStr for Server {
             created_by: parts[2].to_string(),
             ram: u32::from_str(parts[3]).map_err(|_| ())?,
             cpu: u32::from_str(parts[4]).map_err(|_| ())?,
-            tests: MyVector::new(),
+            tests: KeyedVector::new(),
         })
     }
 }
@@ -161,5 +182,28 @@ impl PartialOrd for Server {
     }
 }
 
+impl Keyed for Server {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
 unsafe impl Send for SafePointer<Server> {}
-unsafe impl Sync for SafePointer<Server> {}
\ No newline at end of file
+unsafe impl Sync for SafePointer<Server> {}
+
+impl Searchable for Server {
+    const FIELDS: &'static [&'static str] = &["id", "name", "created_by", "ram", "cpu", "tests"];
+    const DEFAULT_FIELDS: &'static [&'static str] = &["id", "name"];
+
+    fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "id" => Some(self.get_id()),
+            "name" => Some(self.get_name()),
+            "created_by" => Some(self.get_created_by()),
+            "ram" => Some(self.get_ram().to_string()),
+            "cpu" => Some(self.get_cpu().to_string()),
+            "tests" => Some(self.tests.length.to_string()),
+            _ => None,
+        }
+    }
+}
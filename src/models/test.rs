@@ -1,5 +1,5 @@
 use std::cmp::Ordering;
-use std::error::Error;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io;
@@ -7,15 +7,18 @@ use std::io::BufRead;
 use std::path::Path;
 use std::str::FromStr;
 use rocket::tokio::runtime::Runtime;
-use rocket::yansi::Paint;
+use crate::keyed_vector::{Keyed, KeyedVector};
 use crate::models::{DataPoint, Test};
 use crate::my_vector::{MyVector, SafePointer};
+use crate::query::Searchable;
+use crate::timestamp::Timestamp;
 
 impl Test {
     pub fn new(id: String) -> Self {
         Self {
             id,
             data: MyVector::new(),
+            metrics: KeyedVector::new(),
         }
     }
     
@@ -26,6 +29,46 @@ impl Test {
     pub fn set_id(&mut self, id: String) {
         self.id = id;
     }
+
+    // Index of the data point at a time. Data is kept in time order, so this is a binary search
+    pub async fn find_time(&self, time: Timestamp) -> Option<u32> {
+        self.data.binary_search_by(|data_point| data_point.get_time().cmp(&time)).await.ok()
+    }
+
+    // Check the values given for this test's custom metrics, by name. Empty values are left out.
+    // Errors are one "metrics[name]: message" line per problem, like any other form field
+    pub async fn parse_metrics(&self, values: &HashMap<String, String>) -> Result<BTreeMap<String, f64>, String> {
+        let mut metrics = BTreeMap::new();
+        let mut errors = Vec::new();
+
+        for (name, value) in values {
+            if value.trim().is_empty() {
+                continue;
+            }
+            match self.metrics.get_by_key(name).await {
+                Some(metric) => match metric.parse_value(value) {
+                    Ok(value) => {
+                        metrics.insert(name.clone(), value);
+                    }
+                    Err(error) => errors.push(format!("metrics[{}]: {}", name, error)),
+                },
+                None => errors.push(format!("metrics[{}]: is not a metric of this test", name)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(metrics)
+        } else {
+            errors.sort(); // HashMap order would change between requests
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+// Where a test's custom metrics are kept.
+// Not beside its data, as every file in a server's tests directory is read as a test
+pub fn schema_path(server_id: &str, test_id: &str) -> String {
+    format!("./data/schemas/{}/{}", server_id, test_id)
 }
 
 impl Display for Test {
@@ -62,12 +105,12 @@ impl FromStr for Test {
                     let data_point = line.parse::<DataPoint>().map_err(|_| ())?;
                     // Add the DataPoint to `data`
                     let rt = Runtime::new().unwrap();
-                    let _ = rt.block_on(data.push(data_point));
+                    rt.block_on(data.push(data_point));
                 }
             }
         }
 
-        Ok(Test { id, data }) // Return the constructed Test instance
+        Ok(Test { id, data, metrics: KeyedVector::new() }) // Return the constructed Test instance
     }
 }
 
@@ -83,5 +126,24 @@ impl PartialOrd for Test {
     }
 }
 
+impl Keyed for Test {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
 unsafe impl Send for SafePointer<Test> {}
-unsafe impl Sync for SafePointer<Test> {}
\ No newline at end of file
+unsafe impl Sync for SafePointer<Test> {}
+
+impl Searchable for Test {
+    const FIELDS: &'static [&'static str] = &["id", "data_points"];
+    const DEFAULT_FIELDS: &'static [&'static str] = &["id"];
+
+    fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "id" => Some(self.get_id()),
+            "data_points" => Some(self.data.length.to_string()),
+            _ => None,
+        }
+    }
+}
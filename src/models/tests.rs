@@ -0,0 +1,276 @@
+// Property tests for the models' file formats.
+// Every model is stored as one comma-separated line, so parsing must never panic on bad lines,
+// and anything written must read back the same
+use std::collections::HashMap;
+use std::str::FromStr;
+use proptest::prelude::*;
+use crate::models::{AlertChange, AlertEvent, AlertRule, Comparator, DataPoint, DeliveryAttempt, DeliveryOutcome, Metric, MetricKind, Position, ScheduleEntry, Server, Test, User, Webhook, WebhookDelivery, WebhookEvent};
+use crate::timestamp::Timestamp;
+
+// A field value that can be stored, which cannot contain the separator or a new line
+fn field() -> impl Strategy<Value = String> {
+    "[^,\n\r]{0,12}"
+}
+
+fn non_empty_field() -> impl Strategy<Value = String> {
+    "[^,\n\r]{1,12}"
+}
+
+// Any time from 1970 to 2100, to the second
+fn timestamp() -> impl Strategy<Value = Timestamp> {
+    (0i64..4_102_444_800).prop_map(|seconds| Timestamp::from_unix_seconds(seconds).unwrap())
+}
+
+// Text that is often, but not always, a valid timestamp
+fn date_like() -> impl Strategy<Value = String> {
+    "20[0-9]{2}-[0-1][0-9]-[0-3][0-9]([T ][0-2][0-9]:[0-5][0-9](:[0-5][0-9])?(Z|[+-]0[0-9]:[0-5][0-9])?)?"
+}
+
+// Values of custom metrics, by a name that could have been declared on a test
+fn metrics() -> impl Strategy<Value = std::collections::BTreeMap<String, f64>> {
+    prop::collection::btree_map("[a-z_]{1,8}", prop::num::f64::NORMAL | prop::num::f64::ZERO, 0..4)
+}
+
+fn metric_kind() -> impl Strategy<Value = MetricKind> {
+    prop_oneof![Just(MetricKind::Integer), Just(MetricKind::Decimal)]
+}
+
+fn comparator() -> impl Strategy<Value = Comparator> {
+    prop_oneof![Just(Comparator::Above), Just(Comparator::AtLeast), Just(Comparator::Below), Just(Comparator::AtMost)]
+}
+
+fn alert_change() -> impl Strategy<Value = AlertChange> {
+    prop_oneof![Just(AlertChange::Fired), Just(AlertChange::Resolved)]
+}
+
+fn webhook_event() -> impl Strategy<Value = WebhookEvent> {
+    prop::sample::select(WebhookEvent::ALL)
+}
+
+fn delivery_outcome() -> impl Strategy<Value = DeliveryOutcome> {
+    prop_oneof![Just(DeliveryOutcome::Delivered), Just(DeliveryOutcome::Retrying), Just(DeliveryOutcome::Failed)]
+}
+
+fn position() -> impl Strategy<Value = Position> {
+    prop_oneof![Just(Position::Developer), Just(Position::Manager)]
+}
+
+// Lines with roughly the right number of fields, so that the parsers get past the length check
+fn line() -> impl Strategy<Value = String> {
+    prop_oneof![
+        ".*",
+        prop::collection::vec(".{0,8}", 3..7).prop_map(|fields| fields.join(",")),
+        prop::collection::vec(prop_oneof!["[0-9]{1,10}", "-?[0-9]{1,3}", date_like(), field()], 3..7).prop_map(|fields| fields.join(",")),
+    ]
+}
+
+// Parsing a written value back must give the same line again
+fn assert_stable<T>(value: &T)
+where
+    T: ToString + FromStr,
+    T::Err: std::fmt::Debug,
+{
+    let written = value.to_string();
+    let parsed = T::from_str(&written).expect("Written value could not be read back");
+    assert_eq!(parsed.to_string(), written);
+}
+
+proptest! {
+    #[test]
+    fn user_round_trip(username in field(), forename in proptest::option::of(non_empty_field()), surname in proptest::option::of(non_empty_field()), position in position(), email in proptest::option::of(non_empty_field())) {
+        let mut user = User::new(username, forename.clone(), surname.clone(), position);
+        user.set_email(email.clone());
+        assert_stable(&user);
+
+        let parsed = User::from_str(&user.to_string()).unwrap();
+        prop_assert_eq!(parsed.get_username(), user.get_username());
+        prop_assert_eq!(parsed.get_forename(), forename);
+        prop_assert_eq!(parsed.get_surname(), surname);
+        prop_assert_eq!(parsed.get_position().to_string(), user.get_position().to_string());
+        prop_assert_eq!(parsed.get_email(), email);
+    }
+
+    #[test]
+    fn server_round_trip(id in field(), name in field(), created_by in field(), ram in any::<u32>(), cpu in any::<u32>()) {
+        let server = Server::new(id, name, created_by, ram, cpu);
+        assert_stable(&server);
+
+        let parsed = Server::from_str(&server.to_string()).unwrap();
+        prop_assert_eq!(parsed.get_name(), server.get_name());
+        prop_assert_eq!(parsed.get_created_by(), server.get_created_by());
+        prop_assert_eq!(parsed.get_ram(), ram);
+        prop_assert_eq!(parsed.get_cpu(), cpu);
+    }
+
+    #[test]
+    fn data_point_round_trip(time in timestamp(), ram in any::<u32>(), cpu in any::<u32>(), comment in proptest::option::of(non_empty_field()), metrics in metrics()) {
+        let mut data_point = DataPoint::new(time, ram, cpu);
+        data_point.set_comment(comment.clone());
+        data_point.set_metrics(metrics.clone());
+        assert_stable(&data_point);
+
+        let parsed = DataPoint::from_str(&data_point.to_string()).unwrap();
+        prop_assert_eq!(parsed.get_time(), time);
+        prop_assert_eq!(parsed.get_ram(), ram);
+        prop_assert_eq!(parsed.get_cpu(), cpu);
+        prop_assert_eq!(parsed.get_comment(), comment);
+        prop_assert_eq!(parsed.get_metrics(), &metrics);
+    }
+
+    #[test]
+    fn metric_round_trip(name in "[a-z_]{1,8}", unit in field(), kind in metric_kind()) {
+        assert_stable(&Metric::new(name, unit, kind));
+    }
+
+    #[test]
+    fn schedule_entry_round_trip(id in field(), datetime in timestamp(), assignees in prop::collection::vec("[a-z0-9_-]{1,8}", 0..4), server_id in field(), test_id in field()) {
+        let entry = ScheduleEntry::new(id, datetime, assignees.clone(), server_id, test_id);
+        assert_stable(&entry);
+        prop_assert_eq!(ScheduleEntry::from_str(&entry.to_string()).unwrap().get_assignees(), assignees);
+    }
+
+    #[test]
+    fn alert_rule_round_trip(id in field(), server_id in field(), test_id in proptest::option::of(non_empty_field()), metric in field(), comparator in comparator(), threshold in prop::num::f64::NORMAL | prop::num::f64::ZERO, of_server_ram in any::<bool>(), duration in any::<u32>()) {
+        let rule = AlertRule::new(id, server_id, test_id.clone(), metric, comparator, threshold, of_server_ram, duration);
+        assert_stable(&rule);
+        prop_assert_eq!(AlertRule::from_str(&rule.to_string()).unwrap().get_test_id(), test_id);
+    }
+
+    #[test]
+    fn alert_event_round_trip(time in timestamp(), rule_id in field(), server_id in field(), test_id in field(), change in alert_change(), value in prop::num::f64::NORMAL, limit in prop::num::f64::NORMAL) {
+        assert_stable(&AlertEvent::new(time, rule_id, server_id, test_id, change, value, limit));
+    }
+
+    #[test]
+    fn webhook_round_trip(id in field(), url in field(), events in prop::collection::vec(webhook_event(), 0..4), secret in field()) {
+        assert_stable(&Webhook::new(id, url, events, secret));
+    }
+
+    // The payload and message are written last, so they may contain commas
+    #[test]
+    fn webhook_delivery_round_trip(id in field(), webhook_id in field(), event in webhook_event(), next_attempt in timestamp(), payload in "[^\n\r]{0,40}", attempts in 0u32..20, status in proptest::option::of(100u16..600), outcome in delivery_outcome(), message in "[^\n\r]{0,40}") {
+        let mut delivery = WebhookDelivery::new(id, webhook_id, event, next_attempt, payload);
+        for _ in 0..attempts {
+            delivery.retry_at(next_attempt);
+        }
+        assert_stable(&delivery);
+        assert_stable(&DeliveryAttempt::new(next_attempt, &delivery, status, outcome, &message));
+    }
+
+    // Fuzz the parsers with arbitrary lines, they must never panic, and anything they accept must be stable
+    #[test]
+    fn user_parse_fuzz(line in line()) {
+        if let Ok(user) = User::from_str(&line) {
+            assert_stable(&user);
+        }
+    }
+
+    #[test]
+    fn server_parse_fuzz(line in line()) {
+        if let Ok(server) = Server::from_str(&line) {
+            assert_stable(&server);
+        }
+    }
+
+    #[test]
+    fn data_point_parse_fuzz(line in line()) {
+        if let Ok(data_point) = DataPoint::from_str(&line) {
+            assert_stable(&data_point);
+        }
+    }
+
+    #[test]
+    fn alert_rule_parse_fuzz(line in line()) {
+        if let Ok(rule) = AlertRule::from_str(&line) {
+            assert_stable(&rule);
+        }
+    }
+
+    #[test]
+    fn schedule_entry_parse_fuzz(line in line()) {
+        if let Ok(entry) = ScheduleEntry::from_str(&line) {
+            assert_stable(&entry);
+        }
+    }
+}
+
+#[rocket::async_test]
+async fn test_parse_metrics() {
+    let mut test = Test::new("test".to_string());
+    let _ = test.metrics.insert(Metric::new("errors".to_string(), String::new(), MetricKind::Integer)).await;
+    let _ = test.metrics.insert(Metric::new("latency".to_string(), "ms".to_string(), MetricKind::Decimal)).await;
+
+    let values = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    };
+
+    let parsed = test.parse_metrics(&values(&[("errors", "3"), ("latency", " 12.5 ")])).await.unwrap();
+    assert_eq!(parsed.get("errors"), Some(&3.0));
+    assert_eq!(parsed.get("latency"), Some(&12.5));
+
+    // Empty values are missing rather than zero
+    assert!(test.parse_metrics(&values(&[("errors", ""), ("latency", "")])).await.unwrap().is_empty());
+
+    assert_eq!(
+        test.parse_metrics(&values(&[("errors", "1.5"), ("latency", "fast"), ("disk", "1")])).await.unwrap_err(),
+        "metrics[disk]: is not a metric of this test\nmetrics[errors]: must be a whole number\nmetrics[latency]: must be a number"
+    );
+}
+
+#[rocket::async_test]
+async fn test_find_time() {
+    let mut test = Test::new("test".to_string());
+    for seconds in [10, 20, 30, 40] {
+        test.data.push(DataPoint::new(Timestamp::from_unix_seconds(seconds).unwrap(), 0, 0)).await;
+    }
+
+    assert_eq!(test.find_time(Timestamp::from_unix_seconds(10).unwrap()).await, Some(0));
+    assert_eq!(test.find_time(Timestamp::from_unix_seconds(40).unwrap()).await, Some(3));
+    assert_eq!(test.find_time(Timestamp::from_unix_seconds(25).unwrap()).await, None);
+}
+
+#[test]
+fn test_user_without_email() {
+    // Saved before users had an email
+    let user = User::from_str("alice,Alice,Smith,Manager").unwrap();
+    assert_eq!(user.get_email(), None);
+    assert_eq!(user.to_string(), "alice,Alice,Smith,Manager,");
+    assert_eq!(User::from_str("alice,,,Developer,alice@example.com").unwrap().get_email(), Some("alice@example.com".to_string()));
+}
+
+#[test]
+fn test_assignees() {
+    let mut entry = ScheduleEntry::from_str("soak,2024-05-01T09:00:00Z,alice bob,web,load").unwrap();
+    assert_eq!(entry.get_assignees(), vec!["alice", "bob"]);
+    assert!(!entry.add_assignee("alice".to_string()));
+    assert!(entry.add_assignee("carol".to_string()));
+    assert!(entry.rename_assignee("bob", "robert"));
+    assert!(entry.remove_assignee("alice"));
+    assert!(!entry.remove_assignee("alice"));
+    assert_eq!(entry.to_string(), "soak,2024-05-01T09:00:00Z,robert carol,web,load");
+}
+
+#[test]
+fn test_schedule_entry_test() {
+    let entry = ScheduleEntry::from_str("soak,2024-05-01T09:00:00Z,alice,web,load").unwrap();
+    assert!(entry.is_for("web", Some("load")));
+    assert!(entry.is_for("web", None));
+    assert!(!entry.is_for("web", Some("other")));
+    assert!(!entry.is_for("db", None));
+
+    // Entries saved before they had a server keep their test, with no server
+    let old = ScheduleEntry::from_str("soak,2024-05-01T09:00:00Z,alice,load").unwrap();
+    assert_eq!((old.get_server_id(), old.get_test_id()), (String::new(), "load".to_string()));
+    assert_eq!(old.to_string(), "soak,2024-05-01T09:00:00Z,alice,,load");
+}
+
+#[test]
+fn test_data_point_metric_fields() {
+    use crate::query::{Query, Searchable};
+    let data_point = DataPoint::from_str("2024-05-01T09:00:00Z,512,20,,Latency=1.5,p95=3").unwrap();
+    assert_eq!(data_point.field_value("latency"), Some("1.5".to_string())); // Query fields are lower case
+    assert_eq!(data_point.field_value("p95"), Some("3".to_string()));
+    assert_eq!(data_point.field_value("missing"), None);
+    assert!(Query::from_str("p95>=2 latency<2").unwrap().matches(&data_point));
+    assert!(!Query::from_str("p95>3").unwrap().matches(&data_point));
+}
@@ -1,8 +1,10 @@
 use std::cmp::Ordering;
 use std::fmt::Display;
 use std::str::FromStr;
+use crate::keyed_vector::Keyed;
 use crate::models::{Position, User};
 use crate::my_vector::SafePointer;
+use crate::query::Searchable;
 
 impl User {
     pub fn new(username: String, forename: Option<String>, surname: Option<String>, position: Position) -> Self {
@@ -10,7 +12,8 @@ impl User {
             username,
             forename,
             surname,
-            position
+            position,
+            email: None,
         }
     }
     
@@ -30,6 +33,10 @@ impl User {
         self.position.clone()
     }
 
+    pub fn get_email(&self) -> Option<String> {
+        self.email.clone()
+    }
+
     pub fn set_username(&mut self, username: String) {
         self.username = username
     }
@@ -45,6 +52,10 @@ impl User {
     pub fn set_position(&mut self, position: Position) {
         self.position = position;
     }
+
+    pub fn set_email(&mut self, email: Option<String>) {
+        self.email = email;
+    }
 }
 
 impl Display for User { // ToString
@@ -55,11 +66,12 @@ impl Display for User { // ToString
         }; // Turn the position into a string
         
         let str = format!(
-            "{},{},{},{}",
+            "{},{},{},{},{}",
             self.username,
             self.forename.clone().unwrap_or_default(),
             self.surname.clone().unwrap_or_default(),
             position,
+            self.email.clone().unwrap_or_default(),
         );
         write!(f, "{}", str)
     }
@@ -70,7 +82,7 @@ impl FromStr for User { // FromString
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.split(',').collect();
-        if parts.len() != 4 {
+        if parts.len() != 4 && parts.len() != 5 { // Users saved before emails were added have no email
             return Err("Invalid string format".to_string());
         }
 
@@ -84,6 +96,7 @@ impl FromStr for User { // FromString
             forename: if parts[1].is_empty() { None } else { Some(parts[1].to_string()) }, // If nothing there, None
             surname: if parts[2].is_empty() { None } else { Some(parts[2].to_string()) },
             position,
+            email: parts.get(4).filter(|email| !email.is_empty()).map(|email| email.to_string()),
         })
     }
 }
@@ -100,5 +113,27 @@ impl PartialOrd<Self> for User {
     }
 }
 
+impl Keyed for User {
+    fn get_key(&self) -> String {
+        self.get_username()
+    }
+}
+
 unsafe impl Send for SafePointer<User> {}
-unsafe impl Sync for SafePointer<User> {}
\ No newline at end of file
+unsafe impl Sync for SafePointer<User> {}
+
+impl Searchable for User {
+    const FIELDS: &'static [&'static str] = &["username", "forename", "surname", "position", "email"];
+    const DEFAULT_FIELDS: &'static [&'static str] = &["username", "forename", "surname"];
+
+    fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "username" => Some(self.get_username()),
+            "forename" => self.get_forename(),
+            "surname" => self.get_surname(),
+            "position" => Some(self.get_position().to_string()),
+            "email" => self.get_email(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::keyed_vector::Keyed;
+use crate::models::{Webhook, WebhookEvent};
+use crate::my_vector::SafePointer;
+
+impl WebhookEvent {
+    pub const ALL: &'static [WebhookEvent] = &[
+        WebhookEvent::TestCreated,
+        WebhookEvent::DataPointsIngested,
+        WebhookEvent::AlertFired,
+        WebhookEvent::AlertResolved,
+        WebhookEvent::ScheduleCreated,
+        WebhookEvent::ScheduleUpdated,
+        WebhookEvent::ScheduleDeleted,
+    ];
+}
+
+impl Display for WebhookEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookEvent::TestCreated => write!(f, "test.created"),
+            WebhookEvent::DataPointsIngested => write!(f, "datapoints.ingested"),
+            WebhookEvent::AlertFired => write!(f, "alert.fired"),
+            WebhookEvent::AlertResolved => write!(f, "alert.resolved"),
+            WebhookEvent::ScheduleCreated => write!(f, "schedule.created"),
+            WebhookEvent::ScheduleUpdated => write!(f, "schedule.updated"),
+            WebhookEvent::ScheduleDeleted => write!(f, "schedule.deleted"),
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.iter().find(|event| event.to_string() == s).copied().ok_or_else(|| format!("Unknown event '{}'", s))
+    }
+}
+
+impl Webhook {
+    pub fn new(id: String, url: String, events: Vec<WebhookEvent>, secret: String) -> Self {
+        Self {
+            id,
+            url,
+            events,
+            secret,
+        }
+    }
+
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub fn get_url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn get_events(&self) -> &[WebhookEvent] {
+        &self.events
+    }
+
+    pub fn get_secret(&self) -> String {
+        self.secret.clone()
+    }
+
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.contains(&event)
+    }
+}
+
+impl Display for Webhook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let events: Vec<String> = self.events.iter().map(|event| event.to_string()).collect();
+        write!(f, "{},{},{},{}", self.id, self.url, events.join(" "), self.secret)
+    }
+}
+
+impl FromStr for Webhook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        if parts.len() != 4 {
+            return Err("Invalid string format: expected id,url,events,secret".to_string());
+        }
+
+        Ok(Self {
+            id: parts[0].to_string(),
+            url: parts[1].to_string(),
+            events: parts[2].split_whitespace().map(WebhookEvent::from_str).collect::<Result<Vec<_>, _>>()?,
+            secret: parts[3].to_string(),
+        })
+    }
+}
+
+impl PartialEq for Webhook {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for Webhook {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.id.partial_cmp(&other.id)
+    }
+}
+
+impl Keyed for Webhook {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
+unsafe impl Send for SafePointer<Webhook> {}
+unsafe impl Sync for SafePointer<Webhook> {}
@@ -0,0 +1,225 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::keyed_vector::Keyed;
+use crate::models::{DeliveryAttempt, DeliveryOutcome, WebhookDelivery, WebhookEvent};
+use crate::my_vector::SafePointer;
+use crate::timestamp::Timestamp;
+
+impl WebhookDelivery {
+    pub fn new(id: String, webhook_id: String, event: WebhookEvent, next_attempt: Timestamp, payload: String) -> Self {
+        Self {
+            id,
+            webhook_id,
+            event,
+            attempts: 0,
+            next_attempt,
+            payload,
+        }
+    }
+
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub fn get_webhook_id(&self) -> String {
+        self.webhook_id.clone()
+    }
+
+    pub fn get_event(&self) -> WebhookEvent {
+        self.event
+    }
+
+    pub fn get_attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn get_next_attempt(&self) -> Timestamp {
+        self.next_attempt
+    }
+
+    pub fn get_payload(&self) -> &str {
+        &self.payload
+    }
+
+    // Count a failed attempt, and say when to try again
+    pub fn retry_at(&mut self, next_attempt: Timestamp) {
+        self.attempts += 1;
+        self.next_attempt = next_attempt;
+    }
+}
+
+impl Display for WebhookDelivery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{},{},{}", self.id, self.webhook_id, self.event, self.attempts, self.next_attempt, self.payload)
+    }
+}
+
+impl FromStr for WebhookDelivery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.splitn(6, ',').collect(); // The payload keeps its commas
+        if parts.len() != 6 {
+            return Err("Invalid string format: expected id,webhook,event,attempts,next_attempt,payload".to_string());
+        }
+
+        Ok(Self {
+            id: parts[0].to_string(),
+            webhook_id: parts[1].to_string(),
+            event: WebhookEvent::from_str(parts[2])?,
+            attempts: parts[3].parse::<u32>().map_err(|_| format!("Invalid attempts '{}'", parts[3]))?,
+            next_attempt: Timestamp::from_str(parts[4])?,
+            payload: parts[5].to_string(),
+        })
+    }
+}
+
+impl PartialEq for WebhookDelivery {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for WebhookDelivery {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.id.partial_cmp(&other.id)
+    }
+}
+
+impl Keyed for WebhookDelivery {
+    fn get_key(&self) -> String {
+        self.get_id()
+    }
+}
+
+unsafe impl Send for SafePointer<WebhookDelivery> {}
+unsafe impl Sync for SafePointer<WebhookDelivery> {}
+
+impl Display for DeliveryOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryOutcome::Delivered => write!(f, "Delivered"),
+            DeliveryOutcome::Retrying => write!(f, "Retrying"),
+            DeliveryOutcome::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+impl FromStr for DeliveryOutcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Delivered" => Ok(Self::Delivered),
+            "Retrying" => Ok(Self::Retrying),
+            "Failed" => Ok(Self::Failed),
+            _ => Err(format!("Unknown delivery outcome '{}'", s)),
+        }
+    }
+}
+
+impl DeliveryAttempt {
+    // The message cannot contain new lines, as each attempt is one line of the log
+    pub fn new(time: Timestamp, delivery: &WebhookDelivery, status: Option<u16>, outcome: DeliveryOutcome, message: &str) -> Self {
+        Self {
+            time,
+            delivery_id: delivery.get_id(),
+            webhook_id: delivery.get_webhook_id(),
+            event: delivery.get_event(),
+            attempt: delivery.get_attempts() + 1,
+            status,
+            outcome,
+            message: message.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    pub fn get_time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn get_delivery_id(&self) -> String {
+        self.delivery_id.clone()
+    }
+
+    pub fn get_webhook_id(&self) -> String {
+        self.webhook_id.clone()
+    }
+
+    pub fn get_event(&self) -> WebhookEvent {
+        self.event
+    }
+
+    pub fn get_attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn get_status(&self) -> Option<u16> {
+        self.status
+    }
+
+    pub fn get_outcome(&self) -> DeliveryOutcome {
+        self.outcome
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+impl Display for DeliveryAttempt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.time,
+            self.delivery_id,
+            self.webhook_id,
+            self.event,
+            self.attempt,
+            self.status.map(|status| status.to_string()).unwrap_or_default(),
+            self.outcome,
+            self.message
+        )
+    }
+}
+
+impl FromStr for DeliveryAttempt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.splitn(8, ',').collect(); // The message keeps its commas
+        if parts.len() != 8 {
+            return Err("Invalid string format: expected time,delivery,webhook,event,attempt,status,outcome,message".to_string());
+        }
+
+        Ok(Self {
+            time: Timestamp::from_str(parts[0])?,
+            delivery_id: parts[1].to_string(),
+            webhook_id: parts[2].to_string(),
+            event: WebhookEvent::from_str(parts[3])?,
+            attempt: parts[4].parse::<u32>().map_err(|_| format!("Invalid attempt '{}'", parts[4]))?,
+            status: match parts[5] {
+                "" => None,
+                status => Some(status.parse::<u16>().map_err(|_| format!("Invalid status '{}'", status))?),
+            },
+            outcome: DeliveryOutcome::from_str(parts[6])?,
+            message: parts[7].to_string(),
+        })
+    }
+}
+
+impl PartialEq for DeliveryAttempt {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl PartialOrd for DeliveryAttempt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.time.partial_cmp(&other.time) // Sort chronologically
+    }
+}
+
+unsafe impl Send for SafePointer<DeliveryAttempt> {}
+unsafe impl Sync for SafePointer<DeliveryAttempt> {}
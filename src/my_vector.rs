@@ -1,9 +1,10 @@
 use std::alloc::{alloc, dealloc, Layout};
+use std::cmp::Ordering;
 use std::{alloc, io, ptr};
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use rocket::tokio::fs::{create_dir_all, File};
+use rocket::tokio::fs::{create_dir_all, File, OpenOptions};
 use rocket::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
 // Lots of pointer arithmetic :(
@@ -34,7 +35,6 @@ impl<T> SafePointer<T> {
 }
 
 // T is a generic type, allowing this vector to be used for any object
-#[derive(Clone)]
 pub struct MyVector<T> {
     slice: SafePointer<T>, // Raw pointer to the data
     pub length: u32, // Length of the current data, publicly accessible
@@ -42,6 +42,45 @@ pub struct MyVector<T> {
     sorted: bool, // Whether the array is sorted or not
 }
 
+// Cloning copies the elements into new memory, so changing the clone does not change the original
+impl<T: Clone> Clone for MyVector<T> {
+    fn clone(&self) -> Self {
+        let layout = Layout::array::<T>(self.capacity as usize).unwrap();
+        let source = self.slice.get_pointer();
+
+        let ptr = unsafe {
+            let ptr = alloc::alloc(layout) as *mut T;
+            for i in 0..self.length as usize {
+                ptr::write(ptr.add(i), (*source.add(i)).clone()); // Clone each element into the new memory
+            }
+            ptr
+        };
+
+        MyVector {
+            slice: SafePointer::new(ptr),
+            length: self.length,
+            capacity: self.capacity,
+            sorted: self.sorted,
+        }
+    }
+}
+
+// Drop the elements and free the memory, or every vector, including each clone, would leak
+impl<T> Drop for MyVector<T> {
+    fn drop(&mut self) {
+        let slice = self.slice.get_pointer();
+        let layout = Layout::array::<T>(self.capacity as usize).unwrap();
+
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(slice, self.length as usize)); // Only the first length elements are in use
+            if layout.size() > 0 { // Nothing was allocated
+                dealloc(slice as *mut u8, layout);
+            }
+        }
+    }
+}
+
+#[allow(dead_code)] // General-purpose container, not every method is used by the site yet
 impl<T> MyVector<T>
 where
     T: Clone + PartialOrd, // Must be able to clone elements and compare elements for quick sort
@@ -68,6 +107,16 @@ where
         Self::new_with_capacity(10)
     }
 
+    // Create a vector from values that are already in ascending order, so it can be searched without sorting it again
+    pub async fn from_sorted(values: Vec<T>) -> MyVector<T> {
+        let mut vector = Self::new_with_capacity((values.len() as u32).max(1)); // Memory cannot be allocated for no elements
+        for value in values {
+            vector.push(value).await;
+        }
+        vector.sorted = true;
+        vector
+    }
+
     // Push an element to the end of MyVector
     pub async fn push(&mut self, value: T) {
         self.sorted = false;
@@ -158,21 +207,38 @@ where
         let slice = self.slice.get_pointer();
         unsafe {
             let element_ptr = slice.add(index as usize);
-            ptr::write(element_ptr, value);
+            *element_ptr = value; // Drops the old value
         }
     }
 
     // Get array as an immutable slice, useful for reading data from the array without editing
-    async fn as_slice(&self) -> &[T] { // Returns a slice (an array)
+    pub async fn as_slice(&self) -> &[T] { // Returns a slice (an array)
         let slice = self.slice.get_pointer();
-        let slice_unguarded: *mut T = slice.clone();
+        let slice_unguarded: *mut T = slice;
         unsafe {
             std::slice::from_raw_parts(slice_unguarded, self.length as usize) // Convert our allocated memory into a slice
         }
     }
 
+    // View the elements between start (inclusive) and end (exclusive) without copying them.
+    // Out of range indices are clamped to the vector, so this never panics
+    pub async fn range(&self, start: u32, end: u32) -> &[T] {
+        let end = end.min(self.length);
+        let start = start.min(end);
+        &self.as_slice().await[start as usize..end as usize]
+    }
+
+    // View up to limit elements, skipping the first offset elements
+    pub async fn page(&self, offset: u32, limit: u32) -> &[T] {
+        paginate(self.as_slice().await, offset, limit)
+    }
+
     // Get the value as a Clone
     pub async fn get(&self, index: u32) -> Option<T> { // Returns optional, as element may not exist
+        if index >= self.length { // Past the end is unused memory
+            return None;
+        }
+
         let ptr = self.slice.get_pointer();
         let element_ptr = unsafe { ptr.add(index as usize) };
 
@@ -193,9 +259,9 @@ where
     }
 
     // Get element at some index in an array as a mutable reference
-    pub async fn get_mut(&self, index: u32) -> Option<&mut T> { // Returns optional, as element may not exist
+    pub async fn get_mut(&mut self, index: u32) -> Option<&mut T> { // Returns optional, as element may not exist
 
-        if index > self.length {
+        if index >= self.length {
             return None;
         }
 
@@ -204,50 +270,90 @@ where
 
         // Safe read of the element
         unsafe {
-            let value = if !element_ptr.is_null() {
-                ptr
-            } else {
+            if element_ptr.is_null() {
                 return None;
-            };
+            }
 
-            Some(&mut *value)
+            Some(&mut *element_ptr) // Reference the element itself, not the start of the array
         }
     }
 
     // Begins performing the quick sort
     pub async fn quick_sort(&mut self) {
+        self.quick_sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Greater)).await;
+        self.sorted = true; // List is now sorted
+    }
+
+    // Quick sort using a custom comparison, e.g. to sort by a primary key instead of the natural order
+    pub async fn quick_sort_by<F>(&mut self, compare: F)
+    where
+        F: Fn(&T, &T) -> Ordering,
+    {
         if self.length > 1 { // If there is more than one element, else nothing needs sorting
             let len = self.length;
-            self.quick_sort_helper(0, len - 1).await; // Sort the whole array, to start with
+            self.quick_sort_helper(0, len - 1, &compare).await; // Sort the whole array, to start with
         }
-        self.sorted = true; // List is now sorted
+        self.sorted = false; // Only sorted in the natural order once quick_sort says so
     }
 
-    // Helper method to perform quicksort on the vector between 2 indices
-    async fn quick_sort_helper(&mut self, low: u32, high: u32) {
-        if low < high { // If they are the same, sorting is not necessary
-            let pivot_index = self.partition(low, high).await; // Get the pivot by partitioning the array
-            if pivot_index > 0 { // If pivot is 0, there are no elements to sort on this side of the array
-                Box::pin(self.quick_sort_helper(low, pivot_index - 1)).await; // If pivot is not 0, we still need to sort, continue
+    // Helper method to perform quicksort on the vector between 2 indices.
+    // Recurses into the smaller side and loops over the larger, so large vectors cannot overflow the stack
+    async fn quick_sort_helper<F>(&mut self, mut low: u32, mut high: u32, compare: &F)
+    where
+        F: Fn(&T, &T) -> Ordering,
+    {
+        while low < high { // If they are the same, sorting is not necessary
+            let (equal_start, greater_start) = self.partition(low, high, compare).await; // Elements equal to the pivot are already in place
+            if equal_start - low < high + 1 - greater_start { // Left side is smaller
+                if equal_start > low { // Else there are no elements to sort on this side of the array
+                    Box::pin(self.quick_sort_helper(low, equal_start - 1, compare)).await;
+                }
+                low = greater_start; // Sort the right side next
+            } else { // Right side is smaller
+                if greater_start < high {
+                    Box::pin(self.quick_sort_helper(greater_start, high, compare)).await;
+                }
+                if equal_start == low { // Nothing is left of the pivot
+                    break;
+                }
+                high = equal_start - 1; // Sort the left side next
             }
-            Box::pin(self.quick_sort_helper(pivot_index + 1, high)).await; // Sort other half of the array
         }
     }
 
-    // Partitions the array into 2 parts, and returns the pivot index
-    async fn partition(&mut self, low: u32, high: u32) -> u32 {
-        let pivot_value = self.get(high).await; // Pivot element is the element at the high index
-        let mut i = low; // Keep track of where the pivot element should be at the end
-
-        for j in low..high { // Loop through the array from low to high - 1
-            if self.get(j).await <= pivot_value.clone() { // If the current element is less than or equal to the pivot, swap them
-                self.swap(i, j).await;
-                i += 1; // Increment i
+    // Partitions the array into 3 parts: less than, equal to and greater than the pivot.
+    // Returns where the equal and greater parts start, so many equal elements do not make the sort slow
+    async fn partition<F>(&mut self, low: u32, high: u32, compare: &F) -> (u32, u32)
+    where
+        F: Fn(&T, &T) -> Ordering,
+    {
+        // Use the middle element as the pivot, so data that is already mostly in order still splits in half
+        self.swap(low + (high - low) / 2, low).await;
+        let pivot_value = self.get(low).await.expect("Pivot is out of bounds!");
+
+        let mut less_end = low; // Elements before this are less than the pivot
+        let mut greater_start = high + 1; // Elements from this on are greater than the pivot
+        // Elements from less_end up to i are equal to the pivot. The pivot itself is always among them,
+        // even if it does not compare equal to itself (e.g. NaN), so each partition makes progress
+        let mut i = low + 1;
+
+        while i < greater_start {
+            let current = self.get(i).await.expect("Index is out of bounds!");
+            match compare(&current, &pivot_value) {
+                Ordering::Less => {
+                    self.swap(less_end, i).await;
+                    less_end += 1;
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    greater_start -= 1;
+                    self.swap(i, greater_start).await; // Check the element swapped in next
+                }
+                Ordering::Equal => i += 1,
             }
         }
 
-        self.swap(i, high).await; // Place pivot element into correct position, kept track of with i
-        i // Return the pivot index
+        (less_end, greater_start)
     }
 
     // Swap two elements in the vector
@@ -301,18 +407,7 @@ where
             self.quick_sort().await; // Sort the vector if it's not already sorted
         }
 
-        // Linear search for where to insert
-        let mut index = 0;
-        while index < self.length && match self.get(index).await {
-            Some(value) => value,
-            None => {
-                eprintln!("Failed to insert value!");
-                panic!();
-            }
-        } < value {
-            index += 1;
-        }
-
+        let index = self.lower_bound(&value).await; // Binary search for where to insert
         self.insert(index, value).await;
 
         self.sorted = true; // Array is now sorted
@@ -370,6 +465,92 @@ where
         None
     }
 
+    // Perform a binary search using a custom comparison against the wanted element.
+    // The vector must already be ordered consistently with `compare`, the sorted flag is not checked.
+    // Returns Ok(index) if found, or Err(index) of where the element would need inserting
+    pub async fn binary_search_by<F>(&self, compare: F) -> Result<u32, u32>
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        let mut low = 0;
+        let mut high = self.length; // Search the range [low, high)
+
+        while low < high {
+            let mid = low + (high - low) / 2; // Get midpoint
+            let mid_value = self.get(mid).await.expect("Failed to search array!");
+
+            match compare(&mid_value) {
+                Ordering::Equal => return Ok(mid), // Value found
+                Ordering::Less => low = mid + 1, // Value is to the right
+                Ordering::Greater => high = mid, // Value is to the left
+            }
+        }
+
+        Err(low) // Not found, low is where it would be
+    }
+
+    // Index of the first element that is not less than value, or the length if there is none
+    pub async fn lower_bound(&self, value: &T) -> u32 {
+        if !self.sorted { // Must be sorted
+            panic!("Vector is not sorted!");
+        }
+        self.lower_bound_by(|a| a.partial_cmp(value).unwrap_or(Ordering::Less)).await
+    }
+
+    // Index of the first element that is greater than value, or the length if there is none
+    pub async fn upper_bound(&self, value: &T) -> u32 {
+        if !self.sorted { // Must be sorted
+            panic!("Vector is not sorted!");
+        }
+        self.upper_bound_by(|a| a.partial_cmp(value).unwrap_or(Ordering::Less)).await
+    }
+
+    // Index of the first element where compare does not return Less.
+    // The vector must already be ordered consistently with `compare`, the sorted flag is not checked
+    pub async fn lower_bound_by<F>(&self, compare: F) -> u32
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        let mut low = 0;
+        let mut high = self.length; // Answer is always within [low, high]
+
+        while low < high {
+            let mid = low + (high - low) / 2; // Get midpoint
+            let mid_value = self.get(mid).await.expect("Failed to search array!");
+
+            if compare(&mid_value) == Ordering::Less { // Bound is to the right
+                low = mid + 1;
+            } else { // Bound is mid or to the left
+                high = mid;
+            }
+        }
+
+        low
+    }
+
+    // Index of the first element where compare returns Greater.
+    // The vector must already be ordered consistently with `compare`, the sorted flag is not checked
+    pub async fn upper_bound_by<F>(&self, compare: F) -> u32
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        let mut low = 0;
+        let mut high = self.length; // Answer is always within [low, high]
+
+        while low < high {
+            let mid = low + (high - low) / 2; // Get midpoint
+            let mid_value = self.get(mid).await.expect("Failed to search array!");
+
+            if compare(&mid_value) != Ordering::Greater { // Bound is to the right
+                low = mid + 1;
+            } else { // Bound is mid or to the left
+                high = mid;
+            }
+        }
+
+        low
+    }
+
     // Save the vector data to a specified file
     pub async fn save_to_file(&self, file_path: &str) -> io::Result<()> {
         let path = Path::new(file_path);
@@ -391,7 +572,7 @@ where
             }.to_string(); // Convert the element to a string to write to file
             match file.write(format!("{}{}", element_str, "\n").as_bytes()).await { // Write to the file
                 Ok(_) => {},
-                Err(e) => {
+                Err(_) => {
                     eprintln!("Failed to save vector to file!");
                     panic!();
                 }
@@ -401,6 +582,26 @@ where
         Ok(())
     }
 
+    // Add the vector data to the end of a specified file, in a single write
+    pub async fn append_to_file(&self, file_path: &str) -> io::Result<()> {
+        let path = Path::new(file_path);
+
+        // Ensure the directory exists
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+
+        let mut output = String::new();
+        for value in self.as_slice().await {
+            output.push_str(&value.to_string());
+            output.push('\n');
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(output.as_bytes()).await?;
+        file.flush().await // Tokio finishes writes in the background otherwise
+    }
+
     // Load vector data from a specified file
     pub async fn load_from_file(file_path: &str) -> MyVector<T> {
         let path = Path::new(file_path);
@@ -429,9 +630,8 @@ where
         let mut vec = MyVector::new_with_capacity(10); // Default capacity
 
         while let Ok(Some(line)) = lines.next_line().await {
-            match T::from_str(&line) {
-                Ok(value) => vec.push(value).await,
-                Err(_) => {}
+            if let Ok(value) = T::from_str(&line) {
+                vec.push(value).await
             }
         }
 
@@ -452,20 +652,86 @@ where
         }
         result
     }
+
+    // Like search_all, but only copies up to limit matches after skipping the first offset matches.
+    // Also returns the total number of matches, for showing how many pages there are
+    pub async fn search_page<F>(&self, predicate: F, offset: u32, limit: u32) -> (MyVector<T>, u32)
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut result = MyVector::new_with_capacity(limit.clamp(1, 10));
+        let mut total = 0;
+        for value in self.as_slice().await {
+            if predicate(value) {
+                if total >= offset && total - offset < limit {
+                    result.push(value.clone()).await;
+                }
+                total += 1;
+            }
+        }
+        (result, total)
+    }
+}
+
+// Take up to limit items from a slice, skipping the first offset items
+pub fn paginate<T>(items: &[T], offset: u32, limit: u32) -> &[T] {
+    let start = (offset as usize).min(items.len());
+    let end = start.saturating_add(limit as usize).min(items.len());
+    &items[start..end]
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MyVector;
+    use super::{paginate, MyVector};
+    use std::fmt::{Display, Formatter};
     use std::fs;
+    use std::str::FromStr;
+    use std::sync::Arc;
     use rocket::tokio;
 
+    // Counts how many copies are alive, through the Arc's strong count
+    #[derive(Clone, PartialEq, PartialOrd)]
+    struct Tracked(Arc<u32>);
+
+    impl Display for Tracked {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl FromStr for Tracked {
+        type Err = std::num::ParseIntError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            Ok(Tracked(Arc::new(s.parse()?)))
+        }
+    }
+
     #[tokio::test]
     async fn test_new_with_capacity() {
         let vec: MyVector<i32> = MyVector::new_with_capacity(5);
         assert_eq!(vec.capacity, 5);
         assert_eq!(vec.length, 0);
-        assert_eq!(vec.sorted, false);
+        assert!(!vec.sorted);
+    }
+
+    #[tokio::test]
+    async fn test_drop() {
+        let tracked = Tracked(Arc::new(1));
+        let mut vec = MyVector::new_with_capacity(1);
+        for _ in 0..20 {
+            vec.push(tracked.clone()).await; // Grows a few times
+        }
+        vec.set(0, tracked.clone()).await;
+        vec.remove(1).await;
+        vec.quick_sort().await;
+        assert_eq!(Arc::strong_count(&tracked.0), 20);
+
+        let cloned = vec.clone();
+        assert_eq!(Arc::strong_count(&tracked.0), 39);
+        drop(cloned);
+        drop(vec);
+        assert_eq!(Arc::strong_count(&tracked.0), 1); // Nothing leaked
     }
 
     #[tokio::test]
@@ -473,7 +739,7 @@ mod tests {
         let vec: MyVector<i32> = MyVector::new();
         assert_eq!(vec.capacity, 10);
         assert_eq!(vec.length, 0);
-        assert_eq!(vec.sorted, false);
+        assert!(!vec.sorted);
     }
 
     #[tokio::test]
@@ -538,7 +804,7 @@ mod tests {
 
         vec.set(1, 5).await;
         assert_eq!(vec.get(1).await.unwrap(), 5);
-        assert_eq!(vec.sorted, false);
+        assert!(!vec.sorted);
     }
 
     #[tokio::test]
@@ -566,6 +832,39 @@ mod tests {
         assert!(vec.sorted);
     }
 
+    #[tokio::test]
+    async fn test_quick_sort_large_sorted() {
+        let mut vec = MyVector::new();
+        for i in 0..50_000 {
+            vec.push(i).await;
+        }
+        vec.push(-1).await;
+
+        vec.quick_sort().await; // Already sorted data used to recurse once per element and overflow the stack
+        assert_eq!(vec.get(0).await, Some(-1));
+        assert!(vec.as_slice().await.is_sorted());
+    }
+
+    #[tokio::test]
+    async fn test_quick_sort_many_equal() {
+        let mut vec = MyVector::new();
+        for i in 0..50_000 {
+            vec.push(i % 2).await; // Like a passed metric, only ever 0 or 1
+        }
+
+        vec.quick_sort().await; // Equal elements used to all end up on one side of the pivot, taking quadratic time
+        assert_eq!(vec.get(24_999).await, Some(0));
+        assert_eq!(vec.get(25_000).await, Some(1));
+        assert!(vec.as_slice().await.is_sorted());
+
+        let mut floats = MyVector::new();
+        for value in [2.0, f64::NAN, 1.0, f64::NAN, 0.0] {
+            floats.push(value).await;
+        }
+        floats.quick_sort().await; // Values that do not compare equal to themselves still finish
+        assert_eq!(floats.length, 5);
+    }
+
     #[tokio::test]
     async fn test_swap() {
         let mut vec = MyVector::new_with_capacity(3);
@@ -590,7 +889,7 @@ mod tests {
 
         assert_eq!(vec.length, 5);
         assert_eq!(vec.as_slice().await, &[1, 2, 3, 4, 5]);
-        assert_eq!(vec.sorted, false); // Inserting an element disrupts sorting
+        assert!(!vec.sorted); // Inserting an element disrupts sorting
     }
 
     #[tokio::test]
@@ -725,6 +1024,153 @@ mod tests {
         assert_eq!(vec.binary_search(1).await, None); // Should return None for an empty vector
     }
 
+    #[tokio::test]
+    async fn test_binary_search_by() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(10).await;
+        vec.push(20).await;
+        vec.push(30).await;
+
+        assert_eq!(vec.binary_search_by(|a| a.cmp(&20)).await, Ok(1));
+        assert_eq!(vec.binary_search_by(|a| a.cmp(&5)).await, Err(0)); // Would go at the start
+        assert_eq!(vec.binary_search_by(|a| a.cmp(&25)).await, Err(2)); // Would go between 20 and 30
+        assert_eq!(vec.binary_search_by(|a| a.cmp(&35)).await, Err(3)); // Would go at the end
+    }
+
+    #[tokio::test]
+    async fn test_lower_and_upper_bound() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(10).await;
+        vec.push(20).await;
+        vec.push(20).await;
+        vec.push(30).await;
+        vec.quick_sort().await;
+
+        assert_eq!(vec.lower_bound(&20).await, 1); // First 20
+        assert_eq!(vec.upper_bound(&20).await, 3); // After the last 20
+        assert_eq!(vec.lower_bound(&5).await, 0);
+        assert_eq!(vec.upper_bound(&35).await, 4); // Past the end
+        assert_eq!(vec.lower_bound(&25).await, 3);
+        assert_eq!(vec.upper_bound(&25).await, 3); // Not present, both bounds are where it would go
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Vector is not sorted!")]
+    async fn test_lower_bound_unsorted_vector() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(5).await;
+        vec.push(1).await;
+
+        vec.lower_bound(&3).await; // This should panic, as the vector is not sorted
+    }
+
+    #[tokio::test]
+    async fn test_bounds_by_key() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(15).await;
+        vec.push(22).await;
+        vec.push(27).await;
+        vec.push(31).await;
+
+        // Everything in the twenties, comparing by the tens digit
+        let start = vec.lower_bound_by(|a| (a / 10).cmp(&2)).await;
+        let end = vec.upper_bound_by(|a| (a / 10).cmp(&2)).await;
+        assert_eq!(vec.range(start, end).await, &[22, 27]);
+    }
+
+    #[tokio::test]
+    async fn test_range() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(1).await;
+        vec.push(2).await;
+        vec.push(3).await;
+        vec.push(4).await;
+
+        assert_eq!(vec.range(1, 3).await, &[2, 3]);
+        assert_eq!(vec.range(2, 10).await, &[3, 4]); // End is clamped
+        assert_eq!(vec.range(3, 1).await, &[] as &[i32]); // Start after end is empty
+        assert_eq!(vec.range(10, 20).await, &[] as &[i32]);
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let mut vec = MyVector::new_with_capacity(5);
+        for i in 1..=7 {
+            vec.push(i).await;
+        }
+
+        assert_eq!(vec.page(0, 3).await, &[1, 2, 3]);
+        assert_eq!(vec.page(3, 3).await, &[4, 5, 6]);
+        assert_eq!(vec.page(6, 3).await, &[7]); // Last page is short
+        assert_eq!(vec.page(9, 3).await, &[] as &[i32]); // Past the end
+        assert_eq!(vec.page(0, u32::MAX).await.len(), 7);
+        assert_eq!(paginate(&[1, 2, 3], 1, 1), &[2]);
+    }
+
+    #[tokio::test]
+    async fn test_search_page() {
+        let mut vec = MyVector::new_with_capacity(5);
+        for i in 1..=10 {
+            vec.push(i).await;
+        }
+
+        let (page, total) = vec.search_page(|a| a % 2 == 0, 1, 2).await; // Even numbers, second and third
+        assert_eq!(page.as_slice().await, &[4, 6]);
+        assert_eq!(total, 5);
+
+        let (page, total) = vec.search_page(|a| *a > 100, 0, 2).await;
+        assert_eq!(page.length, 0);
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_quick_sort_by() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(1).await;
+        vec.push(3).await;
+        vec.push(2).await;
+
+        vec.quick_sort_by(|a: &i32, b: &i32| b.cmp(a)).await; // Sort descending
+        assert_eq!(vec.as_slice().await, &[3, 2, 1]);
+        assert!(!vec.sorted); // Not sorted in the natural order
+    }
+
+    #[tokio::test]
+    async fn test_get_mut() {
+        let mut vec = MyVector::new_with_capacity(3);
+        vec.push(1).await;
+        vec.push(2).await;
+        vec.push(3).await;
+
+        *vec.get_mut(1).await.unwrap() = 5;
+        assert_eq!(vec.as_slice().await, &[1, 5, 3]);
+        assert!(vec.get_mut(3).await.is_none()); // Out of bounds
+    }
+
+    #[tokio::test]
+    async fn test_get_out_of_bounds() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(1).await;
+
+        assert_eq!(vec.get(1).await, None); // Within capacity, but past the end
+        assert_eq!(vec.get(10).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_clone_is_independent() {
+        let mut vec = MyVector::new_with_capacity(5);
+        vec.push(3).await;
+        vec.push(1).await;
+        vec.push(2).await;
+
+        let mut cloned = vec.clone();
+        cloned.quick_sort().await;
+        cloned.set(0, 10).await;
+
+        assert_eq!(vec.as_slice().await, &[3, 1, 2]); // Original is untouched
+        assert_eq!(cloned.as_slice().await, &[10, 2, 3]);
+    }
+
     #[tokio::test]
     async fn test_linear_search_found() {
         let mut vec = MyVector::new_with_capacity(5);
@@ -805,6 +1251,22 @@ mod tests {
         fs::remove_file(file_path).expect("Failed to delete test file");
     }
 
+    #[tokio::test]
+    async fn test_append_to_file() {
+        let file_path = "test_append_vector.txt";
+        fs::write(file_path, "10\n").expect("Failed to write test file");
+
+        let mut vec = MyVector::new();
+        vec.push(20).await;
+        vec.push(30).await;
+        vec.append_to_file(file_path).await.expect("Failed to append to file");
+
+        assert_eq!(fs::read_to_string(file_path).unwrap(), "10\n20\n30\n"); // Existing lines are kept
+
+        // Clean up the test file
+        fs::remove_file(file_path).expect("Failed to delete test file");
+    }
+
     #[tokio::test]
     async fn test_load_from_file_invalid_data() {
         let file_path = "test_invalid_data.txt";
@@ -822,3 +1284,148 @@ mod tests {
         fs::remove_file(file_path).expect("Failed to delete test file");
     }
 }
+
+
+// Property tests, checking that MyVector behaves the same as std's Vec for random sequences of operations
+#[cfg(test)]
+mod property_tests {
+    use super::MyVector;
+    use proptest::prelude::*;
+    use rocket::tokio::runtime::{Builder, Runtime};
+
+    #[derive(Debug, Clone)]
+    enum Operation {
+        Push(i32),
+        Insert(u32, i32), // Index is taken modulo the length, so it is always valid
+        Remove(u32),
+        Set(u32, i32),
+        Sort,
+        InsertSorted(i32),
+        Clone, // Continue with a clone, which must not share memory with the original
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            any::<i32>().prop_map(Operation::Push),
+            (any::<u32>(), any::<i32>()).prop_map(|(i, v)| Operation::Insert(i, v)),
+            any::<u32>().prop_map(Operation::Remove),
+            (any::<u32>(), any::<i32>()).prop_map(|(i, v)| Operation::Set(i, v)),
+            Just(Operation::Sort),
+            (-50..50i32).prop_map(Operation::InsertSorted), // Small range, so duplicates are common
+            Just(Operation::Clone),
+        ]
+    }
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().unwrap()
+    }
+
+    // Apply one operation to both vectors
+    async fn apply(operation: &Operation, mine: &mut MyVector<i32>, expected: &mut Vec<i32>) {
+        let length = expected.len() as u32;
+        match *operation {
+            Operation::Push(value) => {
+                mine.push(value).await;
+                expected.push(value);
+            }
+            Operation::Insert(index, value) => {
+                let index = index % (length + 1);
+                mine.insert(index, value).await;
+                expected.insert(index as usize, value);
+            }
+            Operation::Remove(index) if length > 0 => {
+                let index = index % length;
+                assert_eq!(mine.remove(index).await, Some(expected.remove(index as usize)));
+            }
+            Operation::Set(index, value) if length > 0 => {
+                let index = index % length;
+                mine.set(index, value).await;
+                expected[index as usize] = value;
+            }
+            Operation::Sort => {
+                mine.quick_sort().await;
+                expected.sort();
+            }
+            Operation::InsertSorted(value) => {
+                mine.insert_sorted(value).await;
+                expected.sort();
+                let index = expected.partition_point(|a| *a < value);
+                expected.insert(index, value);
+            }
+            Operation::Clone => {
+                let mut original = std::mem::replace(mine, mine.clone());
+                for i in 0..original.length {
+                    original.set(i, i32::MIN).await; // Overwrite the original, which must not change the clone
+                }
+            }
+            _ => {} // Remove or set on an empty vector, nothing to compare
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn behaves_like_vec(operations in prop::collection::vec(operation(), 0..100)) {
+            runtime().block_on(async {
+                let mut mine = MyVector::new_with_capacity(1);
+                let mut expected = Vec::new();
+
+                for operation in &operations {
+                    apply(operation, &mut mine, &mut expected).await;
+
+                    assert_eq!(mine.length as usize, expected.len());
+                    assert_eq!(mine.as_slice().await, expected.as_slice());
+                }
+
+                for (i, value) in expected.iter().enumerate() {
+                    assert_eq!(mine.get(i as u32).await, Some(*value));
+                }
+                assert_eq!(mine.get(expected.len() as u32).await, None);
+            });
+        }
+
+        #[test]
+        fn sorted_vector_search(values in prop::collection::vec(-100..100i32, 0..50), wanted in -100..100i32) {
+            runtime().block_on(async {
+                let mut mine = MyVector::new();
+                for value in &values {
+                    mine.push(*value).await;
+                }
+                mine.quick_sort().await;
+
+                let mut expected = values.clone();
+                expected.sort();
+
+                // Any matching index is fine, as duplicates are allowed
+                match mine.binary_search(wanted).await {
+                    Some(index) => assert_eq!(expected[index as usize], wanted),
+                    None => assert!(!expected.contains(&wanted)),
+                }
+                match mine.binary_search_by(|a| a.cmp(&wanted)).await {
+                    Ok(index) => assert_eq!(expected[index as usize], wanted),
+                    Err(index) => {
+                        assert!(!expected.contains(&wanted));
+                        assert_eq!(index as usize, expected.partition_point(|a| *a < wanted));
+                    }
+                }
+                assert_eq!(mine.search(|a| *a == wanted).await.is_some(), expected.contains(&wanted));
+
+                assert_eq!(mine.lower_bound(&wanted).await as usize, expected.partition_point(|a| *a < wanted));
+                assert_eq!(mine.upper_bound(&wanted).await as usize, expected.partition_point(|a| *a <= wanted));
+            });
+        }
+
+        #[test]
+        fn page_matches_skip_take(values in prop::collection::vec(any::<i32>(), 0..50), offset in 0..60u32, limit in 0..60u32) {
+            runtime().block_on(async {
+                let mut mine = MyVector::new();
+                for value in &values {
+                    mine.push(*value).await;
+                }
+
+                let expected: Vec<i32> = values.iter().skip(offset as usize).take(limit as usize).cloned().collect();
+                assert_eq!(mine.page(offset, limit).await, expected.as_slice());
+                assert_eq!(mine.range(offset, offset + limit).await, expected.as_slice());
+            });
+        }
+    }
+}
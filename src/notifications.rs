@@ -0,0 +1,481 @@
+// Email notifications for the schedule.
+// Users with an email are told when they are assigned to an entry, when an entry they are assigned to changes or
+// is deleted, and reminded a while before it starts. Mail is sent over plain SMTP, without TLS or a login, to the
+// server set by smtp_host in Rocket's config (Rocket.toml or ROCKET_SMTP_HOST), so is meant for a local relay.
+// Nothing is sent without smtp_host
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use rocket::figment::Figment;
+use rocket::Shutdown;
+use rocket::tokio::fs;
+use rocket::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use rocket::tokio::net::TcpStream;
+use rocket::tokio::select;
+use rocket::tokio::sync::Mutex;
+use rocket::tokio::time::{sleep, timeout};
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+use crate::models::{ScheduleEntry, SiteData, User};
+use crate::timestamp::Timestamp;
+
+pub const REMINDERS_PATH: &str = "./data/notifications/reminded"; // Entries already reminded about, as id,datetime
+
+const DEFAULT_SMTP_PORT: u16 = 25;
+const DEFAULT_FROM: &str = "test-management@localhost";
+const DEFAULT_REMINDER_MINUTES: u32 = 60;
+const TIMEOUT: Duration = Duration::from_secs(30); // For a whole session with the SMTP server
+const REMINDER_CHECK: Duration = Duration::from_secs(60);
+
+// The SMTP server mail is sent through
+#[derive(Clone, Debug)]
+pub struct MailConfig {
+    server: String, // With the port
+    from: String,
+}
+
+impl MailConfig {
+    pub fn new(server: String, from: String) -> Self {
+        Self { server, from }
+    }
+
+    // Set up from smtp_host, smtp_port and smtp_from in Rocket's config, or None if smtp_host is not set
+    pub fn from_config(figment: &Figment) -> Option<Self> {
+        let host = figment.extract_inner::<String>("smtp_host").ok()?;
+        let port = figment.extract_inner::<u16>("smtp_port").unwrap_or(DEFAULT_SMTP_PORT);
+        let from = figment.extract_inner::<String>("smtp_from").unwrap_or_else(|_| DEFAULT_FROM.to_string());
+        Some(Self::new(format!("{}:{}", host, port), from))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// The entry as it is shown in an email
+fn describe(entry: &ScheduleEntry) -> String {
+    format!(
+        "Test: {}/{}\nWhen: {}\nAssignees: {}\n",
+        entry.get_server_id(),
+        entry.get_test_id(),
+        entry.get_datetime(),
+        entry.get_assignees().join(", ")
+    )
+}
+
+// An email to a user, if they exist and have an email
+fn email_to(users: &[User], username: &str, subject: String, body: String) -> Option<Email> {
+    let to = users.iter().find(|user| user.get_username() == username)?.get_email()?;
+    Some(Email { to, subject, body })
+}
+
+// Emails for the assignees of an entry that was created (old is None), updated, or deleted (new is None)
+pub fn schedule_emails(users: &[User], old: Option<&ScheduleEntry>, new: Option<&ScheduleEntry>) -> Vec<Email> {
+    let before = old.map(|entry| entry.get_assignees()).unwrap_or_default();
+    let after = new.map(|entry| entry.get_assignees()).unwrap_or_default();
+    let mut emails = Vec::new();
+
+    if let Some(new) = new {
+        for username in &after {
+            let email = match old {
+                Some(old) if before.contains(username) => {
+                    if old.to_string() == new.to_string() {
+                        continue; // Saved without changes
+                    }
+                    email_to(
+                        users,
+                        username,
+                        format!("Schedule entry {} has changed", new.get_id()),
+                        format!("A schedule entry you are assigned to has changed.\n\nWas {}:\n{}\nNow {}:\n{}", old.get_id(), describe(old), new.get_id(), describe(new)),
+                    )
+                }
+                _ => email_to(
+                    users,
+                    username,
+                    format!("You have been assigned to {}", new.get_id()),
+                    format!("You have been assigned to schedule entry {}.\n\n{}", new.get_id(), describe(new)),
+                ),
+            };
+            emails.extend(email);
+        }
+    }
+
+    if let Some(old) = old {
+        for username in before.iter().filter(|username| !after.contains(username)) {
+            let (subject, body) = match new {
+                Some(_) => (
+                    format!("You are no longer assigned to {}", old.get_id()),
+                    format!("You have been removed from schedule entry {}.\n\n{}", old.get_id(), describe(old)),
+                ),
+                None => (
+                    format!("Schedule entry {} has been deleted", old.get_id()),
+                    format!("A schedule entry you were assigned to has been deleted.\n\n{}", describe(old)),
+                ),
+            };
+            emails.extend(email_to(users, username, subject, body));
+        }
+    }
+    emails
+}
+
+// Reminders for entries starting within `before` seconds that have not had one, by the (ID, datetime) they are
+// remembered by. An entry that is moved is reminded about again
+fn due_reminders(
+    schedules: &[ScheduleEntry],
+    users: &[User],
+    now: Timestamp,
+    before: i64,
+    reminded: &HashSet<(String, Timestamp)>,
+) -> Vec<((String, Timestamp), Vec<Email>)> {
+    let mut due = Vec::new();
+    for entry in schedules {
+        let starts_in = entry.get_datetime().unix_seconds() - now.unix_seconds();
+        let key = (entry.get_id(), entry.get_datetime());
+        if starts_in <= 0 || starts_in > before || reminded.contains(&key) {
+            continue;
+        }
+
+        let emails = entry
+            .get_assignees()
+            .iter()
+            .filter_map(|username| {
+                email_to(
+                    users,
+                    username,
+                    format!("Reminder: {} starts at {}", entry.get_id(), entry.get_datetime().short()),
+                    format!("Schedule entry {} starts in {} minutes.\n\n{}", entry.get_id(), (starts_in + 59) / 60, describe(entry)),
+                )
+            })
+            .collect();
+        due.push((key, emails));
+    }
+    due
+}
+
+// Sends the schedule's emails, and remembers which entries have been reminded about
+pub struct Notifier {
+    config: Option<MailConfig>,
+    reminder_before: i64, // Seconds
+    reminded: Mutex<HashSet<(String, Timestamp)>>,
+    reminded_path: String,
+}
+
+impl Notifier {
+    pub async fn load(config: Option<MailConfig>, reminder_before: i64, reminded_path: &str) -> Self {
+        let mut reminded = HashSet::new();
+        for line in fs::read_to_string(reminded_path).await.unwrap_or_default().lines() {
+            if let Some((id, datetime)) = line.split_once(',') {
+                if let Ok(datetime) = Timestamp::from_str(datetime) {
+                    reminded.insert((id.to_string(), datetime));
+                }
+            }
+        }
+        Self { config, reminder_before, reminded: Mutex::new(reminded), reminded_path: reminded_path.to_string() }
+    }
+
+    // Set up from Rocket's config, with reminders sent reminder_minutes before each entry
+    pub async fn from_config(figment: &Figment, reminded_path: &str) -> Self {
+        let minutes = figment.extract_inner::<u32>("reminder_minutes").unwrap_or(DEFAULT_REMINDER_MINUTES);
+        Self::load(MailConfig::from_config(figment), minutes as i64 * 60, reminded_path).await
+    }
+
+    // Tell the assignees about a change to an entry, in the background so the request is not held up
+    pub fn schedule_changed(&self, users: &[User], old: Option<&ScheduleEntry>, new: Option<&ScheduleEntry>) {
+        let Some(config) = self.config.clone() else {
+            return;
+        };
+        let emails = schedule_emails(users, old, new);
+        if emails.is_empty() {
+            return;
+        }
+        rocket::tokio::spawn(async move {
+            if let Err(error) = send_mail(&config, &emails).await {
+                eprintln!("Cannot send schedule notifications: {}", error);
+            }
+        });
+    }
+
+    // Send the reminders that are due. Ones that cannot be sent are tried again next time, until the entry starts
+    pub async fn send_reminders(&self, site_data: &Mutex<SiteData>, now: Timestamp) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let due = {
+            let site_data = site_data.lock().await;
+            let reminded = self.reminded.lock().await;
+            due_reminders(site_data.schedules.as_slice().await, site_data.users.as_slice().await, now, self.reminder_before, &reminded)
+        };
+        if due.is_empty() {
+            return;
+        }
+
+        let mut reminded = self.reminded.lock().await;
+        for (key, emails) in due {
+            if !emails.is_empty() {
+                if let Err(error) = send_mail(config, &emails).await {
+                    eprintln!("Cannot send reminders for {}: {}", key.0, error);
+                    continue;
+                }
+            }
+            reminded.insert(key);
+        }
+        reminded.retain(|(_, datetime)| *datetime > now); // Entries that have started cannot be reminded about again
+
+        let mut lines: Vec<String> = reminded.iter().map(|(id, datetime)| format!("{},{}\n", id, datetime)).collect();
+        lines.sort();
+        if let Some(directory) = std::path::Path::new(&self.reminded_path).parent() {
+            fs::create_dir_all(directory).await.expect("Failed to create the notifications directory!");
+        }
+        fs::write(&self.reminded_path, lines.concat()).await.expect("Failed to save sent reminders!");
+    }
+
+    // Send reminders as they become due, until the server stops
+    pub async fn run(self: Arc<Self>, site_data: Arc<Mutex<SiteData>>, mut shutdown: Shutdown) {
+        loop {
+            self.send_reminders(&site_data, Timestamp::now()).await;
+            select! {
+                _ = sleep(REMINDER_CHECK) => {}
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+}
+
+// The email as sent after DATA, ending with the line holding only a dot
+fn message(from: &str, email: &Email, date: OffsetDateTime) -> String {
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        from,
+        email.to,
+        email.subject,
+        date.format(&Rfc2822).unwrap_or_default()
+    );
+    for line in email.body.lines() {
+        if line.starts_with('.') {
+            message.push('.'); // So the line does not end the message early
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    message
+}
+
+// Send a command, or nothing for the greeting, and read the reply, which may span several lines.
+// Fails unless the reply code is in the same class as expected, e.g. any 2xx for 250
+async fn exchange(stream: &mut BufReader<TcpStream>, command: Option<&str>, expected: u16) -> Result<(), String> {
+    if let Some(command) = command {
+        stream.get_mut().write_all(format!("{}\r\n", command).as_bytes()).await.map_err(|error| format!("Cannot send to the SMTP server: {}", error))?;
+    }
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.map_err(|error| format!("No reply from the SMTP server: {}", error))? == 0 {
+            return Err("The SMTP server closed the connection".to_string());
+        }
+        let code: u16 = line.get(..3).and_then(|code| code.parse().ok()).ok_or_else(|| format!("Unexpected reply '{}'", line.trim()))?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue; // More lines follow
+        }
+        if code / 100 != expected / 100 {
+            let sent = command.map(|command| command.lines().next().unwrap_or_default().to_string()).unwrap_or_else(|| "connecting".to_string());
+            return Err(format!("The SMTP server replied '{}' to {}", line.trim(), sent));
+        }
+        return Ok(());
+    }
+}
+
+// Send emails in one SMTP session, stopping at the first one the server refuses
+pub async fn send_mail(config: &MailConfig, emails: &[Email]) -> Result<(), String> {
+    let session = async {
+        let stream = TcpStream::connect(&config.server).await.map_err(|error| format!("Cannot connect to {}: {}", config.server, error))?;
+        let mut stream = BufReader::new(stream);
+        exchange(&mut stream, None, 220).await?;
+        exchange(&mut stream, Some("EHLO localhost"), 250).await?;
+        for email in emails {
+            exchange(&mut stream, Some(&format!("MAIL FROM:<{}>", config.from)), 250).await?;
+            exchange(&mut stream, Some(&format!("RCPT TO:<{}>", email.to)), 250).await?;
+            exchange(&mut stream, Some("DATA"), 354).await?;
+            exchange(&mut stream, Some(&message(&config.from, email, OffsetDateTime::now_utc())), 250).await?;
+        }
+        exchange(&mut stream, Some("QUIT"), 221).await
+    };
+    timeout(TIMEOUT, session).await.map_err(|_| format!("{} did not respond within {} s", config.server, TIMEOUT.as_secs()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{due_reminders, schedule_emails, send_mail, Email, MailConfig, Notifier};
+    use std::collections::HashSet;
+    use rocket::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use rocket::tokio::net::TcpListener;
+    use rocket::tokio::sync::Mutex;
+    use rocket::tokio::task::JoinHandle;
+    use crate::alerts::AlertStates;
+    use crate::keyed_vector::KeyedVector;
+    use crate::models::{Position, ScheduleEntry, SiteData, User};
+    use crate::stats::StatsCache;
+    use crate::timestamp::Timestamp;
+
+    fn user(username: &str, email: Option<&str>) -> User {
+        let mut user = User::new(username.to_string(), None, None, Position::Developer);
+        user.set_email(email.map(String::from));
+        user
+    }
+
+    fn users() -> Vec<User> {
+        vec![user("alice", Some("alice@example.com")), user("bob", Some("bob@example.com")), user("carol", None)]
+    }
+
+    fn entry(id: &str, seconds: i64, assignees: &str) -> ScheduleEntry {
+        ScheduleEntry::new(id.to_string(), Timestamp::from_unix_seconds(seconds).unwrap(), assignees.split_whitespace().map(String::from).collect(), "web".to_string(), "load".to_string())
+    }
+
+    fn sent(emails: &[Email]) -> Vec<(String, String)> {
+        emails.iter().map(|email| (email.to.clone(), email.subject.clone())).collect()
+    }
+
+    #[test]
+    fn test_schedule_emails() {
+        let users = users();
+        let first = entry("soak", 1_000_000, "alice carol nobody");
+        // carol has no email, and nobody is not a user
+        assert_eq!(sent(&schedule_emails(&users, None, Some(&first))), vec![("alice@example.com".to_string(), "You have been assigned to soak".to_string())]);
+        assert!(schedule_emails(&users, Some(&first), Some(&first)).is_empty());
+
+        let moved = entry("soak", 2_000_000, "alice bob");
+        assert_eq!(
+            sent(&schedule_emails(&users, Some(&first), Some(&moved))),
+            vec![
+                ("alice@example.com".to_string(), "Schedule entry soak has changed".to_string()),
+                ("bob@example.com".to_string(), "You have been assigned to soak".to_string()),
+            ]
+        );
+
+        let without_alice = entry("soak", 2_000_000, "bob");
+        let emails = schedule_emails(&users, Some(&moved), Some(&without_alice));
+        assert_eq!(sent(&emails)[1], ("alice@example.com".to_string(), "You are no longer assigned to soak".to_string()));
+
+        assert_eq!(sent(&schedule_emails(&users, Some(&without_alice), None)), vec![("bob@example.com".to_string(), "Schedule entry soak has been deleted".to_string())]);
+    }
+
+    #[test]
+    fn test_due_reminders() {
+        let users = users();
+        let now = Timestamp::from_unix_seconds(10_000).unwrap();
+        let schedules = [entry("started", 9_000, "alice"), entry("soon", 10_600, "alice bob"), entry("later", 20_000, "alice")];
+
+        let due = due_reminders(&schedules, &users, now, 3600, &HashSet::new());
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, ("soon".to_string(), Timestamp::from_unix_seconds(10_600).unwrap()));
+        assert_eq!(due[0].1.len(), 2);
+        assert!(due[0].1[0].body.starts_with("Schedule entry soon starts in 10 minutes."));
+
+        let reminded = HashSet::from([due[0].0.clone()]);
+        assert!(due_reminders(&schedules, &users, now, 3600, &reminded).is_empty());
+        // Moved, so reminded about again
+        assert_eq!(due_reminders(&[entry("soon", 10_700, "alice")], &users, now, 3600, &reminded).len(), 1);
+    }
+
+    // Accepts one session, refusing mail to refused@example.com, and returns each message it was sent
+    async fn stub() -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let handle = rocket::tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut messages = Vec::new();
+            stream.get_mut().write_all(b"220 stub ready\r\n").await.unwrap();
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let reply = match line.trim_end() {
+                    command if command.starts_with("EHLO") => "250-stub\r\n250 8BITMIME\r\n",
+                    "RCPT TO:<refused@example.com>" => "550 No such user\r\n",
+                    "DATA" => {
+                        stream.get_mut().write_all(b"354 Go ahead\r\n").await.unwrap();
+                        let mut message = String::new();
+                        loop {
+                            let mut line = String::new();
+                            stream.read_line(&mut line).await.unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            message.push_str(&line);
+                        }
+                        messages.push(message);
+                        "250 Queued\r\n"
+                    }
+                    "QUIT" => {
+                        stream.get_mut().write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => "250 OK\r\n",
+                };
+                stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+            }
+            messages
+        });
+        (server, handle)
+    }
+
+    fn email(to: &str, body: &str) -> Email {
+        Email { to: to.to_string(), subject: "Hello".to_string(), body: body.to_string() }
+    }
+
+    #[rocket::async_test]
+    async fn test_send_mail() {
+        let (server, handle) = stub().await;
+        let config = MailConfig::new(server, "tests@example.com".to_string());
+        send_mail(&config, &[email("alice@example.com", "First line\n.starts with a dot"), email("bob@example.com", "Hi")]).await.unwrap();
+
+        let messages = handle.await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("From: tests@example.com\r\nTo: alice@example.com\r\nSubject: Hello\r\n"), "{}", messages[0]);
+        assert!(messages[0].ends_with("\r\n\r\nFirst line\r\n..starts with a dot\r\n"), "{}", messages[0]);
+
+        let (server, _) = stub().await;
+        let config = MailConfig::new(server, "tests@example.com".to_string());
+        let error = send_mail(&config, &[email("refused@example.com", "Hi")]).await.unwrap_err();
+        assert_eq!(error, "The SMTP server replied '550 No such user' to RCPT TO:<refused@example.com>");
+    }
+
+    #[rocket::async_test]
+    async fn test_reminders_are_sent_once() {
+        let path = std::env::temp_dir().join(format!("notifications_{}/reminded", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().to_string();
+
+        let mut schedules = KeyedVector::new();
+        let _ = schedules.insert(entry("soon", 10_600, "alice")).await;
+        let mut users_list = KeyedVector::new();
+        for user in users() {
+            let _ = users_list.insert(user).await;
+        }
+        let site_data = Mutex::new(SiteData {
+            users: users_list,
+            servers: KeyedVector::new(),
+            schedules,
+            stats: StatsCache::default(),
+            alert_rules: KeyedVector::new(),
+            alerts: AlertStates::default(),
+            webhooks: KeyedVector::new(),
+        });
+        let now = Timestamp::from_unix_seconds(10_000).unwrap();
+
+        let (server, handle) = stub().await;
+        let notifier = Notifier::load(Some(MailConfig::new(server, "tests@example.com".to_string())), 3600, &path).await;
+        notifier.send_reminders(&site_data, now).await;
+        assert_eq!(handle.await.unwrap().len(), 1);
+
+        // Remembered after a restart, so there is no second session with the stub
+        let notifier = Notifier::load(Some(MailConfig::new("127.0.0.1:1".to_string(), String::new())), 3600, &path).await;
+        notifier.send_reminders(&site_data, now).await;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("soon,{}\n", Timestamp::from_unix_seconds(10_600).unwrap()));
+    }
+}
@@ -0,0 +1,258 @@
+use std::cmp::Ordering;
+use rocket::http::Header;
+use rocket::{FromForm, Responder};
+use crate::html::{encode_query_value, html, Html};
+
+pub const DEFAULT_PER_PAGE: u32 = 25;
+pub const MAX_PER_PAGE: u32 = 100;
+
+// Paging and sorting query parameters, shared by every table endpoint
+#[derive(FromForm, Default, Clone)]
+pub struct ListOptions {
+    pub page: Option<u32>, // Starts at 1
+    pub per_page: Option<u32>,
+    pub sort: Option<String>, // Field to sort by, each endpoint has its own list of allowed fields
+    pub order: Option<String>, // "asc" or "desc"
+}
+
+impl ListOptions {
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> u32 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+    }
+
+    // How many rows to skip to get to the current page
+    pub fn offset(&self) -> u32 {
+        (self.page() - 1).saturating_mul(self.per_page())
+    }
+
+    pub fn descending(&self) -> bool {
+        self.order.as_deref() == Some("desc")
+    }
+
+    // The requested sort field if it is allowed, else the first allowed field
+    pub fn sort_field<'a>(&self, allowed: &[&'a str]) -> &'a str {
+        allowed
+            .iter()
+            .find(|field| Some(**field) == self.sort.as_deref())
+            .copied()
+            .unwrap_or(allowed[0])
+    }
+
+    // Whether the rows can be shown in the order they are stored, without sorting
+    pub fn is_natural_order(&self) -> bool {
+        self.sort.is_none() && !self.descending()
+    }
+
+    // Apply the requested order to a comparison
+    pub fn order(&self, ordering: Ordering) -> Ordering {
+        if self.descending() {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+// A page of a table, with the total number of matching rows in the X-Total-Count header
+#[derive(Responder)]
+#[response(content_type = "html")]
+pub struct TablePage {
+    html: String,
+    total: Header<'static>,
+}
+
+// Builds the links and controls for one table, keeping the search and sorting between pages.
+// The table is wrapped in a .paged-table div, which the controls replace with the page they load
+pub struct Pager<'a> {
+    base_url: &'a str, // The endpoint, without a query string
+    search: Option<&'a str>,
+    options: &'a ListOptions,
+    sort_fields: &'a [&'a str],
+    total: u32,
+    parameters: Vec<(&'a str, &'a str)>, // Filters besides the search, kept between pages
+}
+
+impl<'a> Pager<'a> {
+    pub fn new(base_url: &'a str, search: Option<&'a str>, options: &'a ListOptions, sort_fields: &'a [&'a str], total: u32) -> Self {
+        Self {
+            base_url,
+            search,
+            options,
+            sort_fields,
+            total,
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &'a str, value: &'a str) -> Self {
+        self.parameters.push((name, value));
+        self
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.total.div_ceil(self.options.per_page()).max(1)
+    }
+
+    // URL of the endpoint for a page, sorted by a field in an order.
+    // With no field, the page stays in the order the rows are stored
+    fn url(&self, page: u32, sort: Option<&str>, descending: bool) -> String {
+        let mut url = format!("{}?page={}&per_page={}", self.base_url, page, self.options.per_page());
+        if let Some(sort) = sort {
+            url.push_str(&format!("&sort={}", encode_query_value(sort)));
+        }
+        if descending {
+            url.push_str("&order=desc");
+        }
+        if let Some(search) = self.search {
+            url.push_str(&format!("&search={}", encode_query_value(search)));
+        }
+        for (name, value) in &self.parameters {
+            url.push_str(&format!("&{}={}", name, encode_query_value(value)));
+        }
+        url
+    }
+
+    // Attributes that make an element load a URL in place of the table
+    fn load_attributes(url: &str) -> Html {
+        html!(
+            "hx-get=\"{}\" hx-target=\"closest .paged-table\" hx-swap=\"outerHTML\" style=\"cursor:pointer\"",
+            url
+        )
+    }
+
+    // A header cell, which sorts by its field when clicked if the field is sortable
+    pub fn header(&self, field: &str, label: &str) -> Html {
+        if !self.sort_fields.contains(&field) {
+            return html!("<th>{}</th>", label);
+        }
+
+        let current = self.options.sort_field(self.sort_fields) == field && self.options.sort.is_some();
+        let descending = current && !self.options.descending(); // Clicking the current field flips the order
+        let arrow = match (current, self.options.descending()) {
+            (true, false) => Html::raw(" &#9650;"),
+            (true, true) => Html::raw(" &#9660;"),
+            _ => Html::default(),
+        };
+
+        html!(
+            "<th {}>{}{}</th>",
+            Self::load_attributes(&self.url(1, Some(field), descending)),
+            label,
+            arrow
+        )
+    }
+
+    // Previous/next buttons and the row counts, shown under the table
+    pub fn controls(&self) -> Html {
+        let page = self.options.page();
+        let sort = self.options.sort.as_ref().map(|_| self.options.sort_field(self.sort_fields));
+        let descending = self.options.descending();
+        // A page past the end, e.g. after rows were deleted, shows an empty range
+        let (first_row, last_row) = if self.options.offset() >= self.total {
+            (0, 0)
+        } else {
+            (self.options.offset() + 1, self.options.offset().saturating_add(self.options.per_page()).min(self.total))
+        };
+
+        let mut output = html!("<div class=\"pagination\" style=\"display: flex; align-items: center; gap: 10px; margin: 10px 0;\">");
+
+        if page > 1 {
+            let previous = (page - 1).min(self.page_count()); // From past the end, back to the last page
+            output.push(html!("<button type=\"button\" {}>Previous</button>", Self::load_attributes(&self.url(previous, sort, descending))));
+        }
+
+        output.push(html!(
+            "<span>Showing {}-{} of {} (page {} of {})</span>",
+            first_row, last_row, self.total, page, self.page_count()
+        ));
+
+        if page < self.page_count() {
+            output.push(html!("<button type=\"button\" {}>Next</button>", Self::load_attributes(&self.url(page + 1, sort, descending))));
+        }
+
+        output.push(html!("</div>"));
+        output
+    }
+
+    // Wrap a rendered table with its controls, and attach the total count
+    pub fn finish(&self, table: Html) -> TablePage {
+        TablePage {
+            html: html!("<div class=\"paged-table\" style=\"width: 100%;\">{}{}</div>", table, self.controls()).into_string(),
+            total: Header::new("X-Total-Count", self.total.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListOptions, Pager};
+
+    fn options(page: Option<u32>, per_page: Option<u32>, sort: Option<&str>, order: Option<&str>) -> ListOptions {
+        ListOptions {
+            page,
+            per_page,
+            sort: sort.map(str::to_string),
+            order: order.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_defaults_and_limits() {
+        let default = ListOptions::default();
+        assert_eq!(default.page(), 1);
+        assert_eq!(default.per_page(), super::DEFAULT_PER_PAGE);
+        assert_eq!(default.offset(), 0);
+        assert!(default.is_natural_order());
+
+        let out_of_range = options(Some(0), Some(1000), None, None);
+        assert_eq!(out_of_range.page(), 1); // Pages start at 1
+        assert_eq!(out_of_range.per_page(), super::MAX_PER_PAGE);
+
+        assert_eq!(options(Some(3), Some(10), None, None).offset(), 20);
+    }
+
+    #[test]
+    fn test_sort_field() {
+        let fields = &["id", "name"];
+        assert_eq!(options(None, None, Some("name"), None).sort_field(fields), "name");
+        assert_eq!(options(None, None, Some("password"), None).sort_field(fields), "id"); // Not allowed, use the default
+        assert!(!options(None, None, None, Some("desc")).is_natural_order());
+    }
+
+    #[test]
+    fn test_controls() {
+        let list = options(Some(2), Some(10), None, None);
+        let pager = Pager::new("/api/list", Some("a b"), &list, &["id"], 25);
+
+        let controls = pager.controls().to_string();
+        assert_eq!(pager.page_count(), 3);
+        assert!(controls.contains("Showing 11-20 of 25 (page 2 of 3)"));
+        assert!(controls.contains("/api/list?page=1&amp;per_page=10&amp;search=a%20b")); // Previous keeps the search
+        assert!(controls.contains("/api/list?page=3&amp;per_page=10&amp;search=a%20b")); // Next
+
+        let filtered = Pager::new("/api/list", None, &list, &["id"], 25).with_parameter("assignee", "a&b");
+        assert!(filtered.controls().to_string().contains("/api/list?page=3&amp;per_page=10&amp;assignee=a%26b")); // Keeps the filter
+
+        let empty = Pager::new("/api/list", None, &list, &["id"], 0);
+        assert!(empty.controls().to_string().contains("Showing 0-0 of 0"));
+
+        let past_end = Pager::new("/api/list", None, &options(Some(5), Some(10), None, None), &["id"], 25).controls().to_string();
+        assert!(past_end.contains("Showing 0-0 of 25 (page 5 of 3)"), "{}", past_end);
+        assert!(past_end.contains("/api/list?page=3&amp;per_page=10")); // Previous goes back to the last page
+        assert!(!past_end.contains("Next"));
+    }
+
+    #[test]
+    fn test_header_flips_order() {
+        let list = options(None, None, Some("id"), None);
+        let pager = Pager::new("/api/list", None, &list, &["id", "name"], 1);
+
+        assert!(pager.header("id", "ID").to_string().contains("sort=id&amp;order=desc")); // Currently ascending, so flip
+        assert!(pager.header("name", "Name").to_string().contains("sort=name\""));
+        assert_eq!(pager.header("tests", "Tests").to_string(), "<th>Tests</th>"); // Not sortable
+    }
+}
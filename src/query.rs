@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use crate::timestamp::Timestamp;
+
+// Anything that can be searched with a Query, by looking up its fields by name
+pub trait Searchable {
+    const FIELDS: &'static [&'static str]; // Every field that can be searched
+    const DEFAULT_FIELDS: &'static [&'static str]; // Fields that words without a field name are matched against
+
+    fn field_value(&self, field: &str) -> Option<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Contains, // field:value
+    Equals, // field=value
+    Greater, // field>value
+    GreaterOrEqual, // field>=value
+    Less, // field<value
+    LessOrEqual, // field<=value
+}
+
+// One part of a query, such as name:web or ram>=4096
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub field: Option<String>, // None for a plain word, which is matched against the default fields
+    pub comparison: Comparison,
+    pub value: String,
+    pub negated: bool, // Written with a leading -, e.g. -position:Manager
+}
+
+// A parsed search, such as: name:web created_by:alice ram>=4096 comment:"cpu spike"
+// Every condition must match for an item to match
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub conditions: Vec<Condition>,
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    // Check every field name is valid for T, so typos are reported instead of matching nothing
+    pub fn check_fields<T: Searchable>(&self) -> Result<(), String> {
+        self.check_fields_with::<T>(&[])
+    }
+
+    // The same, also allowing fields only some items have, such as a test's custom metrics
+    pub fn check_fields_with<T: Searchable>(&self, extra_fields: &[String]) -> Result<(), String> {
+        for condition in &self.conditions {
+            if let Some(ref field) = condition.field {
+                if !T::FIELDS.contains(&field.as_str()) && !extra_fields.iter().any(|extra| extra.to_lowercase() == *field) {
+                    let mut fields: Vec<&str> = T::FIELDS.to_vec();
+                    fields.extend(extra_fields.iter().map(String::as_str));
+                    return Err(format!("Unknown field '{}', try one of: {}", field, fields.join(", ")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches<T: Searchable>(&self, item: &T) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(item))
+    }
+}
+
+impl Condition {
+    fn matches<T: Searchable>(&self, item: &T) -> bool {
+        let matched = match self.field {
+            Some(ref field) => item
+                .field_value(field)
+                .is_some_and(|value| compare(&value, self.comparison, &self.value)),
+            None => T::DEFAULT_FIELDS.iter().any(|field| {
+                item.field_value(field)
+                    .is_some_and(|value| compare(&value, self.comparison, &self.value))
+            }),
+        };
+        matched != self.negated
+    }
+}
+
+// Compare a field's value against the value in the query.
+// Numbers are compared as numbers, anything else as text. Text matching ignores case
+fn compare(field_value: &str, comparison: Comparison, wanted: &str) -> bool {
+    if comparison == Comparison::Contains {
+        return field_value.to_lowercase().contains(&wanted.to_lowercase());
+    }
+
+    // Numbers compare as numbers and timestamps by when they are, whatever their offset
+    let ordering = match (field_value.parse::<f64>(), wanted.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Less),
+        _ => match (Timestamp::from_str(field_value), Timestamp::from_str(wanted)) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => field_value.to_lowercase().cmp(&wanted.to_lowercase()),
+        },
+    };
+
+    match comparison {
+        Comparison::Equals => ordering == Ordering::Equal,
+        Comparison::Greater => ordering == Ordering::Greater,
+        Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        Comparison::Less => ordering == Ordering::Less,
+        Comparison::LessOrEqual => ordering != Ordering::Greater,
+        Comparison::Contains => unreachable!(),
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conditions = Vec::new();
+        for token in split_tokens(s)? {
+            conditions.push(parse_condition(&token)?);
+        }
+        Ok(Query { conditions })
+    }
+}
+
+// Split on whitespace, keeping anything in double quotes together
+fn split_tokens(s: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c); // Quotes are removed once the token is parsed
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("Missing closing quote in search".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn parse_condition(token: &str) -> Result<Condition, String> {
+    let (negated, token) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    // A field name is letters, numbers, - and _, like a metric name, directly followed by an operator.
+    // It starts with a letter or _, so times such as 12:30 stay plain words
+    let name_length = match token.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => token
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(token.len()),
+        _ => 0,
+    };
+    let (name, rest) = token.split_at(name_length);
+
+    let operators = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equals),
+        (":", Comparison::Contains),
+    ];
+
+    if !name.is_empty() {
+        for (operator, comparison) in operators {
+            if let Some(value) = rest.strip_prefix(operator) {
+                return Ok(Condition {
+                    field: Some(name.to_lowercase()),
+                    comparison,
+                    value: unquote(value),
+                    negated,
+                });
+            }
+        }
+    }
+
+    Ok(Condition { // A plain word
+        field: None,
+        comparison: Comparison::Contains,
+        value: unquote(token),
+        negated,
+    })
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Condition, Query, Searchable};
+
+    struct Item {
+        name: String,
+        ram: u32,
+        comment: String,
+    }
+
+    impl Searchable for Item {
+        const FIELDS: &'static [&'static str] = &["name", "ram", "comment"];
+        const DEFAULT_FIELDS: &'static [&'static str] = &["name", "comment"];
+
+        fn field_value(&self, field: &str) -> Option<String> {
+            match field {
+                "name" => Some(self.name.clone()),
+                "ram" => Some(self.ram.to_string()),
+                "comment" => Some(self.comment.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    fn item(name: &str, ram: u32, comment: &str) -> Item {
+        Item {
+            name: name.to_string(),
+            ram,
+            comment: comment.to_string(),
+        }
+    }
+
+    fn query(s: &str) -> Query {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = query("name:web ram>=4096 comment:\"cpu spike\" -old");
+        assert_eq!(parsed.conditions, vec![
+            Condition { field: Some("name".to_string()), comparison: Comparison::Contains, value: "web".to_string(), negated: false },
+            Condition { field: Some("ram".to_string()), comparison: Comparison::GreaterOrEqual, value: "4096".to_string(), negated: false },
+            Condition { field: Some("comment".to_string()), comparison: Comparison::Contains, value: "cpu spike".to_string(), negated: false },
+            Condition { field: None, comparison: Comparison::Contains, value: "old".to_string(), negated: true },
+        ]);
+        assert!(query("   ").is_empty());
+    }
+
+    #[test]
+    fn test_parse_plain_words() {
+        let parsed = query("12:30 \"a:b\"");
+        assert_eq!(parsed.conditions[0].field, None); // Not a field name, so a plain word
+        assert_eq!(parsed.conditions[0].value, "12:30");
+        assert_eq!(parsed.conditions[1].field, None); // Quoted, so a plain word
+        assert_eq!(parsed.conditions[1].value, "a:b");
+    }
+
+    #[test]
+    fn test_parse_field_with_numbers() {
+        let parsed = query("p95>=2 disk-io<5 -http_2xx=0");
+        let fields: Vec<_> = parsed.conditions.iter().map(|condition| condition.field.clone().unwrap()).collect();
+        assert_eq!(fields, vec!["p95", "disk-io", "http_2xx"]);
+        assert!(parsed.conditions[2].negated);
+        assert_eq!(query("2024-05-01").conditions[0].field, None);
+    }
+
+    #[test]
+    fn test_parse_unclosed_quote() {
+        assert!("comment:\"spike".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn test_check_fields() {
+        assert!(query("name:a ram>1 plain").check_fields::<Item>().is_ok());
+        let error = query("password:a").check_fields::<Item>().unwrap_err();
+        assert!(error.contains("password"));
+        assert!(error.contains("name, ram, comment"));
+
+        let extra = ["Latency".to_string()];
+        assert!(query("latency>1").check_fields_with::<Item>(&extra).is_ok()); // Names are not case sensitive
+        assert!(query("latency>1").check_fields::<Item>().is_err());
+        assert!(query("p95>1").check_fields_with::<Item>(&extra).unwrap_err().contains("name, ram, comment, Latency"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let web = item("web-01", 8192, "CPU spike at start");
+        let db = item("db-01", 2048, "");
+
+        assert!(query("name:WEB").matches(&web)); // Ignores case
+        assert!(!query("name:web").matches(&db));
+        assert!(query("ram>=4096").matches(&web));
+        assert!(!query("ram>=4096").matches(&db));
+        assert!(query("ram<4096 name:db").matches(&db));
+        assert!(query("ram=2048").matches(&db));
+        assert!(query("comment:\"cpu spike\"").matches(&web));
+        assert!(query("spike").matches(&web)); // Plain words search the default fields
+        assert!(!query("spike").matches(&db));
+        assert!(query("-spike").matches(&db));
+        assert!(query("").matches(&db)); // Empty query matches everything
+    }
+
+    #[test]
+    fn test_numbers_compare_numerically() {
+        let small = item("a", 900, "");
+        assert!(query("ram<1000").matches(&small)); // "900" > "1000" as text, but not as a number
+        assert!(query("name<b").matches(&small)); // Text compares as text
+    }
+
+    #[test]
+    fn test_timestamps_compare_chronologically() {
+        let morning = item("2024-05-01T10:00:00+05:00", 0, ""); // 05:00 UTC
+        assert!(query("name<2024-05-01T09:00:00Z").matches(&morning)); // Later as text, but earlier in time
+        assert!(query("name=2024-05-01T05:00").matches(&morning));
+        assert!(query("name>=2024-05-01").matches(&morning));
+        assert!(!query("name>2024-05-02").matches(&morning));
+    }
+}
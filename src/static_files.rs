@@ -0,0 +1,373 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::fs;
+use rocket::figment::Figment;
+use rocket::{catch, get, Request, State};
+
+// A file compiled into the binary: its path from public/, and its contents
+pub type EmbeddedFile = (&'static str, &'static [u8]);
+
+// Generated by build.rs, empty unless built with the embed-assets feature
+mod embedded {
+    use super::EmbeddedFile;
+    include!(concat!(env!("OUT_DIR"), "/embedded_files.rs"));
+}
+
+pub const PUBLIC_DIR: &str = "./public";
+pub const NOT_FOUND_PAGE: &str = "404.html";
+
+// Pages are checked on every load so changes show straight away, everything else can be cached for an hour
+const PAGE_CACHE_CONTROL: &str = "no-cache";
+const ASSET_CACHE_CONTROL: &str = "public, max-age=3600";
+
+// Serves the pages and assets in the public directory, from disk or from the binary
+pub struct StaticFiles {
+    directory: Option<PathBuf>, // Canonical, so every file served can be checked to be inside it. Served before embedded files
+    embedded: &'static [EmbeddedFile],
+}
+
+// A file loaded from the public directory
+pub struct Asset {
+    body: Cow<'static, [u8]>,
+    content_type: ContentType,
+    etag: String,
+    cache_control: &'static str,
+}
+
+pub enum StaticResponse {
+    Found(Asset),
+    NotModified(Asset), // The browser's copy is up to date, so only the headers are sent
+    NotFound(Option<Asset>), // The 404 page, if there is one
+}
+
+// The ETags in the If-None-Match header, which the browser sends with the ETag of its cached copy
+pub struct IfNoneMatch(Vec<String>);
+
+impl StaticFiles {
+    // Serve files from a directory, and any embedded files it does not have
+    pub fn new(directory: Option<&Path>, embedded: &'static [EmbeddedFile]) -> io::Result<Self> {
+        let directory = match directory {
+            Some(directory) => Some(std::fs::canonicalize(directory)?),
+            None => None,
+        };
+        Ok(Self {
+            directory,
+            embedded,
+        })
+    }
+
+    // Set up from Rocket's config (Rocket.toml or ROCKET_PUBLIC_DIR).
+    // Built with embed-assets, files are served from the binary, and public_dir is an optional override for local development.
+    // Otherwise, files are served from public_dir, which defaults to ./public
+    pub fn from_config(figment: &Figment) -> io::Result<Self> {
+        let public_dir = figment.extract_inner::<PathBuf>("public_dir").ok();
+
+        if cfg!(feature = "embed-assets") {
+            Self::new(public_dir.as_deref(), embedded::EMBEDDED_FILES)
+        } else {
+            Self::new(Some(public_dir.as_deref().unwrap_or(Path::new(PUBLIC_DIR))), &[])
+        }
+    }
+
+    // Map a request path to a file inside the directory.
+    // Returns None if the file does not exist or is outside the directory, e.g. through a symlink
+    async fn resolve(&self, relative: &Path) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+
+        let file = fs::canonicalize(directory.join(relative)).await.ok()?;
+        if !file.starts_with(directory) {
+            return None;
+        }
+        if !fs::metadata(&file).await.ok()?.is_file() {
+            return None;
+        }
+        Some(file)
+    }
+
+    // Find the embedded file with this path, which is looked up by name so cannot point anywhere else
+    fn find_embedded(&self, relative: &Path) -> Option<&'static [u8]> {
+        let mut parts = Vec::new();
+        for component in relative.components() {
+            parts.push(component.as_os_str().to_str()?);
+        }
+        let name = parts.join("/");
+
+        self.embedded
+            .iter()
+            .find(|(embedded_name, _)| *embedded_name == name)
+            .map(|(_, body)| *body)
+    }
+
+    // Paths without an extension are pages, so .html is added
+    pub async fn load(&self, path: &Path) -> Option<Asset> {
+        let mut relative = path.to_path_buf();
+        if relative.as_os_str().is_empty() {
+            return None;
+        }
+        if relative.extension().is_none() {
+            relative.set_extension("html");
+        }
+
+        if let Some(file) = self.resolve(&relative).await {
+            let body = fs::read(&file).await.ok()?;
+            return Some(Asset::new(&file, Cow::Owned(body)));
+        }
+
+        let body = self.find_embedded(&relative)?;
+        Some(Asset::new(&relative, Cow::Borrowed(body)))
+    }
+
+    // Load a file, or the 404 page if it cannot be served
+    pub async fn serve(&self, path: &Path, if_none_match: &IfNoneMatch) -> StaticResponse {
+        match self.load(path).await {
+            Some(asset) if if_none_match.matches(&asset.etag) => StaticResponse::NotModified(asset),
+            Some(asset) => StaticResponse::Found(asset),
+            None => StaticResponse::NotFound(self.load(Path::new(NOT_FOUND_PAGE)).await),
+        }
+    }
+}
+
+impl Asset {
+    fn new(path: &Path, body: Cow<'static, [u8]>) -> Self {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let content_type = ContentType::from_extension(extension).unwrap_or(ContentType::Binary);
+        let cache_control = if content_type == ContentType::HTML { PAGE_CACHE_CONTROL } else { ASSET_CACHE_CONTROL };
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+
+        Self {
+            etag: format!("\"{:016x}\"", hasher.finish()),
+            body,
+            content_type,
+            cache_control,
+        }
+    }
+}
+
+impl IfNoneMatch {
+    fn matches(&self, etag: &str) -> bool {
+        self.0.iter().any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let tags = request
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|header| header.split(','))
+            .map(|tag| tag.trim().to_string())
+            .collect();
+        Outcome::Success(IfNoneMatch(tags))
+    }
+}
+
+impl<'r> Responder<'r, 'static> for StaticResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        match self {
+            StaticResponse::Found(asset) => {
+                response
+                    .status(Status::Ok)
+                    .header(asset.content_type)
+                    .header(Header::new("ETag", asset.etag))
+                    .header(Header::new("Cache-Control", asset.cache_control))
+                    .sized_body(asset.body.len(), io::Cursor::new(asset.body));
+            }
+            StaticResponse::NotModified(asset) => {
+                response
+                    .status(Status::NotModified)
+                    .header(Header::new("ETag", asset.etag))
+                    .header(Header::new("Cache-Control", asset.cache_control));
+            }
+            StaticResponse::NotFound(Some(page)) => {
+                response
+                    .status(Status::NotFound)
+                    .header(page.content_type)
+                    .sized_body(page.body.len(), io::Cursor::new(page.body));
+            }
+            StaticResponse::NotFound(None) => {
+                let body = "404: Not Found";
+                response
+                    .status(Status::NotFound)
+                    .header(ContentType::Plain)
+                    .sized_body(body.len(), io::Cursor::new(body));
+            }
+        }
+        response.ok()
+    }
+}
+
+#[get("/<path..>")] // Ran when accessing anything not specified. Serves the public directory
+pub async fn catch_all(path: PathBuf, files: &State<StaticFiles>, if_none_match: IfNoneMatch) -> StaticResponse {
+    files.serve(&path, &if_none_match).await
+}
+
+#[catch(404)] // Anything else that is not found, e.g. paths Rocket refuses to turn into a PathBuf
+pub async fn not_found(request: &Request<'_>) -> StaticResponse {
+    let page = match request.rocket().state::<StaticFiles>() {
+        Some(files) => files.load(Path::new(NOT_FOUND_PAGE)).await,
+        None => None,
+    };
+    StaticResponse::NotFound(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{catch_all, not_found, EmbeddedFile, StaticFiles};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::{catchers, routes};
+    use std::path::{Path, PathBuf};
+
+    // A public directory with a page, a stylesheet and a 404 page, next to a secret file outside it
+    fn setup(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("static_files_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let public = base.join("public");
+        std::fs::create_dir_all(&public).unwrap();
+        std::fs::write(public.join("page.html"), "<h1>Page</h1>").unwrap();
+        std::fs::write(public.join("style.css"), "h1 {}").unwrap();
+        std::fs::write(public.join("404.html"), "<h1>Missing</h1>").unwrap();
+        std::fs::write(base.join("secret"), "secret").unwrap();
+        base
+    }
+
+    async fn client_for(files: StaticFiles) -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![catch_all])
+            .register("/", catchers![not_found])
+            .manage(files);
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    async fn client(base: &Path) -> Client {
+        client_for(StaticFiles::new(Some(&base.join("public")), &[]).unwrap()).await
+    }
+
+    const EMBEDDED: &[EmbeddedFile] = &[
+        ("404.html", b"<h1>Embedded missing</h1>"),
+        ("css/site.css", b"body {}"),
+        ("page.html", b"<h1>Embedded page</h1>"),
+    ];
+
+    #[rocket::async_test]
+    async fn test_serves_files_with_cache_headers() {
+        let base = setup("serves");
+        let client = client(&base).await;
+
+        let response = client.get("/page").dispatch().await; // .html is added
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("no-cache"));
+        assert!(response.headers().get_one("ETag").is_some());
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Page</h1>");
+
+        let response = client.get("/style.css").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::CSS));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=3600"));
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_not_modified() {
+        let base = setup("etag");
+        let client = client(&base).await;
+
+        let etag = client.get("/style.css").dispatch().await.headers().get_one("ETag").unwrap().to_string();
+        let response = client.get("/style.css").header(Header::new("If-None-Match", etag.clone())).dispatch().await;
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_bytes().await.unwrap_or_default().is_empty());
+
+        let response = client.get("/style.css").header(Header::new("If-None-Match", "\"other\"")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        std::fs::write(base.join("public/style.css"), "h2 {}").unwrap(); // Changing the file changes the ETag
+        let response = client.get("/style.css").header(Header::new("If-None-Match", etag)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_not_found_page() {
+        let base = setup("missing");
+        let client = client(&base).await;
+
+        let response = client.get("/nothing-here").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Missing</h1>");
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_refuses_paths_outside_root() {
+        let base = setup("outside");
+        let client = client(&base).await;
+
+        let response = client.get("/../secret").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Missing</h1>");
+
+        let response = client.get("/%2E%2E/secret").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        #[cfg(unix)]
+        {
+            // A symlink inside the public directory that points outside it
+            std::os::unix::fs::symlink(base.join("secret"), base.join("public/link.txt")).unwrap();
+            let response = client.get("/link.txt").dispatch().await;
+            assert_eq!(response.status(), Status::NotFound);
+        }
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_embedded_files() {
+        let client = client_for(StaticFiles::new(None, EMBEDDED).unwrap()).await;
+
+        let response = client.get("/page").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response.headers().get_one("ETag").is_some());
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Embedded page</h1>");
+
+        let response = client.get("/css/site.css").dispatch().await; // Files in subdirectories
+        assert_eq!(response.content_type(), Some(ContentType::CSS));
+        assert_eq!(response.into_string().await.unwrap(), "body {}");
+
+        let response = client.get("/secret").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Embedded missing</h1>");
+    }
+
+    #[rocket::async_test]
+    async fn test_override_directory() {
+        let base = setup("override");
+        let client = client_for(StaticFiles::new(Some(&base.join("public")), EMBEDDED).unwrap()).await;
+
+        // The directory is served first, so pages can be edited without rebuilding
+        let response = client.get("/page").dispatch().await;
+        assert_eq!(response.into_string().await.unwrap(), "<h1>Page</h1>");
+
+        // Anything not in the directory falls back to the embedded copy
+        let response = client.get("/css/site.css").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().await.unwrap(), "body {}");
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
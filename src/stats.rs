@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use crate::models::{DataPoint, Metric, Test};
+use crate::my_vector::{MyVector, SafePointer};
+
+// Summary statistics of one measurement over every data point in a test
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub count: u32,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64, // 95% of values are at or below this
+    pub stddev: f64, // Population standard deviation
+}
+
+// Statistics that can be updated one value at a time.
+// Values are kept sorted so the median and percentiles are a lookup, and the sums give the mean and standard deviation
+#[derive(Clone)]
+pub struct RunningStats {
+    sorted: MyVector<f64>,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+// RAM, CPU and custom metric statistics for one test
+#[derive(Clone)]
+pub struct TestStats {
+    pub ram: RunningStats,
+    pub cpu: RunningStats,
+    pub metrics: Vec<(Metric, RunningStats)>, // In the order of the test's metrics
+}
+
+// Statistics for each test that has been looked at, keyed by (server ID, test ID).
+// Kept up to date as data points are added, and dropped when a test's data changes in any other way
+#[derive(Default)]
+pub struct StatsCache {
+    tests: HashMap<(String, String), TestStats>,
+}
+
+impl RunningStats {
+    pub fn new() -> Self {
+        Self {
+            sorted: MyVector::new(),
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        }
+    }
+
+    pub async fn add(&mut self, value: f64) {
+        self.sorted.insert_sorted(value).await;
+        self.sum += value;
+        self.sum_of_squares += value * value;
+    }
+
+    // Add many values at once. They are sorted together and merged in, rather than each being inserted on its own
+    pub async fn add_all(&mut self, mut values: Vec<f64>) {
+        if values.is_empty() {
+            return;
+        }
+        values.sort_by(f64::total_cmp);
+        self.sum += values.iter().sum::<f64>();
+        self.sum_of_squares += values.iter().map(|value| value * value).sum::<f64>();
+
+        let existing = self.sorted.as_slice().await;
+        let mut merged = Vec::with_capacity(existing.len() + values.len());
+        let (mut i, mut j) = (0, 0);
+        while i < existing.len() && j < values.len() {
+            if existing[i].total_cmp(&values[j]).is_le() {
+                merged.push(existing[i]);
+                i += 1;
+            } else {
+                merged.push(values[j]);
+                j += 1;
+            }
+        }
+        merged.extend_from_slice(&existing[i..]);
+        merged.extend_from_slice(&values[j..]);
+        self.sorted = MyVector::from_sorted(merged).await;
+    }
+
+    pub async fn summary(&self) -> Option<Summary> {
+        let count = self.sorted.length;
+        if count == 0 {
+            return None;
+        }
+
+        let values = self.sorted.as_slice().await;
+        let mean = self.sum / count as f64;
+        let variance = (self.sum_of_squares / count as f64 - mean * mean).max(0.0); // Rounding can make it slightly negative
+
+        let middle = (count / 2) as usize;
+        let median = if count.is_multiple_of(2) {
+            (values[middle - 1] + values[middle]) / 2.0
+        } else {
+            values[middle]
+        };
+
+        // Nearest rank: the smallest value with at least 95% of values at or below it
+        let p95_rank = (count as f64 * 0.95).ceil() as usize;
+
+        Some(Summary {
+            count,
+            min: values[0],
+            max: values[count as usize - 1],
+            mean,
+            median,
+            p95: values[p95_rank.max(1) - 1],
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+impl TestStats {
+    pub fn new(metrics: &[Metric]) -> Self {
+        Self {
+            ram: RunningStats::new(),
+            cpu: RunningStats::new(),
+            metrics: metrics.iter().map(|metric| (metric.clone(), RunningStats::new())).collect(),
+        }
+    }
+
+    pub async fn from_test(test: &Test) -> Self {
+        let mut stats = Self::new(test.metrics.as_slice().await);
+        stats.add_all(test.data.as_slice().await).await;
+        stats
+    }
+
+    pub async fn add(&mut self, data_point: &DataPoint) {
+        self.ram.add(data_point.get_ram() as f64).await;
+        self.cpu.add(data_point.get_cpu() as f64).await;
+        for (metric, stats) in &mut self.metrics {
+            if let Some(value) = data_point.get_metric(&metric.get_name()) { // Not every data point records every metric
+                stats.add(value).await;
+            }
+        }
+    }
+
+    pub async fn add_all(&mut self, data_points: &[DataPoint]) {
+        self.ram.add_all(data_points.iter().map(|data_point| data_point.get_ram() as f64).collect()).await;
+        self.cpu.add_all(data_points.iter().map(|data_point| data_point.get_cpu() as f64).collect()).await;
+        for (metric, stats) in &mut self.metrics {
+            let name = metric.get_name();
+            stats.add_all(data_points.iter().filter_map(|data_point| data_point.get_metric(&name)).collect()).await;
+        }
+    }
+}
+
+impl StatsCache {
+    // The statistics for a test, working them out from its data if they are not cached
+    pub async fn get(&mut self, server_id: &str, test: &Test) -> &TestStats {
+        let key = (server_id.to_string(), test.get_id());
+        if !self.tests.contains_key(&key) {
+            let stats = TestStats::from_test(test).await;
+            self.tests.insert(key.clone(), stats);
+        }
+        &self.tests[&key]
+    }
+
+    // Update a test's statistics with a new data point, if they are cached
+    pub async fn add(&mut self, server_id: &str, test_id: &str, data_point: &DataPoint) {
+        if let Some(stats) = self.tests.get_mut(&(server_id.to_string(), test_id.to_string())) {
+            stats.add(data_point).await;
+        }
+    }
+
+    // Update a test's statistics with many new data points, if they are cached
+    pub async fn add_all(&mut self, server_id: &str, test_id: &str, data_points: &[DataPoint]) {
+        if let Some(stats) = self.tests.get_mut(&(server_id.to_string(), test_id.to_string())) {
+            stats.add_all(data_points).await;
+        }
+    }
+
+    // Forget a test's statistics, after a data point is changed or removed, its metrics change, or the test is renamed
+    pub fn invalidate(&mut self, server_id: &str, test_id: &str) {
+        self.tests.remove(&(server_id.to_string(), test_id.to_string()));
+    }
+
+    // Forget the statistics of every test on a server, after it is renamed or deleted
+    pub fn invalidate_server(&mut self, server_id: &str) {
+        self.tests.retain(|(server, _), _| server != server_id);
+    }
+}
+
+unsafe impl Send for SafePointer<f64> {}
+unsafe impl Sync for SafePointer<f64> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunningStats, StatsCache, Summary};
+    use std::collections::BTreeMap;
+    use crate::models::{DataPoint, Metric, MetricKind, Test};
+    use crate::timestamp::Timestamp;
+    use proptest::prelude::*;
+    use rocket::tokio;
+
+    async fn summary_of(values: &[u32]) -> Option<Summary> {
+        let mut stats = RunningStats::new();
+        for value in values {
+            stats.add(*value as f64).await;
+        }
+        stats.summary().await
+    }
+
+    #[tokio::test]
+    async fn test_summary() {
+        let summary = summary_of(&[2, 4, 4, 4, 5, 5, 7, 9]).await.unwrap();
+        assert_eq!(summary.count, 8);
+        assert_eq!(summary.min, 2.0);
+        assert_eq!(summary.max, 9.0);
+        assert_eq!(summary.mean, 5.0);
+        assert_eq!(summary.median, 4.5); // Average of the middle two
+        assert_eq!(summary.p95, 9.0);
+        assert_eq!(summary.stddev, 2.0);
+
+        assert!(summary_of(&[]).await.is_none());
+        assert_eq!(summary_of(&[7]).await.unwrap().median, 7.0);
+    }
+
+    #[tokio::test]
+    async fn test_p95() {
+        let values: Vec<u32> = (1..=100).rev().collect(); // Added out of order
+        assert_eq!(summary_of(&values).await.unwrap().p95, 95.0);
+        assert_eq!(summary_of(&[1, 2, 3]).await.unwrap().p95, 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_add_all_merges() {
+        let mut one_at_a_time = RunningStats::new();
+        for value in [5.0, 1.0, 9.0, 3.0, 3.0, 7.0] {
+            one_at_a_time.add(value).await;
+        }
+
+        let mut batches = RunningStats::new();
+        batches.add(3.0).await;
+        batches.add_all(vec![9.0, 1.0, 5.0]).await; // Merged around what is already there
+        batches.add_all(vec![7.0, 3.0]).await;
+        batches.add_all(Vec::new()).await;
+
+        assert_eq!(batches.sorted.as_slice().await, [1.0, 3.0, 3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(batches.summary().await, one_at_a_time.summary().await);
+    }
+
+    fn data_point(seconds: i64, ram: u32, cpu: u32, latency: Option<f64>) -> DataPoint {
+        let mut data_point = DataPoint::new(Timestamp::from_unix_seconds(seconds).unwrap(), ram, cpu);
+        data_point.set_metrics(latency.map(|latency| BTreeMap::from([("latency".to_string(), latency)])).unwrap_or_default());
+        data_point
+    }
+
+    #[tokio::test]
+    async fn test_cache_updates_incrementally() {
+        let mut test = Test::new("test".to_string());
+        assert!(test.metrics.insert(Metric::new("latency".to_string(), "ms".to_string(), MetricKind::Decimal)).await.is_ok());
+        test.data.push(data_point(1, 100, 10, Some(0.5))).await;
+        test.data.push(data_point(2, 300, 30, None)).await;
+
+        let mut cache = StatsCache::default();
+        let stats = cache.get("server", &test).await;
+        assert_eq!(stats.ram.summary().await.unwrap().mean, 200.0);
+        assert_eq!(stats.metrics[0].1.summary().await.unwrap().count, 1); // Only recorded once
+
+        // Adding to the cache is enough, the data is not read again
+        cache.add("server", "test", &data_point(3, 500, 50, Some(1.5))).await;
+        let stats = cache.get("server", &test).await;
+        assert_eq!(stats.ram.summary().await.unwrap().count, 3);
+        assert_eq!(stats.cpu.summary().await.unwrap().max, 50.0);
+        assert_eq!(stats.metrics[0].1.summary().await.unwrap().mean, 1.0);
+
+        cache.invalidate("server", "test"); // Worked out again from the data
+        assert_eq!(cache.get("server", &test).await.ram.summary().await.unwrap().count, 2);
+
+        cache.invalidate_server("server");
+        assert!(cache.tests.is_empty());
+    }
+
+    fn naive_summary(values: &[u32]) -> (f64, f64, f64, f64) {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        let mean = values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        (sorted[0] as f64, sorted[sorted.len() - 1] as f64, mean, variance.sqrt())
+    }
+
+    proptest! {
+        #[test]
+        fn matches_naive_calculation(values in prop::collection::vec(0u32..100_000, 1..60)) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let summary = runtime.block_on(summary_of(&values)).unwrap();
+            let (min, max, mean, stddev) = naive_summary(&values);
+
+            prop_assert_eq!(summary.min, min);
+            prop_assert_eq!(summary.max, max);
+            prop_assert!((summary.mean - mean).abs() < 1e-6);
+            prop_assert!((summary.stddev - stddev).abs() < 1e-3);
+            prop_assert!(summary.min <= summary.p95 && summary.p95 <= summary.max);
+        }
+    }
+}
@@ -0,0 +1,487 @@
+// The HTML fragments returned to htmx by the API.
+// Every value is escaped by html!, and every link is built with url so its parameters are encoded
+use crate::html::{html, url, Html};
+use crate::models::{AlertEvent, AlertRule, DataPoint, DeliveryAttempt, Metric, MetricKind, ScheduleEntry, Server, Test, User, Webhook};
+use crate::stats::Summary;
+
+pub const SEARCH_RESULTS_PER_GROUP: usize = 20;
+
+// A table row that goes to a page when clicked.
+// The link is percent-encoded by url, so it cannot contain a quote to break out of the JavaScript string
+fn link_row(link: &str, cells: Html) -> Html {
+    html!(
+        "<tr onclick=\"window.location.href='{}'\" style=\"cursor:pointer\">{}</tr>\n",
+        link,
+        cells
+    )
+}
+
+pub fn user_row(user: &User) -> Html {
+    let username = user.get_username();
+    link_row(
+        &url("/manage-user", &[("username", &username)]),
+        html!(
+            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            username,
+            user.get_forename(),
+            user.get_surname(),
+            user.get_position().to_string(),
+            user.get_email()
+        ),
+    )
+}
+
+// A server with its tests loaded, linking to page_path (the test list or the server manager)
+pub fn server_row(server: &Server, page_path: &str) -> Html {
+    link_row(
+        &url(page_path, &[("server_id", &server.get_id())]),
+        html!(
+            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            server.get_id(),
+            server.get_name(),
+            server.get_created_by(),
+            server.get_ram(),
+            server.get_cpu(),
+            server.tests.length
+        ),
+    )
+}
+
+pub fn test_row(server_id: &str, test: &Test, ram: &Option<Summary>, cpu: &Option<Summary>) -> Html {
+    link_row(
+        &url("/test-data", &[("server_id", server_id), ("test_id", &test.get_id())]),
+        html!(
+            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            test.get_id(),
+            test.data.length,
+            ram.as_ref().map(|ram| decimal(ram.mean)),
+            ram.as_ref().map(|ram| ram.p95),
+            cpu.as_ref().map(|cpu| decimal(cpu.mean)),
+            cpu.as_ref().map(|cpu| cpu.p95)
+        ),
+    )
+}
+
+// Picks one statistic out of a summary, as text
+type Statistic = fn(&Summary) -> String;
+
+// Summary statistics of a test, one column per value recorded (RAM, CPU, then each custom metric) and one row per statistic.
+// A column is None when none of the data points have that value
+pub fn stats_table(columns: &[(String, Option<Summary>)]) -> Html {
+    if columns.iter().all(|(_, summary)| summary.is_none()) {
+        return html!("<p>No data points yet.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>Statistic</th>");
+    for (label, _) in columns {
+        output.push(html!("<th>{}</th>", label));
+    }
+    output.push(html!("</tr>\n"));
+
+    let rows: [(&str, Statistic); 7] = [
+        ("Count", |summary| summary.count.to_string()),
+        ("Min", |summary| summary.min.to_string()),
+        ("Max", |summary| summary.max.to_string()),
+        ("Mean", |summary| decimal(summary.mean)),
+        ("Median", |summary| decimal(summary.median)),
+        ("95th percentile", |summary| summary.p95.to_string()),
+        ("Std. deviation", |summary| decimal(summary.stddev)),
+    ];
+    for (name, statistic) in rows {
+        output.push(html!("<tr><td>{}</td>", name));
+        for (_, summary) in columns {
+            output.push(html!("<td>{}</td>", summary.as_ref().map(statistic)));
+        }
+        output.push(html!("</tr>\n"));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+fn decimal(value: f64) -> String {
+    format!("{:.1}", value)
+}
+
+// A data point with a cell for each of the test's custom metrics, empty where it has no value
+pub fn data_point_row(server_id: &str, test_id: &str, metrics: &[Metric], data_point: &DataPoint) -> Html {
+    let time = data_point.get_time().to_string();
+    let mut cells = html!(
+        "<td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+        time,
+        data_point.get_ram(),
+        data_point.get_cpu(),
+        data_point.get_comment()
+    );
+    for metric in metrics {
+        cells.push(html!("<td>{}</td>", data_point.get_metric(&metric.get_name())));
+    }
+    link_row(
+        &url("/manage-datapoint", &[("server_id", server_id), ("test_id", test_id), ("time", &time)]),
+        cells,
+    )
+}
+
+// The custom metrics of a test, each with a button to remove it
+pub fn metrics_table(server_id: &str, test_id: &str, metrics: &[Metric]) -> Html {
+    if metrics.is_empty() {
+        return html!("<p>Only RAM and CPU are recorded.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>Name</th><th>Unit</th><th>Kind</th><th></th></tr>\n");
+    for metric in metrics {
+        let delete = url("/api/delete_metric", &[("server_id", server_id), ("test_id", test_id), ("name", &metric.get_name())]);
+        output.push(html!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td><button hx-delete=\"{}\" hx-target=\"#metrics-table\" hx-confirm=\"Remove this metric? Values already recorded are kept.\">Remove</button></td></tr>\n",
+            metric.get_name(),
+            metric.get_unit(),
+            metric.get_kind().to_string(),
+            delete
+        ));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+pub fn alert_rules_table(rules: &[AlertRule]) -> Html {
+    if rules.is_empty() {
+        return html!("<p>No alert rules.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>ID</th><th>Test</th><th>Condition</th><th></th></tr>\n");
+    for rule in rules {
+        let delete = url("/api/delete_alert_rule", &[("id", &rule.get_id())]);
+        output.push(html!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td><button hx-delete=\"{}\" hx-target=\"#alert-rules-table\" hx-confirm=\"Remove this alert rule? Alerts it already recorded are kept.\">Remove</button></td></tr>\n",
+            rule.get_id(),
+            rule.get_test_id().unwrap_or("Every test".to_string()),
+            rule.describe(),
+            delete
+        ));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+// Alerts that fired or resolved, newest first
+pub fn alert_events_table(events: &[AlertEvent]) -> Html {
+    if events.is_empty() {
+        return html!("<p>No alerts yet.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>Time</th><th>Test</th><th>Rule</th><th>Alert</th><th>Value</th><th>Threshold</th></tr>\n");
+    for event in events {
+        output.push(html!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            event.get_time().to_string(),
+            event.get_test_id(),
+            event.get_rule_id(),
+            event.get_change().to_string(),
+            event.get_value(),
+            event.get_limit()
+        ));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+// Webhooks, each with how many deliveries it has waiting
+pub fn webhooks_table(webhooks: &[(Webhook, usize)]) -> Html {
+    if webhooks.is_empty() {
+        return html!("<p>No webhooks.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>ID</th><th>URL</th><th>Events</th><th>Waiting</th><th></th></tr>\n");
+    for (webhook, pending) in webhooks {
+        let events: Vec<String> = webhook.get_events().iter().map(|event| event.to_string()).collect();
+        output.push(html!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><button hx-get=\"{}\" hx-target=\"#deliveries-table\">Deliveries</button> <button hx-delete=\"{}\" hx-target=\"#webhooks-table\" hx-confirm=\"Remove this webhook? Deliveries still waiting will not be sent.\">Remove</button></td></tr>\n",
+            webhook.get_id(),
+            webhook.get_url(),
+            events.join(", "),
+            *pending,
+            url(&format!("/api/get_webhook_deliveries/{}", webhook.get_id()), &[]),
+            url("/api/delete_webhook", &[("id", &webhook.get_id())])
+        ));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+// Attempts to send to a webhook, newest first
+pub fn delivery_attempts_table(attempts: &[DeliveryAttempt]) -> Html {
+    if attempts.is_empty() {
+        return html!("<p>Nothing has been sent yet.</p>");
+    }
+
+    let mut output = html!("<table>\n<tr><th>Time</th><th>Event</th><th>Delivery</th><th>Attempt</th><th>Status</th><th>Outcome</th><th>Message</th></tr>\n");
+    for attempt in attempts {
+        output.push(html!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            attempt.get_time().to_string(),
+            attempt.get_event().to_string(),
+            attempt.get_delivery_id(),
+            attempt.get_attempt(),
+            attempt.get_status().map(|status| status.to_string()),
+            attempt.get_outcome().to_string(),
+            attempt.get_message()
+        ));
+    }
+    output.push(html!("</table>"));
+    output
+}
+
+// A number input for each custom metric, filled in from the data point being edited
+pub fn metric_inputs(metrics: &[Metric], data_point: Option<&DataPoint>) -> Html {
+    let mut output = Html::default();
+    for metric in metrics {
+        let name = metric.get_name();
+        let step = match metric.get_kind() {
+            MetricKind::Integer => "1",
+            MetricKind::Decimal => "any",
+        };
+        output.push(html!(
+            "<div style=\"margin-bottom: 10px;\"><label for=\"metric-{}\">{}:</label>\n<input type=\"number\" id=\"metric-{}\" name=\"metrics[{}]\" step=\"{}\" value=\"{}\"></div>\n",
+            name,
+            metric.label(),
+            name,
+            name,
+            step,
+            data_point.and_then(|data_point| data_point.get_metric(&name))
+        ));
+    }
+    output
+}
+
+// The test links to its data, without also following the row's link. Entries saved before they had a server have no link
+pub fn schedule_row(schedule: &ScheduleEntry) -> Html {
+    let (server_id, test_id) = (schedule.get_server_id(), schedule.get_test_id());
+    let test = match server_id.as_str() {
+        "" => html!("{}", test_id),
+        _ => html!(
+            "<a href=\"{}\" onclick=\"event.stopPropagation()\">{}</a>",
+            url("/test-data", &[("server_id", &server_id), ("test_id", &test_id)]),
+            test_id
+        ),
+    };
+    link_row(
+        &url("/manage-scheduleentry", &[("id", &schedule.get_id())]),
+        html!(
+            "<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            schedule.get_id(),
+            schedule.get_datetime(),
+            schedule.get_assignees().join(", "),
+            server_id,
+            test
+        ),
+    )
+}
+
+// An entry's assignees, each with a button to remove them. Usernames that are no longer users are still listed
+pub fn assignees_list(schedule: &ScheduleEntry, users: &[User]) -> Html {
+    let assignees = schedule.get_assignees();
+    if assignees.is_empty() {
+        return html!("<p>Nobody is assigned.</p>");
+    }
+
+    let mut output = html!("<ul>\n");
+    for username in &assignees {
+        let name = users
+            .iter()
+            .find(|user| &user.get_username() == username)
+            .map(|user| format!("{} {}", user.get_forename().unwrap_or_default(), user.get_surname().unwrap_or_default()).trim().to_string())
+            .unwrap_or_default();
+        let label = if name.is_empty() { username.clone() } else { format!("{} ({})", username, name) };
+        output.push(html!(
+            "<li>{} <button hx-delete=\"{}\" hx-target=\"#assignees-list\">Remove</button></li>\n",
+            label,
+            url("/api/remove_assignee", &[("schedule_entry_id", &schedule.get_id()), ("username", username)])
+        ));
+    }
+    output.push(html!("</ul>"));
+    output
+}
+
+// Every user as an option, for choosing assignees
+pub fn user_options(users: &[User]) -> Html {
+    let mut output = Html::default();
+    for user in users {
+        output.push(html!("<option value=\"{}\">{}</option>\n", user.get_username(), user.get_username()));
+    }
+    output
+}
+
+// One group of global search results as a list of (link, label), e.g. every matching user
+pub fn search_group(title: &str, results: &[(String, String)]) -> Html {
+    let mut output = html!("<h2>{} ({})</h2>", title, results.len());
+
+    if results.is_empty() {
+        output.push(html!("<p>No matches</p>"));
+        return output;
+    }
+
+    output.push(html!("<ul>"));
+    for (link, label) in results.iter().take(SEARCH_RESULTS_PER_GROUP) {
+        output.push(html!("<li><a href=\"{}\">{}</a></li>", link, label));
+    }
+    if results.len() > SEARCH_RESULTS_PER_GROUP {
+        output.push(html!("<li>and {} more</li>", results.len() - SEARCH_RESULTS_PER_GROUP));
+    }
+    output.push(html!("</ul>"));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assignees_list, data_point_row, metric_inputs, metrics_table, schedule_row, search_group, server_row, stats_table, test_row, user_options, user_row};
+    use std::collections::BTreeMap;
+    use crate::html::{url, Html};
+    use crate::models::{DataPoint, Metric, MetricKind, Position, ScheduleEntry, Server, Test, User};
+    use crate::stats::Summary;
+    use crate::timestamp::Timestamp;
+    use std::str::FromStr;
+
+    // Closes the attribute and the JavaScript string, then opens a script
+    const PAYLOAD: &str = "x'\"><script>alert(1)</script>";
+    const ESCAPED: &str = "x&#39;&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;";
+    const ENCODED: &str = "x%27%22%3E%3Cscript%3Ealert%281%29%3C%2Fscript%3E";
+
+    fn payload() -> String {
+        PAYLOAD.to_string()
+    }
+
+    fn time(time: &str) -> Timestamp {
+        Timestamp::from_str(time).unwrap()
+    }
+
+    // The payload must only ever appear escaped or encoded
+    fn assert_safe(output: &Html) {
+        let output = output.to_string();
+        assert!(!output.contains("<script>"), "{}", output);
+        assert!(!output.contains("x'"), "{}", output);
+        assert!(!output.contains("x\""), "{}", output);
+        assert!(output.contains(ESCAPED), "{}", output);
+    }
+
+    #[test]
+    fn test_user_row() {
+        let user = User::new(payload(), Some(payload()), Some(payload()), Position::Developer);
+        let output = user_row(&user);
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/manage-user?username={}", ENCODED)));
+    }
+
+    #[test]
+    fn test_server_row() {
+        let server = Server::new(payload(), payload(), payload(), 1, 2);
+        let output = server_row(&server, "/manage-server");
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/manage-server?server_id={}", ENCODED)));
+    }
+
+    #[test]
+    fn test_test_row() {
+        let output = test_row(PAYLOAD, &Test::new(payload()), &None, &None);
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/test-data?server_id={}&amp;test_id={}", ENCODED, ENCODED)));
+    }
+
+    #[test]
+    fn test_stats_table() {
+        let summary = Summary { count: 3, min: 1.0, max: 9.0, mean: 13.0 / 3.0, median: 3.0, p95: 9.0, stddev: 3.4 };
+        let columns = [
+            ("RAM (MB)".to_string(), Some(summary.clone())),
+            ("CPU (%)".to_string(), Some(summary)),
+            (payload(), None), // A metric with no values yet
+        ];
+        let output = stats_table(&columns).to_string();
+        assert!(output.contains("<tr><td>Mean</td><td>4.3</td><td>4.3</td><td></td></tr>"), "{}", output);
+        assert!(output.contains("<tr><td>95th percentile</td><td>9</td><td>9</td><td></td></tr>"), "{}", output);
+        assert!(output.contains(&format!("<th>{}</th>", ESCAPED)), "{}", output);
+
+        assert_eq!(stats_table(&[("RAM (MB)".to_string(), None)]).to_string(), "<p>No data points yet.</p>");
+    }
+
+    #[test]
+    fn test_data_point_row() {
+        let mut data_point = DataPoint::new(time("2024-05-01T14:30:00+01:00"), 1, 2);
+        data_point.set_comment(Some(payload()));
+        let output = data_point_row(PAYLOAD, PAYLOAD, &[], &data_point);
+        assert_safe(&output);
+        assert!(output.to_string().contains("&amp;time=2024-05-01T14%3A30%3A00%2B01%3A00")); // The + must not become a space
+    }
+
+    #[test]
+    fn test_data_point_row_without_comment() {
+        let output = data_point_row("server", "test", &[], &DataPoint::new(time("2024-05-01T12:00:00Z"), 1, 2));
+        assert!(output.to_string().ends_with("<td>2024-05-01T12:00:00Z</td><td>1</td><td>2</td><td></td></tr>\n"));
+    }
+
+    fn metrics() -> Vec<Metric> {
+        vec![
+            Metric::new("errors".to_string(), String::new(), MetricKind::Integer),
+            Metric::new("latency".to_string(), "ms".to_string(), MetricKind::Decimal),
+        ]
+    }
+
+    #[test]
+    fn test_data_point_row_with_metrics() {
+        let mut data_point = DataPoint::new(time("2024-05-01T12:00:00Z"), 1, 2);
+        data_point.set_metrics(BTreeMap::from([("latency".to_string(), 12.5)]));
+        let output = data_point_row("server", "test", &metrics(), &data_point);
+        assert!(output.to_string().ends_with("<td></td><td></td><td>12.5</td></tr>\n"), "{}", output); // No comment, no errors
+    }
+
+    #[test]
+    fn test_metrics_table() {
+        let metric = Metric::new("latency".to_string(), payload(), MetricKind::Decimal);
+        let output = metrics_table(PAYLOAD, PAYLOAD, &[metric]);
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/api/delete_metric?server_id={}&amp;test_id={}&amp;name=latency", ENCODED, ENCODED)));
+
+        assert_eq!(metrics_table("server", "test", &[]).to_string(), "<p>Only RAM and CPU are recorded.</p>");
+    }
+
+    #[test]
+    fn test_metric_inputs() {
+        let mut data_point = DataPoint::new(time("2024-05-01T12:00:00Z"), 1, 2);
+        data_point.set_metrics(BTreeMap::from([("errors".to_string(), 3.0)]));
+        let output = metric_inputs(&metrics(), Some(&data_point)).to_string();
+        assert!(output.contains("name=\"metrics[errors]\" step=\"1\" value=\"3\""), "{}", output);
+        assert!(output.contains("<label for=\"metric-latency\">latency (ms):</label>"), "{}", output);
+        assert!(output.contains("name=\"metrics[latency]\" step=\"any\" value=\"\""), "{}", output);
+    }
+
+    #[test]
+    fn test_schedule_row() {
+        let schedule = ScheduleEntry::new(payload(), time("2024-05-01"), vec![payload()], payload(), payload());
+        let output = schedule_row(&schedule);
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/manage-scheduleentry?id={}", ENCODED)));
+        assert!(output.to_string().contains(&format!("/test-data?server_id={}&amp;test_id={}", ENCODED, ENCODED)));
+
+        let old = ScheduleEntry::new("soak".to_string(), time("2024-05-01"), vec![], String::new(), "load".to_string());
+        assert!(!schedule_row(&old).to_string().contains("/test-data"));
+    }
+
+    #[test]
+    fn test_assignees_list() {
+        let schedule = ScheduleEntry::new("soak".to_string(), time("2024-05-01"), vec![payload(), "gone".to_string()], payload(), payload());
+        let users = [User::new(payload(), Some(payload()), None, Position::Developer)];
+        let output = assignees_list(&schedule, &users);
+        assert_safe(&output);
+        assert!(output.to_string().contains(&format!("/api/remove_assignee?schedule_entry_id=soak&amp;username={}", ENCODED)));
+        assert!(output.to_string().contains("<li>gone <button"));
+        assert_safe(&user_options(&users));
+    }
+
+    #[test]
+    fn test_search_group() {
+        let results = vec![(url("/manage-user", &[("username", PAYLOAD)]), payload())];
+        let output = search_group(PAYLOAD, &results);
+        assert_safe(&output);
+
+        let many: Vec<(String, String)> = (0..25).map(|i| (String::new(), i.to_string())).collect();
+        assert!(search_group("Users", &many).to_string().contains("and 5 more"));
+        assert!(search_group("Users", &[]).to_string().contains("No matches"));
+    }
+}
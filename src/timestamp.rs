@@ -0,0 +1,135 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use rocket::form::{self, FromFormField, ValueField};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+use crate::html::{escape, ToHtml};
+
+// A point in time with the timezone offset it was given in, stored and shown as RFC 3339,
+// e.g. 2024-05-01T14:30:00+01:00. Compares by the instant, so the same moment in two offsets is equal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(OffsetDateTime);
+
+pub const EXAMPLE: &str = "2024-05-01T14:30:00Z";
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(OffsetDateTime::now_utc())
+    }
+
+    pub fn from_unix_seconds(seconds: i64) -> Option<Self> {
+        OffsetDateTime::from_unix_timestamp(seconds).ok().map(Self)
+    }
+
+    pub fn unix_seconds(&self) -> i64 {
+        self.0.unix_timestamp()
+    }
+
+    // Shorter form for labels, in the timestamp's own offset, e.g. 2024-05-01 14:30
+    pub fn short(&self) -> String {
+        let format = format_description!("[year]-[month]-[day] [hour]:[minute]");
+        self.0.format(&format).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0.format(&Rfc3339) {
+            Ok(formatted) => write!(f, "{}", formatted),
+            Err(_) => Err(std::fmt::Error), // Only years outside 0 to 9999, which cannot be parsed in
+        }
+    }
+}
+
+// Accepts RFC 3339, and without an offset, which is taken as UTC:
+// a date and time separated by T or a space, with or without seconds (as sent by datetime-local inputs), or just a date
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(timestamp) = OffsetDateTime::parse(s, &Rfc3339) {
+            return Ok(Self(timestamp));
+        }
+
+        let without_offset = [
+            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond]"), // As test runners write them
+            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+            format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+            format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+            format_description!("[year]-[month]-[day] [hour]:[minute]"),
+        ];
+        for format in without_offset {
+            if let Ok(timestamp) = PrimitiveDateTime::parse(s, &format) {
+                return Ok(Self(timestamp.assume_utc()));
+            }
+        }
+        if let Ok(date) = Date::parse(s, &format_description!("[year]-[month]-[day]")) {
+            return Ok(Self(date.midnight().assume_utc()));
+        }
+
+        Err(format!("'{}' is not a date and time, e.g. {}", s, EXAMPLE))
+    }
+}
+
+impl<'v> FromFormField<'v> for Timestamp {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        if field.value.trim().is_empty() {
+            return Err(form::Error::validation("cannot be empty").into());
+        }
+        Timestamp::from_str(field.value).map_err(|error| form::Error::validation(error).into())
+    }
+}
+
+impl ToHtml for Timestamp {
+    fn to_html(&self) -> String {
+        escape(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+    use std::str::FromStr;
+
+    fn parse(s: &str) -> Timestamp {
+        Timestamp::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_rfc_3339() {
+        assert_eq!(parse("2024-05-01T14:30:00+01:00").to_string(), "2024-05-01T14:30:00+01:00"); // Keeps its offset
+        assert_eq!(parse("2024-05-01T14:30:00.5Z").to_string(), "2024-05-01T14:30:00.5Z");
+        assert_eq!(parse("2024-05-01T14:30:00+01:00"), parse("2024-05-01T13:30:00Z")); // The same instant
+    }
+
+    #[test]
+    fn test_without_offset_is_utc() {
+        assert_eq!(parse("2024-05-01T14:30").to_string(), "2024-05-01T14:30:00Z");
+        assert_eq!(parse("2024-05-01 14:30:15").to_string(), "2024-05-01T14:30:15Z");
+        assert_eq!(parse("2024-05-01T14:30:15.250").to_string(), "2024-05-01T14:30:15.25Z");
+        assert_eq!(parse("2024-05-01").to_string(), "2024-05-01T00:00:00Z");
+        assert_eq!(parse(" 2024-05-01 ").short(), "2024-05-01 00:00");
+    }
+
+    #[test]
+    fn test_invalid() {
+        for invalid in ["", "test", "Date", "12:00", "2024-13-01", "2024-02-30T10:00", "5"] {
+            assert!(Timestamp::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_chronological_order() {
+        // Later by the clock but earlier in time, which comparing the text would get wrong
+        assert!(parse("2024-05-01T10:00:00+05:00") < parse("2024-05-01T09:00:00Z"));
+        assert!(parse("2023-12-31T23:59:59Z") < parse("2024-01-01"));
+    }
+
+    #[test]
+    fn test_unix_seconds() {
+        assert_eq!(parse("1970-01-01T00:01:00Z").unix_seconds(), 60);
+        assert_eq!(Timestamp::from_unix_seconds(60), Some(parse("1970-01-01T00:01:00Z")));
+    }
+}
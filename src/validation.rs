@@ -0,0 +1,198 @@
+// Rules for form fields, used with #[field(validate = ...)] on the form structs in api.rs.
+// A form that breaks any rule is rejected with one "field: message" line per problem,
+// which the pages in public/ show next to each field (see form-errors.js)
+use std::borrow::Cow;
+use rocket::form::{self, Context, Contextual, Error};
+use rocket::form::error::ErrorKind;
+use rocket::http::Status;
+
+pub const MAX_ID_LENGTH: usize = 64;
+pub const MAX_NAME_LENGTH: usize = 100;
+pub const MAX_COMMENT_LENGTH: usize = 500;
+pub const MAX_UNIT_LENGTH: usize = 20;
+pub const MAX_URL_LENGTH: usize = 2000;
+pub const MAX_EMAIL_LENGTH: usize = 254;
+// Numeric limits are isize, as that is what Rocket's range validator compares against
+pub const MAX_RAM: isize = 16_777_216; // MB, 16 TB
+pub const MAX_CPU_CORES: isize = 1024;
+pub const MAX_CPU_PERCENT: isize = 100;
+pub const MAX_ALERT_DURATION: isize = 604_800; // Seconds, a week
+
+// IDs and usernames are used in URLs and as file and directory names under ./data,
+// so only allow characters that cannot escape the directory or need encoding
+pub fn identifier<'v>(value: &str) -> form::Result<'v, ()> {
+    if value.is_empty() {
+        return Err(Error::validation("cannot be empty").into());
+    }
+    if value.chars().count() > MAX_ID_LENGTH {
+        return Err(Error::validation(format!("cannot be longer than {} characters", MAX_ID_LENGTH)).into());
+    }
+    if !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::validation("may only contain letters, numbers, - and _").into());
+    }
+    Ok(())
+}
+
+// Free text, which is stored in comma-separated files, so cannot contain commas or new lines
+pub fn text<'v>(value: &str, required: bool, max_length: usize) -> form::Result<'v, ()> {
+    check_text(value, required, max_length).map_err(|message| Error::validation(message).into())
+}
+
+// The rule behind text, for values that do not come from a form
+pub fn check_text(value: &str, required: bool, max_length: usize) -> Result<(), String> {
+    if required && value.trim().is_empty() {
+        return Err("cannot be empty".to_string());
+    }
+    if value.chars().count() > max_length {
+        return Err(format!("cannot be longer than {} characters", max_length));
+    }
+    if value.contains([',', '\n', '\r']) {
+        return Err("cannot contain commas or new lines".to_string());
+    }
+    Ok(())
+}
+
+// An optional email address. Only the basic shape is checked, as it ends up in the headers of mail sent to it
+pub fn email_address<'v>(value: &str) -> form::Result<'v, ()> {
+    check_email(value).map_err(|message| Error::validation(message).into())
+}
+
+fn check_email(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    if value.chars().count() > MAX_EMAIL_LENGTH {
+        return Err(format!("cannot be longer than {} characters", MAX_EMAIL_LENGTH));
+    }
+    if value.contains(|c: char| c.is_whitespace() || c.is_control() || ",;:<>()[]\\\"".contains(c)) {
+        return Err("cannot contain spaces or any of , ; : < > ( ) [ ] \\ \"".to_string());
+    }
+    match value.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() && !domain.contains('@') => Ok(()),
+        _ => Err("must look like name@example.com".to_string()),
+    }
+}
+
+// A whole number from 0 to max, like range(0..=max) on a form field
+pub fn check_number(value: &str, max: isize) -> Result<u32, String> {
+    match value.trim().parse::<u32>() {
+        Ok(number) if number as isize <= max => Ok(number),
+        Ok(_) => Err(format!("value must be between 0 and {}", max)), // Worded as Rocket words it
+        Err(_) => Err("must be a whole number".to_string()),
+    }
+}
+
+// Describe every error in a form, one "field: message" line each
+pub fn describe_errors(context: &Context<'_>) -> String {
+    let mut lines = Vec::new();
+    for error in context.errors() {
+        let message: Cow<str> = match error.kind {
+            ErrorKind::Int(_) => "must be a whole number".into(),
+            ErrorKind::Missing => "is required".into(),
+            ref kind => kind.to_string().into(),
+        };
+        match error.name {
+            Some(ref name) => lines.push(format!("{}: {}", name, message)),
+            None => lines.push(message.into_owned()),
+        }
+    }
+    lines.join("\n")
+}
+
+// The form's values if every field is valid, else a 422 describing every problem
+pub fn validated<'a, T>(form: &'a Contextual<'_, T>) -> Result<&'a T, (Status, String)> {
+    match form.value {
+        Some(ref value) => Ok(value),
+        None => Err((Status::UnprocessableEntity, describe_errors(&form.context))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_number, email_address, identifier, text, validated, MAX_ID_LENGTH};
+    use rocket::form::{Contextual, Form, FromForm};
+    use rocket::http::Status;
+
+    #[derive(FromForm, Debug)]
+    struct Example {
+        #[field(validate = identifier())]
+        id: String,
+        #[field(validate = text(false, 10))]
+        note: String,
+        #[field(validate = range(1..=100))]
+        amount: u32,
+    }
+
+    fn parse(body: &str) -> Result<(String, String, u32), (Status, String)> {
+        let form: Contextual<Example> = Form::parse(body).unwrap();
+        validated(&form).map(|example| (example.id.clone(), example.note.clone(), example.amount))
+    }
+
+    #[test]
+    fn test_id() {
+        assert!(identifier("server_01-a").is_ok());
+        assert!(identifier("").is_err());
+        assert!(identifier("../etc").is_err()); // Path traversal
+        assert!(identifier("a/b").is_err());
+        assert!(identifier("a b").is_err());
+        assert!(identifier(&"a".repeat(MAX_ID_LENGTH)).is_ok());
+        assert!(identifier(&"a".repeat(MAX_ID_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_text() {
+        assert!(text("", false, 10).is_ok());
+        assert!(text("  ", true, 10).is_err());
+        assert!(text("a,b", false, 10).is_err()); // Would split the stored line
+        assert!(text("a\nb", false, 10).is_err());
+        assert!(text("abcdefghijk", false, 10).is_err());
+        assert!(text("<b>\"quoted\"</b>", false, 20).is_ok()); // Escaped when shown, so allowed
+    }
+
+    #[test]
+    fn test_email_address() {
+        assert!(email_address("").is_ok()); // Optional
+        assert!(email_address("alice.smith+tests@example.com").is_ok());
+        assert!(email_address("alice").is_err());
+        assert!(email_address("@example.com").is_err());
+        assert!(email_address("alice@").is_err());
+        assert!(email_address("a@b@c").is_err());
+        assert!(email_address("alice@example.com\r\nBcc: eve@example.com").is_err()); // Would add a header
+        assert!(email_address("Alice <alice@example.com>").is_err());
+    }
+
+    #[test]
+    fn test_check_number() {
+        assert_eq!(check_number(" 42 ", 100), Ok(42));
+        assert_eq!(check_number("101", 100), Err("value must be between 0 and 100".to_string()));
+        assert_eq!(check_number("-1", 100), Err("must be a whole number".to_string()));
+        assert_eq!(check_number("1.5", 100), Err("must be a whole number".to_string()));
+    }
+
+    #[test]
+    fn test_valid_form() {
+        assert_eq!(parse("id=abc&note=hi&amount=5"), Ok(("abc".to_string(), "hi".to_string(), 5)));
+    }
+
+    #[test]
+    fn test_every_error_is_described() {
+        let (status, message) = parse("id=../x&note=a,b&amount=-1").unwrap_err();
+        assert_eq!(status, Status::UnprocessableEntity);
+
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", message);
+        assert!(lines.contains(&"id: may only contain letters, numbers, - and _"));
+        assert!(lines.contains(&"note: cannot contain commas or new lines"));
+        assert!(lines.contains(&"amount: must be a whole number")); // Not silently zeroed
+    }
+
+    #[test]
+    fn test_range_and_missing() {
+        let (_, message) = parse("id=a&note=&amount=1000").unwrap_err();
+        assert_eq!(message, "amount: value must be between 1 and 100");
+
+        let (_, message) = parse("note=").unwrap_err();
+        assert!(message.contains("id: is required"), "{}", message);
+        assert!(message.contains("amount: is required"), "{}", message);
+    }
+}
@@ -0,0 +1,390 @@
+// Outgoing webhooks, which tell other tools when tests are created, data points are ingested,
+// alerts fire or resolve, and schedule entries change.
+// Each event is posted to every webhook that wants it as JSON, signed with the webhook's secret:
+//     X-Webhook-Signature: sha256=<hex HMAC-SHA256 of the body>
+// Deliveries wait in a queue under ./data until they are accepted, and are tried again less and less often
+// until they have failed MAX_ATTEMPTS times. Every attempt is kept in the delivery log
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use rocket::Shutdown;
+use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+use rocket::tokio::net::TcpStream;
+use rocket::tokio::select;
+use rocket::tokio::sync::{Mutex, Notify};
+use rocket::tokio::time::{sleep, timeout};
+use time::OffsetDateTime;
+use crate::json::Json;
+use crate::keyed_vector::KeyedVector;
+use crate::models::{AlertEvent, DataPoint, DeliveryAttempt, DeliveryOutcome, ScheduleEntry, SiteData, Webhook, WebhookDelivery, WebhookEvent};
+use crate::my_vector::MyVector;
+use crate::timestamp::Timestamp;
+
+pub const WEBHOOKS_PATH: &str = "./data/webhooks/subscriptions";
+pub const WEBHOOK_DATA_DIRECTORY: &str = "./data/webhooks"; // Holds the queue and the delivery log
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 10;
+const RETRY_DELAY: i64 = 10; // Seconds after the first failure, doubling after each one
+const MAX_RETRY_DELAY: i64 = 3600;
+const IDLE_CHECK: Duration = Duration::from_secs(60); // How often the queue is looked at with nothing due
+
+// Where a webhook's URL points, which must be plain HTTP
+#[derive(Debug, PartialEq)]
+struct Target {
+    host: String, // With the port, as sent in the Host header
+    path: String,
+}
+
+impl Target {
+    fn parse(url: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("http://").ok_or("must start with http://")?;
+        let (host, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        if host.is_empty() {
+            return Err("has no host".to_string());
+        }
+        if url.contains(|c: char| c.is_whitespace() || c.is_control() || c == ',') {
+            return Err("cannot contain spaces or commas".to_string());
+        }
+        let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+        Ok(Self { host, path: path.to_string() })
+    }
+}
+
+// Whether a URL can be posted to, as the message to show next to the field
+pub fn check_url(url: &str) -> Result<(), String> {
+    Target::parse(url).map(|_| ())
+}
+
+// The signature header's value for a body
+pub fn sign(secret: &str, body: &str) -> String {
+    let mac = hmac_sha256::HMAC::mac(body.as_bytes(), secret.as_bytes());
+    let hex: String = mac.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", hex)
+}
+
+// The data of an event, as a JSON object
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+}
+
+pub fn text(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+pub fn test_data(server_id: &str, test_id: &str) -> Json {
+    object(vec![("server_id", text(server_id)), ("test_id", text(test_id))])
+}
+
+// Data points added to a test at once, oldest first, which cannot be empty
+pub fn ingested_data(server_id: &str, test_id: &str, data_points: &[DataPoint]) -> Json {
+    object(vec![
+        ("server_id", text(server_id)),
+        ("test_id", text(test_id)),
+        ("count", Json::Number(data_points.len().to_string())),
+        ("first_time", text(&data_points[0].get_time().to_string())),
+        ("last_time", text(&data_points[data_points.len() - 1].get_time().to_string())),
+    ])
+}
+
+pub fn alert_data(alert: &AlertEvent) -> Json {
+    object(vec![
+        ("rule_id", text(&alert.get_rule_id())),
+        ("server_id", text(&alert.get_server_id())),
+        ("test_id", text(&alert.get_test_id())),
+        ("time", text(&alert.get_time().to_string())),
+        ("value", Json::Number(alert.get_value().to_string())),
+        ("threshold", Json::Number(alert.get_limit().to_string())),
+    ])
+}
+
+pub fn schedule_data(schedule: &ScheduleEntry) -> Json {
+    object(vec![
+        ("id", text(&schedule.get_id())),
+        ("datetime", text(&schedule.get_datetime().to_string())),
+        ("assignees", Json::Array(schedule.get_assignees().iter().map(|username| text(username)).collect())),
+        ("server_id", text(&schedule.get_server_id())),
+        ("test_id", text(&schedule.get_test_id())),
+    ])
+}
+
+// The retry queue, and the log of every attempt to send from it
+pub struct Webhooks {
+    queue: Mutex<KeyedVector<WebhookDelivery>>, // Keyed by ID, which is in the order they were queued
+    queue_path: String,
+    log_path: String,
+    wake: Notify, // Told when a delivery is queued
+    next_id: AtomicU64,
+}
+
+impl Webhooks {
+    pub async fn load(directory: &str) -> Self {
+        let queue_path = format!("{}/queue", directory);
+        let log_path = format!("{}/deliveries", directory);
+        let queue = KeyedVector::load_from_file(&queue_path).await;
+        Self { queue: Mutex::new(queue), queue_path, log_path, wake: Notify::new(), next_id: AtomicU64::new(0) }
+    }
+
+    // Queue an event for each webhook that wants it
+    pub async fn send(&self, subscriptions: &[Webhook], event: WebhookEvent, data: Json) {
+        let now = Timestamp::from_unix_seconds(Timestamp::now().unix_seconds()).unwrap();
+        let mut queue = self.queue.lock().await;
+        let mut queued = false;
+        for webhook in subscriptions.iter().filter(|webhook| webhook.wants(event)) {
+            // Sorts in the order queued, and is unique across restarts
+            let id = format!("{:024}-{}", OffsetDateTime::now_utc().unix_timestamp_nanos(), self.next_id.fetch_add(1, Ordering::Relaxed));
+            let payload = object(vec![("id", text(&id)), ("event", text(&event.to_string())), ("time", text(&now.to_string())), ("data", data.clone())]);
+            let _ = queue.insert(WebhookDelivery::new(id, webhook.get_id(), event, now, payload.to_string())).await; // IDs are unique
+            queued = true;
+        }
+
+        if queued {
+            queue.save_to_file(&self.queue_path).await.expect("Failed to save the webhook queue!");
+            self.wake.notify_one();
+        }
+    }
+
+    // Send every delivery that is due, one at a time and oldest first, returning when the next one is due.
+    // Deliveries stay in the saved queue while they are sent, so none are lost if the server stops
+    pub async fn deliver_due(&self, subscriptions: &KeyedVector<Webhook>, now: Timestamp) -> Option<Timestamp> {
+        let due: Vec<WebhookDelivery> = {
+            let queue = self.queue.lock().await;
+            queue.as_slice().await.iter().filter(|delivery| delivery.get_next_attempt() <= now).cloned().collect()
+        };
+
+        let mut attempts = MyVector::new();
+        for mut delivery in due {
+            let webhook = subscriptions.get_by_key(&delivery.get_webhook_id()).await;
+            let (status, outcome, message) = match &webhook {
+                Some(webhook) => match post(webhook, &delivery).await {
+                    Ok(status) if (200..300).contains(&status) => (Some(status), DeliveryOutcome::Delivered, String::new()),
+                    Ok(status) => (Some(status), self.failed(&delivery), format!("The webhook responded {}", status)),
+                    Err(error) => (None, self.failed(&delivery), error),
+                },
+                None => (None, DeliveryOutcome::Failed, "The webhook was removed".to_string()),
+            };
+            attempts.push(DeliveryAttempt::new(now, &delivery, status, outcome, &message)).await;
+
+            let mut queue = self.queue.lock().await;
+            queue.remove_by_key(&delivery.get_id()).await;
+            if outcome == DeliveryOutcome::Retrying {
+                let delay = (RETRY_DELAY << delivery.get_attempts().min(20)).min(MAX_RETRY_DELAY);
+                delivery.retry_at(Timestamp::from_unix_seconds(now.unix_seconds() + delay).unwrap());
+                let _ = queue.insert(delivery).await; // Removed just above
+            }
+            queue.save_to_file(&self.queue_path).await.expect("Failed to save the webhook queue!");
+        }
+        if attempts.length > 0 {
+            attempts.append_to_file(&self.log_path).await.expect("Failed to save the webhook delivery log!");
+        }
+
+        let queue = self.queue.lock().await;
+        queue.as_slice().await.iter().map(|delivery| delivery.get_next_attempt()).min()
+    }
+
+    fn failed(&self, delivery: &WebhookDelivery) -> DeliveryOutcome {
+        if delivery.get_attempts() + 1 >= MAX_ATTEMPTS {
+            DeliveryOutcome::Failed
+        } else {
+            DeliveryOutcome::Retrying
+        }
+    }
+
+    // Send deliveries as they become due, until the server stops
+    pub async fn run(self: Arc<Self>, site_data: Arc<Mutex<SiteData>>, mut shutdown: Shutdown) {
+        loop {
+            let subscriptions = site_data.lock().await.webhooks.clone(); // Not held while sending
+            let next = self.deliver_due(&subscriptions, Timestamp::now()).await;
+
+            let wait = match next {
+                Some(next) => Duration::from_secs((next.unix_seconds() - Timestamp::now().unix_seconds()).max(1) as u64).min(IDLE_CHECK),
+                None => IDLE_CHECK,
+            };
+            select! {
+                _ = self.wake.notified() => {}
+                _ = sleep(wait) => {}
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+
+    // The latest attempts to send to a webhook, newest first
+    pub async fn attempts(&self, webhook_id: &str, limit: usize) -> Vec<DeliveryAttempt> {
+        let log: MyVector<DeliveryAttempt> = MyVector::load_from_file(&self.log_path).await;
+        log.as_slice().await.iter().rev().filter(|attempt| attempt.get_webhook_id() == webhook_id).take(limit).cloned().collect()
+    }
+
+    // How many deliveries are waiting for a webhook
+    pub async fn pending(&self, webhook_id: &str) -> usize {
+        let queue = self.queue.lock().await;
+        queue.as_slice().await.iter().filter(|delivery| delivery.get_webhook_id() == webhook_id).count()
+    }
+}
+
+// Post a delivery to its webhook, returning the status code of the response
+async fn post(webhook: &Webhook, delivery: &WebhookDelivery) -> Result<u16, String> {
+    let target = Target::parse(&webhook.get_url()).map_err(|error| format!("The URL {}", error))?;
+    let body = delivery.get_payload();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nX-Webhook-Event: {}\r\nX-Webhook-Delivery: {}\r\nX-Webhook-Signature: {}\r\nConnection: close\r\n\r\n{}",
+        target.path,
+        target.host,
+        body.len(),
+        delivery.get_event(),
+        delivery.get_id(),
+        sign(&webhook.get_secret(), body),
+        body
+    );
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(&target.host).await.map_err(|error| format!("Cannot connect to {}: {}", target.host, error))?;
+        stream.write_all(request.as_bytes()).await.map_err(|error| format!("Cannot send to {}: {}", target.host, error))?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.map_err(|error| format!("No response from {}: {}", target.host, error))?;
+        Ok::<Vec<u8>, String>(response)
+    };
+    let response = timeout(TIMEOUT, exchange).await.map_err(|_| format!("{} did not respond within {} s", target.host, TIMEOUT.as_secs()))??;
+
+    String::from_utf8_lossy(&response)
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("HTTP/1."))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| "The response was not HTTP".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_url, object, sign, text, Target, Webhooks, MAX_ATTEMPTS};
+    use std::str::FromStr;
+    use rocket::tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use rocket::tokio::net::TcpListener;
+    use rocket::tokio::task::JoinHandle;
+    use crate::json::Json;
+    use crate::keyed_vector::KeyedVector;
+    use crate::models::{DeliveryOutcome, Webhook, WebhookEvent};
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn test_target() {
+        assert_eq!(Target::parse("http://localhost:9000/hooks?a=1").unwrap(), Target { host: "localhost:9000".to_string(), path: "/hooks?a=1".to_string() });
+        assert_eq!(Target::parse("http://example.com").unwrap(), Target { host: "example.com:80".to_string(), path: "/".to_string() });
+        assert_eq!(check_url("https://example.com"), Err("must start with http://".to_string()));
+        assert!(check_url("http://example.com/a,b").is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(sign("Jefe", "what do ya want for nothing?"), "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    // Answers each request with the next status, and returns the requests it was sent, headers and all
+    async fn stand_in(statuses: Vec<u16>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = rocket::tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+                reader.get_mut().write_all(format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn directory(name: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("webhooks_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory.to_string_lossy().to_string()
+    }
+
+    #[rocket::async_test]
+    async fn test_delivery_and_retry() {
+        let (url, handle) = stand_in(vec![500, 204]).await;
+        let mut subscriptions = KeyedVector::new();
+        let _ = subscriptions.insert(Webhook::new("ci".to_string(), url, vec![WebhookEvent::TestCreated], "secret".to_string())).await;
+        let _ = subscriptions.insert(Webhook::new("other".to_string(), "http://127.0.0.1:1/".to_string(), vec![WebhookEvent::AlertFired], "x".to_string())).await;
+
+        let directory = directory("retry");
+        let webhooks = Webhooks::load(&directory).await;
+        let data = object(vec![("server_id", text("web")), ("test_id", text("load"))]);
+        webhooks.send(subscriptions.as_slice().await, WebhookEvent::TestCreated, data).await;
+        assert_eq!((webhooks.pending("ci").await, webhooks.pending("other").await), (1, 0)); // Only sent to the webhook that wants it
+
+        // The first attempt fails, so it is tried again 10 s later, also after a restart
+        let now = Timestamp::now();
+        let next = webhooks.deliver_due(&subscriptions, now).await.unwrap();
+        assert_eq!(next.unix_seconds(), now.unix_seconds() + 10);
+        let webhooks = Webhooks::load(&directory).await;
+        assert_eq!(webhooks.deliver_due(&subscriptions, now).await, Some(next)); // Not due yet
+        assert_eq!(webhooks.deliver_due(&subscriptions, next).await, None);
+
+        let attempts = webhooks.attempts("ci", 10).await;
+        assert_eq!(attempts.iter().map(|attempt| (attempt.get_attempt(), attempt.get_status(), attempt.get_outcome())).collect::<Vec<_>>(), vec![
+            (2, Some(204), DeliveryOutcome::Delivered),
+            (1, Some(500), DeliveryOutcome::Retrying),
+        ]);
+
+        let requests = handle.await.unwrap();
+        assert_eq!(requests[0], requests[1]); // The same delivery again
+        let (headers, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        assert!(headers.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(headers.contains("X-Webhook-Event: test.created\r\n"));
+        assert!(headers.contains(&format!("X-Webhook-Signature: {}\r\n", sign("secret", body))));
+        let payload = Json::parse(body).unwrap();
+        let Json::Object(fields) = payload else { panic!("Expected an object") };
+        assert_eq!(fields[1], ("event".to_string(), text("test.created")));
+        assert_eq!(fields[3].1.to_string(), r#"{"server_id":"web","test_id":"load"}"#);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_gives_up() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener); // Nothing is listening any more
+
+        let mut subscriptions = KeyedVector::new();
+        let _ = subscriptions.insert(Webhook::new("ci".to_string(), url, WebhookEvent::ALL.to_vec(), "secret".to_string())).await;
+        let directory = directory("gives_up");
+        let webhooks = Webhooks::load(&directory).await;
+        webhooks.send(subscriptions.as_slice().await, WebhookEvent::AlertFired, Json::Null).await;
+
+        let mut now = Timestamp::from_str("2030-01-01T00:00:00Z").unwrap();
+        for _ in 0..MAX_ATTEMPTS {
+            now = webhooks.deliver_due(&subscriptions, now).await.unwrap_or(now);
+        }
+        assert_eq!(webhooks.pending("ci").await, 0);
+        let attempts = webhooks.attempts("ci", 2).await;
+        assert_eq!(attempts[0].get_outcome(), DeliveryOutcome::Failed);
+        assert_eq!(attempts[1].get_outcome(), DeliveryOutcome::Retrying);
+        assert!(attempts[0].get_message().starts_with("Cannot connect"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
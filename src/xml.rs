@@ -0,0 +1,316 @@
+// A small XML reader, for test reports such as JUnit's.
+// It reads elements, attributes, text, CDATA and the standard entities, and skips comments,
+// processing instructions and any DOCTYPE. Namespaces are not understood, so prefixes stay part of names
+
+// Deeper than any report goes, and keeps a hostile document from overflowing the stack
+const MAX_DEPTH: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>, // In the order written
+    pub children: Vec<Element>,
+    pub text: String, // All the text directly inside the element, joined
+}
+
+impl Element {
+    // Read a whole document, which must have a single root element
+    pub fn parse(text: &str) -> Result<Element, String> {
+        let mut reader = Reader { chars: text.trim_start_matches('\u{feff}').chars().collect(), position: 0 };
+        reader.skip_misc()?;
+        if !reader.starts_with("<") {
+            return Err(reader.error("expected an element"));
+        }
+        let root = reader.element(0)?;
+        reader.skip_misc()?;
+        if reader.position < reader.chars.len() {
+            return Err(reader.error("expected the end of the document"));
+        }
+        Ok(root)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct Reader {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Reader {
+    // Positions are given as lines and columns, as reports are usually looked at in an editor
+    fn error(&self, message: &str) -> String {
+        let before = &self.chars[..self.position.min(self.chars.len())];
+        let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        format!("{} at line {}, column {}", message, line, column)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        (self.position..).zip(text.chars()).all(|(position, expected)| self.chars.get(position) == Some(&expected))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        if !self.starts_with(expected) {
+            return Err(self.error(&format!("expected '{}'", expected)));
+        }
+        self.position += expected.chars().count();
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    // Move past the next end, returning what came before it
+    fn read_until(&mut self, end: &str, what: &str) -> Result<String, String> {
+        let start = self.position;
+        while !self.starts_with(end) {
+            if self.peek().is_none() {
+                self.position = start;
+                return Err(self.error(&format!("unterminated {}", what)));
+            }
+            self.position += 1;
+        }
+        let text = self.chars[start..self.position].iter().collect();
+        self.position += end.chars().count();
+        Ok(text)
+    }
+
+    // What can come before and after the root element
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("<?") {
+                self.read_until("?>", "processing instruction")?;
+            } else if self.starts_with("<!--") {
+                self.read_until("-->", "comment")?;
+            } else if self.starts_with("<!DOCTYPE") {
+                self.skip_doctype()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // Including any internal subset in [], whose declarations can contain '>'
+    fn skip_doctype(&mut self) -> Result<(), String> {
+        let start = self.position;
+        let mut in_subset = false;
+        loop {
+            match self.peek() {
+                Some('[') => in_subset = true,
+                Some(']') => in_subset = false,
+                Some('>') if !in_subset => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => {
+                    self.position = start;
+                    return Err(self.error("unterminated DOCTYPE"));
+                }
+            }
+            self.position += 1;
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let start = self.position;
+        match self.peek() {
+            Some(c) if c.is_alphabetic() || c == '_' || c == ':' => self.position += 1,
+            _ => return Err(self.error("expected a name")),
+        }
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.')) {
+                break;
+            }
+            self.position += 1;
+        }
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn element(&mut self, depth: usize) -> Result<Element, String> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error("elements are nested too deeply"));
+        }
+        self.expect("<")?;
+        let name = self.name()?;
+        let mut element = Element { name, attributes: Vec::new(), children: Vec::new(), text: String::new() };
+
+        loop {
+            let had_whitespace = matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r'));
+            self.skip_whitespace();
+            if self.starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if self.peek() == Some('>') {
+                self.position += 1;
+                break;
+            }
+            if !had_whitespace {
+                return Err(self.error("expected whitespace, '>' or '/>'"));
+            }
+            let attribute_start = self.position;
+            let (key, value) = self.attribute()?;
+            if element.attribute(&key).is_some() {
+                self.position = attribute_start;
+                return Err(self.error(&format!("attribute '{}' is given twice", key)));
+            }
+            element.attributes.push((key, value));
+        }
+
+        loop {
+            if self.starts_with("</") {
+                self.position += 2;
+                let closing_start = self.position;
+                let closing = self.name()?;
+                if closing != element.name {
+                    self.position = closing_start;
+                    return Err(self.error(&format!("expected '</{}>'", element.name)));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if self.starts_with("<!--") {
+                self.read_until("-->", "comment")?;
+            } else if self.starts_with("<![CDATA[") {
+                self.position += 9;
+                let text = self.read_until("]]>", "CDATA section")?;
+                element.text.push_str(&text);
+            } else if self.starts_with("<?") {
+                self.read_until("?>", "processing instruction")?;
+            } else if self.starts_with("<") {
+                element.children.push(self.element(depth + 1)?);
+            } else if self.peek().is_some() {
+                let text = self.text()?;
+                element.text.push_str(&text);
+            } else {
+                return Err(self.error(&format!("expected '</{}>'", element.name)));
+            }
+        }
+    }
+
+    fn attribute(&mut self) -> Result<(String, String), String> {
+        let key = self.name()?;
+        self.skip_whitespace();
+        self.expect("=")?;
+        self.skip_whitespace();
+        let quote = match self.peek() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error("expected a quoted value")),
+        };
+        self.position += 1;
+
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == quote => {
+                    self.position += 1;
+                    return Ok((key, value));
+                }
+                Some('<') => return Err(self.error("'<' must be written as &lt;")),
+                Some('&') => value.push(self.entity()?),
+                Some('\t' | '\n' | '\r') => { // Line breaks in a value are read as spaces
+                    value.push(' ');
+                    self.position += 1;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.position += 1;
+                }
+                None => return Err(self.error("unterminated attribute value")),
+            }
+        }
+    }
+
+    fn text(&mut self) -> Result<String, String> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '<' => break,
+                '&' => text.push(self.entity()?),
+                c => {
+                    text.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    fn entity(&mut self) -> Result<char, String> {
+        let start = self.position;
+        self.expect("&")?;
+        let name = self.read_until(";", "entity").map_err(|_| {
+            self.position = start;
+            self.error("expected ';' after the entity")
+        })?;
+        let c = match name.as_str() {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => name.strip_prefix('#').and_then(|decimal| decimal.parse().ok()).and_then(char::from_u32),
+            },
+        };
+        c.ok_or_else(|| {
+            self.position = start;
+            self.error(&format!("unknown entity '&{};'", name))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Element;
+
+    #[test]
+    fn test_parse() {
+        let xml = "\u{feff}<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                   <!-- Written by a test runner -->\n\
+                   <suite name='a &amp; b' tests=\"2\">\n\
+                     <case name=\"one\"/>\n\
+                     <case name=\"two\">Not &lt;ok&gt; &#233;&#x1F600;<![CDATA[ <raw> ]]></case>\n\
+                   </suite>\n";
+        let root = Element::parse(xml).unwrap();
+        assert_eq!(root.name, "suite");
+        assert_eq!(root.attribute("name"), Some("a & b"));
+        assert_eq!(root.attribute("missing"), None);
+        let cases: Vec<&Element> = root.children.iter().filter(|child| child.name == "case").collect();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].attribute("name"), Some("one"));
+        assert_eq!(cases[1].text, "Not <ok> é😀 <raw> ");
+    }
+
+    #[test]
+    fn test_doctype_is_skipped() {
+        let root = Element::parse("<!DOCTYPE a [<!ELEMENT a (#PCDATA)>]><a/>").unwrap();
+        assert_eq!(root.name, "a");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Element::parse("<a>\n  <b></a>").unwrap_err(), "expected '</b>' at line 2, column 8");
+        assert_eq!(Element::parse("<a x=\"1\" x=\"2\"/>").unwrap_err(), "attribute 'x' is given twice at line 1, column 10");
+        assert_eq!(Element::parse("<a>&nbsp;</a>").unwrap_err(), "unknown entity '&nbsp;' at line 1, column 4");
+        assert_eq!(Element::parse("<a/><b/>").unwrap_err(), "expected the end of the document at line 1, column 5");
+        for invalid in ["", "text", "<a>", "<a x=1/>", "<a x=\"<\"/>", "<a><!-- </a>", "<1/>"] {
+            assert!(Element::parse(invalid).is_err(), "{}", invalid);
+        }
+        let deep = "<a>".repeat(1000) + &"</a>".repeat(1000);
+        assert!(Element::parse(&deep).unwrap_err().starts_with("elements are nested too deeply"));
+    }
+}